@@ -1,14 +1,17 @@
-use kiyo::app::app::{App, AppConfig};
-use kiyo::app::draw_orch::{DispatchConfig, DrawConfig, Pass};
+use kiyo::app::app::{App, AppConfig, FilterMode, ScalingMode};
+use kiyo::app::draw_orch::{DispatchConfig, DrawConfig, Output, Pass};
+use kiyo::app::params::{Param, ParamValue};
 
-fn main() {
+fn main() -> Result<(), kiyo::Error> {
 
     let app = App::new(AppConfig {
         width: 1000,
         height: 1000,
         vsync: true,
         log_fps: false,
-    });
+        scaling: ScalingMode::Stretch,
+        filter: FilterMode::Nearest,
+    })?;
 
     let mut config = DrawConfig::new();
     config.passes = Vec::from([
@@ -17,14 +20,35 @@ fn main() {
             dispatches: DispatchConfig::FullScreen,
             input_resources: Vec::from([]),
             output_resources: Vec::from([ 0 ]),
+            input_buffers: Vec::from([]),
+            output_buffers: Vec::from([]),
+            workgroup_size: None,
+            entry_point: None,
         },
         Pass {
             shader: "examples/blur-pass/shaders/blur.comp".to_string(),
             dispatches: DispatchConfig::FullScreen,
             input_resources: Vec::from([ 0 ]),
             output_resources: Vec::from([ 1 ]),
+            input_buffers: Vec::from([]),
+            output_buffers: Vec::from([]),
+            workgroup_size: None,
+            entry_point: None,
         }
     ]);
+    config.output = Output::Image( 1 );
+    config.params = Vec::from([
+        Param::new("range", ParamValue::Int( 2 )).with_range(0.0, 8.0),
+    ]);
+
+    // Grow the blur radius every second
+    let params = app.params();
+    std::thread::spawn(move || {
+        for range in (0..=8).cycle() {
+            params.set("range", ParamValue::Int( range ));
+            std::thread::sleep(std::time::Duration::from_secs(1));
+        }
+    });
 
-    app.run(config, None);
+    app.run(config, None)
 }
@@ -0,0 +1,39 @@
+use kiyo::app::app::{App, AppConfig, FilterMode, ScalingMode};
+use kiyo::app::draw_orch::{DispatchConfig, DrawConfig, ImageFormat, ImageResource, ImageSize, Pass, Persistence};
+
+fn main() -> Result<(), kiyo::Error> {
+
+    let app = App::new(AppConfig {
+        width: 1000,
+        height: 1000,
+        vsync: true,
+        log_fps: false,
+        scaling: ScalingMode::Stretch,
+        filter: FilterMode::Nearest,
+    })?;
+
+    let mut config = DrawConfig::new();
+    config.images = Vec::from([
+        ImageResource {
+            id: 0,
+            format: ImageFormat::Rgba16f,
+            size: ImageSize::Relative(1.0),
+            mip_levels: 1,
+            persistence: Persistence::PingPong,
+        },
+    ]);
+    config.passes = Vec::from([
+        Pass {
+            shader: "examples/feedback/shaders/trail.comp".to_string(),
+            dispatches: DispatchConfig::FullScreen,
+            input_resources: Vec::from([]),
+            output_resources: Vec::from([ 0 ]),
+            input_buffers: Vec::from([]),
+            output_buffers: Vec::from([]),
+            workgroup_size: None,
+            entry_point: None,
+        },
+    ]);
+
+    app.run(config, None)
+}
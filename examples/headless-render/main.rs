@@ -0,0 +1,30 @@
+use kiyo::app::draw_orch::{DispatchConfig, DrawConfig, Pass};
+use kiyo::app::headless::{HeadlessApp, HeadlessConfig, ImageFileFormat};
+
+fn main() -> Result<(), kiyo::Error> {
+
+    let app = HeadlessApp::new(HeadlessConfig {
+        width: 1000,
+        height: 1000,
+        frame_count: 60,
+        time_step: 1.0 / 30.0,
+        output_dir: "output".to_string(),
+        file_format: ImageFileFormat::Png,
+    })?;
+
+    let mut config = DrawConfig::new();
+    config.passes = Vec::from([
+        Pass {
+            shader: "examples/with-audio/shaders/colors.comp".to_string(),
+            dispatches: DispatchConfig::FullScreen,
+            input_resources: Vec::from([]),
+            output_resources: Vec::from([ 0 ]),
+            input_buffers: Vec::from([]),
+            output_buffers: Vec::from([]),
+            workgroup_size: None,
+            entry_point: None,
+        },
+    ]);
+
+    app.run(config)
+}
@@ -0,0 +1,48 @@
+use kiyo::app::app::{App, AppConfig, FilterMode, ScalingMode};
+use kiyo::app::draw_orch::{BufferResource, BufferSize, DispatchConfig, DrawConfig, Pass};
+
+fn main() -> Result<(), kiyo::Error> {
+
+    let app = App::new(AppConfig {
+        width: 1000,
+        height: 1000,
+        vsync: true,
+        log_fps: false,
+        scaling: ScalingMode::Stretch,
+        filter: FilterMode::Nearest,
+    })?;
+
+    let mut config = DrawConfig::new();
+    config.buffers = Vec::from([
+        // Indirect dispatch arguments followed by the particle count
+        BufferResource {
+            id: 0,
+            size: BufferSize::Elements { count: 4, stride: 4 },
+            initial_data: None,
+        },
+    ]);
+    config.passes = Vec::from([
+        Pass {
+            shader: "examples/particles/shaders/spawn.comp".to_string(),
+            dispatches: DispatchConfig::Count( 1, 1, 1 ),
+            input_resources: Vec::from([]),
+            output_resources: Vec::from([]),
+            input_buffers: Vec::from([]),
+            output_buffers: Vec::from([ 0 ]),
+            workgroup_size: Some(( 1, 1, 1 )),
+            entry_point: None,
+        },
+        Pass {
+            shader: "examples/particles/shaders/particles.comp".to_string(),
+            dispatches: DispatchConfig::Indirect { buffer: 0, offset: 0 },
+            input_resources: Vec::from([]),
+            output_resources: Vec::from([ 0 ]),
+            input_buffers: Vec::from([ 0 ]),
+            output_buffers: Vec::from([]),
+            workgroup_size: Some(( 256, 1, 1 )),
+            entry_point: None,
+        },
+    ]);
+
+    app.run(config, None)
+}
@@ -0,0 +1,42 @@
+use kiyo::app::app::{App, AppConfig, FilterMode, ScalingMode};
+use kiyo::app::draw_orch::{DispatchConfig, DrawConfig, Output, Pass};
+
+fn main() -> Result<(), kiyo::Error> {
+
+    let app = App::new(AppConfig {
+        width: 1000,
+        height: 1000,
+        vsync: true,
+        log_fps: false,
+        scaling: ScalingMode::Stretch,
+        filter: FilterMode::Nearest,
+    })?;
+
+    // A WGSL pass draws a gradient, a HLSL pass darkens its corners
+    let mut config = DrawConfig::new();
+    config.passes = Vec::from([
+        Pass {
+            shader: "examples/shader-languages/shaders/gradient.wgsl".to_string(),
+            dispatches: DispatchConfig::FullScreen,
+            input_resources: Vec::from([]),
+            output_resources: Vec::from([ 0 ]),
+            input_buffers: Vec::from([]),
+            output_buffers: Vec::from([]),
+            workgroup_size: None,
+            entry_point: None,
+        },
+        Pass {
+            shader: "examples/shader-languages/shaders/vignette.hlsl".to_string(),
+            dispatches: DispatchConfig::FullScreen,
+            input_resources: Vec::from([ 0 ]),
+            output_resources: Vec::from([ 1 ]),
+            input_buffers: Vec::from([]),
+            output_buffers: Vec::from([]),
+            workgroup_size: None,
+            entry_point: Some("CSMain".to_string()),
+        },
+    ]);
+    config.output = Output::Image( 1 );
+
+    app.run(config, None)
+}
@@ -0,0 +1,37 @@
+/*
+ * Kiyo data
+ * - WGSL has no preprocessor, so the engine's macros aren't available:
+ *   the workgroup size and the amount of images are declared here
+ * - The push constants match the ones of <kiyo/engine.glsl>
+ */
+
+struct PushConstants {
+    time: f32,
+    in_image: i32,
+    out_image: i32,
+    mouse_buttons: u32,
+    mouse: vec4<f32>,
+    scroll: vec2<f32>,
+    keys: vec2<u32>,
+}
+
+var<push_constant> constants: PushConstants;
+@group(0) @binding(0) var images: binding_array<texture_storage_2d<rgba8unorm, write>, 2>;
+
+/*
+ * User data
+ */
+
+@compute @workgroup_size(16, 16, 1)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = textureDimensions(images[constants.out_image]);
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+
+    let pos = vec2<f32>(id.xy) / vec2<f32>(size) - 0.5;
+    let angle = atan2(pos.y, pos.x) + constants.time * 0.5;
+    let color = 0.5 + 0.5 * cos(angle + vec3<f32>(0.0, 2.1, 4.2));
+
+    textureStore(images[constants.out_image], vec2<i32>(id.xy), vec4<f32>(color, 1.0));
+}
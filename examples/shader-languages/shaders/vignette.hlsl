@@ -0,0 +1,40 @@
+/*
+ * Kiyo data
+ * - WORKGROUP_SIZE_X, WORKGROUP_SIZE_Y and NUM_IMAGES are provided by the engine
+ * - The push constants match the ones of <kiyo/engine.glsl>
+ */
+
+struct PushConstants
+{
+    float time;
+    int in_image;
+    int out_image;
+    uint mouse_buttons;
+    float4 mouse;
+    float2 scroll;
+    uint2 keys;
+};
+
+[[vk::push_constant]] PushConstants constants;
+[[vk::binding( 0 )]] [[vk::image_format( "rgba8" )]] RWTexture2D<float4> images[NUM_IMAGES];
+
+/*
+ * User data
+ */
+
+[numthreads( WORKGROUP_SIZE_X, WORKGROUP_SIZE_Y, 1 )]
+void CSMain( uint3 id : SV_DispatchThreadID )
+{
+    uint width, height;
+    images[constants.out_image].GetDimensions( width, height );
+    if( id.x >= width || id.y >= height )
+    {
+        return;
+    }
+
+    float2 pos = ( float2( id.xy ) + 0.5f ) / float2( width, height ) - 0.5f;
+    float vignette = smoothstep( 0.75f, 0.2f, length( pos ) );
+
+    float4 color = images[constants.in_image][id.xy];
+    images[constants.out_image][id.xy] = float4( color.rgb * vignette, 1.0f );
+}
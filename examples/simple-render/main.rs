@@ -1,14 +1,16 @@
-use kiyo::app::app::{App, AppConfig};
+use kiyo::app::app::{App, AppConfig, FilterMode, ScalingMode};
 use kiyo::app::draw_orch::{DispatchConfig, DrawConfig, Pass};
 
-fn main() {
+fn main() -> Result<(), kiyo::Error> {
 
     let app = App::new(AppConfig {
         width: 1000,
         height: 1000,
         vsync: true,
         log_fps: false,
-    });
+        scaling: ScalingMode::Stretch,
+        filter: FilterMode::Nearest,
+    })?;
 
     let mut config = DrawConfig::new();
     config.passes = Vec::from([
@@ -17,8 +19,12 @@ fn main() {
             dispatches: DispatchConfig::FullScreen,
             input_resources: Vec::from([]),
             output_resources: Vec::from([ 0 ]),
+            input_buffers: Vec::from([]),
+            output_buffers: Vec::from([]),
+            workgroup_size: None,
+            entry_point: None,
         },
     ]);
 
-    app.run(config, None);
+    app.run(config, None)
 }
@@ -0,0 +1,37 @@
+/*
+ * Kiyo engine data, include with `#include <kiyo/engine.glsl>`
+ */
+
+#ifndef KIYO_ENGINE_GLSL
+#define KIYO_ENGINE_GLSL
+
+// Per pass values
+layout( push_constant ) uniform PushConstants
+{
+    float time;         // Seconds since startup
+    int in_image;       // First input image of the pass, -1 if none
+    int out_image;      // First output image of the pass, -1 if none
+    uint mouse_buttons; // Held mouse buttons: left 1, right 2, middle 4
+    vec4 mouse;         // Shadertoy style iMouse, in output image pixels
+    vec2 scroll;        // Scroll distance in lines during this frame
+    uvec2 keys;         // Bitmask of held keys
+} constants;
+
+// Per frame values
+layout( binding = 2, std140 ) uniform Engine
+{
+    float time;              // Seconds since startup
+    float delta_time;        // Seconds since the previous frame
+    uint frame;              // Frames rendered since startup
+    float sample_rate;       // Audio sample rate, 0 without audio
+    uvec2 resolution;        // Resolution relative image sizes are based on
+    uvec2 output_resolution; // Size of the output image
+    vec4 date;               // Year, month, day and seconds since midnight, in UTC
+} engine;
+
+// Parameters declared in DrawConfig::params, accessible as params.<name>
+#ifdef PARAM_BINDINGS
+PARAM_BINDINGS
+#endif
+
+#endif
@@ -0,0 +1,103 @@
+/*
+ * Kiyo hashes and noise, include with `#include <kiyo/noise.glsl>`
+ */
+
+#ifndef KIYO_NOISE_GLSL
+#define KIYO_NOISE_GLSL
+
+// Integer hash, uniformly distributed over all 32 bits
+uint hash_uint( uint x )
+{
+    x ^= x >> 16;
+    x *= 0x7feb352du;
+    x ^= x >> 15;
+    x *= 0x846ca68bu;
+    x ^= x >> 16;
+    return x;
+}
+
+uint hash_uint( uvec2 v )
+{
+    return hash_uint( v.x ^ hash_uint( v.y ) );
+}
+
+uint hash_uint( uvec3 v )
+{
+    return hash_uint( v.x ^ hash_uint( v.y ^ hash_uint( v.z ) ) );
+}
+
+// Hashes in [0, 1]
+float hash( uint x )
+{
+    return float( hash_uint( x ) ) / 4294967295.0f;
+}
+
+float hash( uvec2 v )
+{
+    return float( hash_uint( v ) ) / 4294967295.0f;
+}
+
+float hash( uvec3 v )
+{
+    return float( hash_uint( v ) ) / 4294967295.0f;
+}
+
+// Value noise in [0, 1], with a smooth interpolation between the lattice points
+float value_noise( vec2 p )
+{
+    uvec2 i = uvec2( ivec2( floor( p ) ) );
+    vec2 f = fract( p );
+    vec2 u = f * f * ( 3.0f - 2.0f * f );
+
+    float a = hash( i );
+    float b = hash( i + uvec2( 1, 0 ) );
+    float c = hash( i + uvec2( 0, 1 ) );
+    float d = hash( i + uvec2( 1, 1 ) );
+    return mix( mix( a, b, u.x ), mix( c, d, u.x ), u.y );
+}
+
+float value_noise( vec3 p )
+{
+    uvec3 i = uvec3( ivec3( floor( p ) ) );
+    vec3 f = fract( p );
+    vec3 u = f * f * ( 3.0f - 2.0f * f );
+
+    float a = mix( hash( i ), hash( i + uvec3( 1, 0, 0 ) ), u.x );
+    float b = mix( hash( i + uvec3( 0, 1, 0 ) ), hash( i + uvec3( 1, 1, 0 ) ), u.x );
+    float c = mix( hash( i + uvec3( 0, 0, 1 ) ), hash( i + uvec3( 1, 0, 1 ) ), u.x );
+    float d = mix( hash( i + uvec3( 0, 1, 1 ) ), hash( i + uvec3( 1, 1, 1 ) ), u.x );
+    return mix( mix( a, b, u.y ), mix( c, d, u.y ), u.z );
+}
+
+// Fractal noise in [0, 1], summing octaves of value noise
+float fbm( vec2 p, int octaves )
+{
+    float value = 0.0f;
+    float amplitude = 0.5f;
+    float total = 0.0f;
+    for( int i = 0; i < octaves; i++ )
+    {
+        value += amplitude * value_noise( p );
+        total += amplitude;
+        p *= 2.0f;
+        amplitude *= 0.5f;
+    }
+    return value / max( total, 1e-6f );
+}
+
+float fbm( vec3 p, int octaves )
+{
+    float value = 0.0f;
+    float amplitude = 0.5f;
+    float total = 0.0f;
+    for( int i = 0; i < octaves; i++ )
+    {
+        value += amplitude * value_noise( p );
+        total += amplitude;
+        p *= 2.0f;
+        amplitude *= 0.5f;
+    }
+    return value / max( total, 1e-6f );
+}
+
+#endif
@@ -0,0 +1,70 @@
+/*
+ * Kiyo signed distance functions, include with `#include <kiyo/sdf.glsl>`
+ * Distances are negative inside the shape.
+ */
+
+#ifndef KIYO_SDF_GLSL
+#define KIYO_SDF_GLSL
+
+float sd_circle( vec2 p, float radius )
+{
+    return length( p ) - radius;
+}
+
+// `size` is the half extent of the box
+float sd_box( vec2 p, vec2 size )
+{
+    vec2 d = abs( p ) - size;
+    return length( max( d, 0.0f ) ) + min( max( d.x, d.y ), 0.0f );
+}
+
+float sd_segment( vec2 p, vec2 a, vec2 b )
+{
+    vec2 pa = p - a;
+    vec2 ba = b - a;
+    float h = clamp( dot( pa, ba ) / max( dot( ba, ba ), 1e-6f ), 0.0f, 1.0f );
+    return length( pa - ba * h );
+}
+
+float sd_sphere( vec3 p, float radius )
+{
+    return length( p ) - radius;
+}
+
+// `size` is the half extent of the box
+float sd_box( vec3 p, vec3 size )
+{
+    vec3 d = abs( p ) - size;
+    return length( max( d, 0.0f ) ) + min( max( d.x, max( d.y, d.z ) ), 0.0f );
+}
+
+// Torus around the y axis, `radii` holds the major and minor radius
+float sd_torus( vec3 p, vec2 radii )
+{
+    vec2 q = vec2( length( p.xz ) - radii.x, p.y );
+    return length( q ) - radii.y;
+}
+
+float op_union( float a, float b )
+{
+    return min( a, b );
+}
+
+float op_subtract( float a, float b )
+{
+    return max( a, -b );
+}
+
+float op_intersect( float a, float b )
+{
+    return max( a, b );
+}
+
+// Union with a rounded blend of size `k` between the shapes
+float op_smooth_union( float a, float b, float k )
+{
+    float h = clamp( 0.5f + 0.5f * ( b - a ) / max( k, 1e-6f ), 0.0f, 1.0f );
+    return mix( b, a, h ) - k * h * ( 1.0f - h );
+}
+
+#endif
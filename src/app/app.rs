@@ -1,18 +1,27 @@
 use notify::event::AccessKind::Close;
-use notify::EventKind::{Access, Modify};
-use std::path::Path;
-use std::time::SystemTime;
+use notify::EventKind::{Access, Create, Modify};
+use std::collections::HashSet;
+use std::{fs, io};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
 use env_logger::{Builder, Env};
 use glam::UVec2;
 use log::{error, info, LevelFilter};
 use notify::{Config, RecommendedWatcher, RecursiveMode, Watcher};
 use notify::event::AccessMode::Write;
-use winit::event::{Event, StartCause, WindowEvent};
+use winit::event::{ElementState, Event, KeyEvent, StartCause, WindowEvent};
+use winit::keyboard::Key;
 use winit::event_loop::{ControlFlow, EventLoop};
 use winit::platform::run_on_demand::EventLoopExtRunOnDemand;
 use cpal::traits::StreamTrait;
-use crate::app::draw_orch::DrawConfig;
-use crate::app::{DrawOrchestrator, Renderer, Window, StreamFactory};
+use serde::Deserialize;
+use crate::app::draw_orch::{CompiledPasses, DrawConfig, ReloadedPasses};
+use crate::app::inspector::{CYCLE_CHANNEL_KEY, CYCLE_IMAGE_KEY};
+use crate::app::{DrawOrchestrator, InputState, Overlay, ParamHandle, Project, Renderer, Window, StreamFactory};
+use crate::vulkan::PipelineErr;
+use crate::Error;
 
 // Maybe delete all the following blocks
 use crate::vulkan::{Device, RenderPass, Framebuffer, CommandBuffer};
@@ -34,24 +43,105 @@ impl RenderContext<'_> {
 }
 // Stop delete
 
+/// Time without file events after which changed shaders are reloaded.
+/// Editors may write a file in several steps, which are reloaded once.
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Shaders compiling on a worker thread, while the event loop keeps drawing.
+enum Compilation {
+    /// The changed passes of the running orchestrator.
+    Reload( JoinHandle<Result<ReloadedPasses, Error>> ),
+    /// All passes, while the error screen is shown.
+    Start( JoinHandle<Result<CompiledPasses, Error>> ),
+}
+
+impl Compilation {
+    fn is_finished(&self) -> bool {
+        match self {
+            Compilation::Reload(handle) => handle.is_finished(),
+            Compilation::Start(handle) => handle.is_finished(),
+        }
+    }
+
+    /// Wait for the worker thread to finish, discarding its result.
+    fn wait(self) {
+        let _ = match self {
+            Compilation::Reload(handle) => handle.join().map(drop),
+            Compilation::Start(handle) => handle.join().map(drop),
+        };
+    }
+}
+
+/// Produces the left and right audio sample at a time in seconds, which restarts every second.
+type AudioFunc = Box<dyn FnMut(f32) -> (f32, f32) + Send>;
+
+/// The result of a compilation, a panic on the worker thread is resumed on the calling thread.
+fn join<T>(handle: JoinHandle<T>) -> T {
+    match handle.join() {
+        Ok(result) => result,
+        Err(panic) => std::panic::resume_unwind(panic),
+    }
+}
+
 pub struct App {
     _start_time: SystemTime,
     renderer: Renderer,
     window: Window,
+    overlay: Overlay,
     event_loop: EventLoop<()>,
     pub app_config: AppConfig,
 }
 
+/// Window settings, the `[window]` table of a project file.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct AppConfig {
     pub width: u32,
     pub height: u32,
     pub vsync: bool,
     pub log_fps: bool,
+    pub scaling: ScalingMode,
+    pub filter: FilterMode,
+}
+
+impl Default for AppConfig {
+    fn default() -> AppConfig {
+        AppConfig {
+            width: 1000,
+            height: 1000,
+            vsync: true,
+            log_fps: false,
+            scaling: ScalingMode::Stretch,
+            filter: FilterMode::Nearest,
+        }
+    }
+}
+
+/// How the output image is presented when its size differs from the window.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScalingMode {
+    /// Stretch the image over the whole window.
+    Stretch,
+    /// Scale the image to fit the window, keeping its aspect ratio. The remaining area is black.
+    Fit,
+    /// Scale the image by the largest whole factor that fits the window, for crisp pixel art.
+    Integer,
+    /// Render at a fixed resolution, independent of the window size, and fit it in the window.
+    Fixed( u32, u32 ),
+}
+
+/// Filter used when the output image is scaled.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FilterMode {
+    Nearest,
+    Linear,
 }
 
 impl App {
 
-    fn init_logger() {
+    pub(crate) fn init_logger() {
         let env = Env::default()
             .filter_or("LOG_LEVEL", "trace")
             .write_style_or("LOG_STYLE", "always");
@@ -66,55 +156,138 @@ impl App {
             .init();
     }
 
-    pub fn new(app_config: AppConfig) -> App{
+    pub fn new(app_config: AppConfig) -> Result<App, Error> {
 
         Self::init_logger();
 
         // App setup
         let start_time = SystemTime::now();
 
-        let event_loop = EventLoop::new().expect("Failed to create event loop.");
-        let window = Window::create(&event_loop, "kiyo engine", app_config.width, app_config.height);
-        let renderer = Renderer::new(&window, app_config.vsync);
+        let event_loop = EventLoop::new().map_err(|e| Error::Window(format!("Failed to create event loop: {}", e)))?;
+        let window = Window::create(&event_loop, "kiyo engine", app_config.width, app_config.height)?;
+        let mut renderer = Renderer::new(&window, app_config.vsync)?;
+        renderer.scaling = app_config.scaling;
+        renderer.filter = app_config.filter;
+        let overlay = Overlay::new(&renderer, &window)?;
 
-        App {
+        Ok(App {
             event_loop,
             window,
             renderer,
+            overlay,
             _start_time: start_time,
             app_config,
+        })
+    }
+
+    /// Handle to the values of the shader parameters, see `DrawConfig::params`.
+    /// It can be cloned and moved to another thread to change parameters while the app runs.
+    pub fn params(&self) -> ParamHandle {
+        self.renderer.params.clone()
+    }
+
+    /// The canonical path of `path`. A missing file is looked up by the canonical path of its directory,
+    /// so creating it can be recognized. `None` when the directory is missing as well.
+    fn canonical_path(path: &Path) -> Option<PathBuf> {
+        fs::canonicalize(path).ok().or_else(|| {
+            let directory = path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+            Some(fs::canonicalize(directory).ok()?.join(path.file_name()?))
+        })
+    }
+
+    /// The shaders of the passes and every file they include, a change to any of them reloads the passes using it.
+    /// Missing shaders are included, creating them starts the passes.
+    fn source_files(draw_config: &DrawConfig, orchestrator: &Result<DrawOrchestrator, PipelineErr>) -> HashSet<PathBuf> {
+        let shaders = draw_config.passes.iter().filter_map(|p| Self::canonical_path(Path::new(&p.shader)));
+        match orchestrator {
+            Ok(o) => shaders.chain(o.passes.iter().flat_map(|p| p.source_files())).collect(),
+            Err(e) => shaders.chain(e.includes().iter().cloned()).collect(),
+        }
+    }
+
+    /// Watch the directories of `files` which aren't in `watched` yet.
+    /// Directories are watched instead of the files, as editors may replace a file when saving it.
+    fn watch_directories(watcher: &mut RecommendedWatcher, files: &HashSet<PathBuf>, watched: &mut HashSet<PathBuf>) -> Result<(), Error> {
+        for directory in files.iter().filter_map(|path| path.parent()) {
+            if !watched.contains(directory) {
+                watcher.watch(directory, RecursiveMode::NonRecursive).map_err(Self::watch_error(directory))?;
+                watched.insert(directory.to_path_buf());
+            }
+        }
+        Ok(())
+    }
+
+    /// Maps a failure to watch `path` for changes to an error.
+    fn watch_error(path: &Path) -> impl FnOnce(notify::Error) -> Error + '_ {
+        move |e| Error::Io(path.to_path_buf(), io::Error::other(e))
+    }
+
+    /// The resolution the orchestrator renders at, the window size unless a fixed resolution is used.
+    fn render_resolution(scaling: ScalingMode, window: &Window) -> UVec2 {
+        match scaling {
+            ScalingMode::Fixed(width, height) => UVec2::new(width, height),
+            _ => UVec2::new( window.get_extent().width, window.get_extent().height ),
         }
     }
 
-    pub fn run(mut self, draw_config: DrawConfig, audio_func: Option<fn(f32)->(f32, f32)>) {
+    /// Run until the window is closed.
+    /// While a shader doesn't compile, an error screen with the compile log is shown until the shader is fixed.
+    /// Changed shaders are compiled on a worker thread, the passes are swapped between frames once they compiled.
+    /// Returns an error when the draw config itself is invalid, e.g. it uses an undeclared buffer,
+    /// or when rendering fails, e.g. when the surface is lost.
+    pub fn run(self, draw_config: DrawConfig, audio_func: Option<fn(f32)->(f32, f32)>) -> Result<(), Error> {
+        let audio_func = audio_func.map(|f| Box::new(f) as AudioFunc);
+        self.run_config(draw_config, None, audio_func)
+    }
+
+    /// Open a window for the project file at `path` and run it until the window is closed, see `Project`.
+    /// Changes to the passes, resources and parameters of the file are reloaded like shaders, the window and audio are set up once.
+    pub fn run_project(path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        let project = Project::from_file(path)?;
+        let app = App::new(project.app_config)?;
+        let audio_func = project.audio.map(|audio| Box::new(move |t| audio.sample(t)) as AudioFunc);
+        app.run_config(project.draw_config, Some(path.to_path_buf()), audio_func)
+    }
 
-        let resolution = UVec2::new( self.window.get_extent().width, self.window.get_extent().height );
+    /// Run `draw_config`, read from `project_file` when it's set, in which case the file is watched as well.
+    fn run_config(mut self, draw_config: DrawConfig, project_file: Option<PathBuf>, audio_func: Option<AudioFunc>) -> Result<(), Error> {
+
+        let mut draw_config = Arc::new(draw_config);
+        let project_file = project_file.and_then(|path| fs::canonicalize(path).ok());
+        let mut resolution = Self::render_resolution(self.app_config.scaling, &self.window);
+        // The orchestrator, or the shader error preventing it from being created.
+        // An invalid draw config can only be fixed by editing it, which is only watched for project files
         let mut orchestrator = match DrawOrchestrator::new(&mut self.renderer, resolution, &draw_config) {
-            Ok(d) => {
-                d
-            },
-            Err(e) => {
+            Ok(o) => Ok(o),
+            Err(Error::Pipeline(e)) if project_file.is_some() || !matches!(e, PipelineErr::Config(_)) => {
                 error!("{}", e);
-                log::info!("A shader contains an error, quitting");
-                std::process::abort();
-            }
+                log::info!("A shader contains an error, waiting for it to be fixed");
+                Err(e)
+            },
+            Err(e) => return Err(e),
         };
 
-        let paths = &draw_config.passes.iter().map(|p| { p.shader.clone() }).collect::<Vec<String>>();
-
         let (tx, rx) = std::sync::mpsc::channel();
-        let mut watcher = RecommendedWatcher::new(tx, Config::default()).unwrap();
-        for path in paths {
-            watcher.watch(Path::new(path), RecursiveMode::Recursive).unwrap();
-        };
+        let mut watcher = RecommendedWatcher::new(tx, Config::default()).map_err(Self::watch_error(Path::new(".")))?;
+        let mut source_files = Self::source_files(&draw_config, &orchestrator);
+        source_files.extend(project_file.clone());
+        let mut watched_directories = HashSet::new();
+        Self::watch_directories(&mut watcher, &source_files, &mut watched_directories)?;
+        let mut changed_paths: HashSet<PathBuf> = HashSet::new();
+        let mut last_change = Instant::now();
+        let mut compilation: Option<Compilation> = None;
+        // Set while the passes of a changed project file fail to start, the previous passes keep running until they do
+        let mut project_pending = false;
 
         // audio
 
-        if let Some(audio_func) = audio_func {
+        if let Some(mut audio_func) = audio_func {
 
-            let sf = StreamFactory::default_factory().unwrap();
+            let sf = StreamFactory::default_factory().map_err(Error::Audio)?;
     
             let sample_rate = sf.config().sample_rate.0;
+            self.renderer.sample_rate = sample_rate as f32;
             let mut sample_clock = 0;
             let routin = move |len: usize| -> Vec<f32> {
                 (0..len / 2) // len is apparently left *and* right
@@ -127,47 +300,159 @@ impl App {
                     .collect()
             };
             
-            let stream = sf.create_stream(routin).unwrap();
-            StreamTrait::play(&stream).unwrap();
+            let stream = sf.create_stream(routin).map_err(Error::Audio)?;
+            StreamTrait::play(&stream).map_err(|e| Error::Audio(e.to_string()))?;
         }
 
         // Event loop
 
+        let mut input = InputState::default();
         let mut last_print_time = SystemTime::now();
         let mut frame_count = 0;
+        // Set when rendering fails, which stops the event loop
+        let mut result = Ok(());
 
-        self.event_loop
+        let run = self.event_loop
             .run_on_demand( |event, elwt| {
                 elwt.set_control_flow(ControlFlow::Poll);
 
-                // File watching and reloading application
-                if let Ok(event) = &rx.try_recv() {
-                    if let Ok(e) = event {
-                        match e.kind {
-                            Access(Close(Write)) | Modify(_) => {
-                                log::info!("File write event: {:?}", e.paths);
-
-                                // Currently just reloads all shaders, it might be better to only compile the changed shader
-                                let new_orch = DrawOrchestrator::new(&mut self.renderer, resolution, &draw_config);
-                                match new_orch {
-                                    Ok(o) => {
-                                        orchestrator = o;
-                                    }
-                                    Err(e) => {
-                                        error!("{}", e);
-                                        log::info!("Shader contains error, not updating");
-                                    }
-                                }
+                // File watching, changes are collected until the files stop changing
+                for e in rx.try_iter().flatten() {
+                    if let Access(Close(Write)) | Modify(_) | Create(_) = e.kind {
+                        let paths = e.paths.into_iter()
+                            .filter(|path| Self::canonical_path(path).is_some_and(|path| source_files.contains(&path)));
+                        for path in paths {
+                            changed_paths.insert(path);
+                            last_change = Instant::now();
+                        }
+                    }
+                }
+
+                // Swap in finished compilations before the next frame, the previous passes are drawn until then
+                if compilation.as_ref().is_some_and(Compilation::is_finished) {
+                    let starting = matches!(compilation, Some(Compilation::Start(_)));
+                    let reloaded = match compilation.take() {
+                        Some(Compilation::Reload(handle)) => join(handle).and_then(|reloaded| match &mut orchestrator {
+                            Ok(o) => o.apply_reload(&mut self.renderer, reloaded).map(|count| {
+                                log::info!("Reloaded {} passes", count);
+                            }),
+                            Err(_) => Ok(()),
+                        }),
+                        Some(Compilation::Start(handle)) => join(handle).and_then(|compiled| {
+                            let o = DrawOrchestrator::from_compiled(&mut self.renderer, resolution, &draw_config, compiled)?;
+                            // Frames in flight may still be using the resources of a replaced orchestrator
+                            self.renderer.device.wait_idle()?;
+                            log::info!("Shaders compiled, starting the passes");
+                            orchestrator = Ok(o);
+                            project_pending = false;
+                            Ok(())
+                        }),
+                        None => Ok(()),
+                    };
+                    let mut failed_includes = Vec::new();
+                    match reloaded {
+                        Ok(()) => {},
+                        Err(Error::Pipeline(e)) => {
+                            error!("{}", e);
+                            // Fixing a file included by the failed shader has to trigger a reload as well
+                            failed_includes = e.includes().to_vec();
+                            if starting && orchestrator.is_ok() {
+                                log::info!("Project contains errors, keeping the previous passes");
+                                project_pending = true;
+                            } else if starting {
+                                orchestrator = Err(e);
+                            } else {
+                                log::info!("Shader contains error, not updating");
+                            }
+                        },
+                        Err(e) => {
+                            result = Err(e);
+                            elwt.exit();
+                            return;
+                        },
+                    }
+
+                    // Includes may have been added or removed
+                    source_files = Self::source_files(&draw_config, &orchestrator);
+                    source_files.extend(failed_includes);
+                    source_files.extend(project_file.clone());
+                    if let Err(e) = Self::watch_directories(&mut watcher, &source_files, &mut watched_directories) {
+                        error!("{}", e);
+                    }
+                }
+
+                // Compile only the changed shaders, images and buffers keep their contents.
+                // Changes made while compiling are picked up by the next compilation.
+                if compilation.is_none() && !changed_paths.is_empty() && last_change.elapsed() >= RELOAD_DEBOUNCE {
+                    let changed = changed_paths.drain().collect::<Vec<PathBuf>>();
+                    log::info!("Shaders changed: {:?}", changed);
+
+                    // A changed project file recreates the orchestrator.
+                    // The current passes keep running while the file is invalid or its passes fail to start.
+                    let project_changed = project_file.as_ref()
+                        .is_some_and(|project| changed.iter().any(|path| fs::canonicalize(path).is_ok_and(|path| path == *project)));
+                    let mut reload_project = false;
+                    if let Some(project) = project_file.as_ref().filter(|_| project_changed) {
+                        match DrawConfig::from_file(project) {
+                            Ok(config) => {
+                                log::info!("Project file changed, recreating the passes");
+                                draw_config = Arc::new(config);
+                                reload_project = true;
                             },
-                            _ => {}
+                            Err(e) => error!("{}", e),
                         }
                     }
+
+                    compilation = match &orchestrator {
+                        Ok(o) if !reload_project && !project_pending => o.reload_job(&self.renderer, &changed)
+                            .map(|job| Compilation::Reload(thread::spawn(move || job.compile()))),
+                        _ => {
+                            let device = self.renderer.device.clone();
+                            let draw_config = draw_config.clone();
+                            Some(Compilation::Start(thread::spawn(move || CompiledPasses::new(&device, &draw_config))))
+                        },
+                    };
                 }
 
                 // Window event
                 match event {
                     | Event::NewEvents(StartCause::Poll) => {
-                        self.renderer.draw_frame(&mut orchestrator);
+                        let extent = self.window.get_extent();
+                        if extent.width == 0 || extent.height == 0 {
+                            // Nothing to present to while minimized
+                            return;
+                        }
+
+                        let frame = (|| -> Result<(), Error> {
+                            if self.renderer.swapchain_out_of_date {
+                                self.renderer.recreate_swapchain(&self.window)?;
+
+                                let new_resolution = Self::render_resolution(self.app_config.scaling, &self.window);
+                                if new_resolution != resolution {
+                                    resolution = new_resolution;
+                                    if let Ok(orchestrator) = &mut orchestrator {
+                                        orchestrator.resize(&mut self.renderer, resolution)?;
+                                    }
+                                }
+                            }
+
+                            match &mut orchestrator {
+                                Ok(orchestrator) => {
+                                    self.overlay.update(&self.window, &mut self.renderer, orchestrator);
+                                    self.renderer.draw_frame(orchestrator, &input, self.overlay.painter())
+                                },
+                                Err(e) => {
+                                    self.overlay.update_error(&self.window, &e.to_string());
+                                    self.renderer.draw_error_frame(self.overlay.painter())
+                                },
+                            }
+                        })();
+                        input.end_frame();
+                        if let Err(e) = frame {
+                            result = Err(e);
+                            elwt.exit();
+                            return;
+                        }
 
                         if self.app_config.log_fps {
                             let current_frame_time = SystemTime::now();
@@ -182,13 +467,70 @@ impl App {
                         }
                     }
                     | Event::WindowEvent { event, .. } => {
-                        self.window.window_event( event.clone(), elwt );
+                        // Input used by the overlay isn't passed on, except for releases so no key or button gets stuck
+                        let consumed = self.overlay.window_event(&self.window, &event);
+                        let released = matches!(
+                            event,
+                            WindowEvent::MouseInput { state: ElementState::Released, .. }
+                            | WindowEvent::KeyboardInput { event: KeyEvent { state: ElementState::Released, .. }, .. }
+                        );
+                        if !consumed {
+                            self.window.window_event( event.clone(), elwt );
+                        }
+                        if !consumed || released {
+                            input.window_event(&event);
+                        }
 
                         match event {
-                            WindowEvent::RedrawRequested => {
-                                self.renderer.draw_frame(&mut orchestrator);
+                            WindowEvent::RedrawRequested if !self.renderer.swapchain_out_of_date => {
+                                let frame = match &mut orchestrator {
+                                    Ok(orchestrator) => self.renderer.draw_frame(orchestrator, &input, self.overlay.painter()),
+                                    Err(_) => self.renderer.draw_error_frame(self.overlay.painter()),
+                                };
+                                input.end_frame();
+                                if let Err(e) = frame {
+                                    result = Err(e);
+                                    elwt.exit();
+                                }
+                            },
+                            WindowEvent::KeyboardInput {
+                                event: KeyEvent {
+                                    logical_key: Key::Character(c),
+                                    state: ElementState::Pressed,
+                                    ..
+                                },
+                                ..
+                            } if c == "r" && !consumed => {
+                                if let Ok(orchestrator) = &mut orchestrator {
+                                    log::info!("Resetting image history");
+                                    orchestrator.reset_history();
+                                }
+                            },
+                            WindowEvent::KeyboardInput {
+                                event: KeyEvent {
+                                    logical_key: Key::Named(key),
+                                    state: ElementState::Pressed,
+                                    repeat: false,
+                                    ..
+                                },
+                                ..
+                            } if !consumed && (key == CYCLE_IMAGE_KEY || key == CYCLE_CHANNEL_KEY) => {
+                                let inspector = &mut self.renderer.inspector;
+                                if key == CYCLE_IMAGE_KEY {
+                                    let image_count = orchestrator.as_ref().map_or(0, |o| o.images.len() as u32);
+                                    inspector.cycle_image(image_count);
+                                } else {
+                                    inspector.cycle_channel();
+                                }
+                                log::info!(
+                                    "Presenting {} of {}",
+                                    inspector.channel.name(),
+                                    inspector.image.map_or("the output image".to_string(), |id| format!("image {}", id))
+                                );
                             },
                             WindowEvent::Resized( _ ) => {
+                                // Recreated before drawing the next frame
+                                self.renderer.swapchain_out_of_date = true;
                             }
                             _ => (),
                         }
@@ -196,11 +538,17 @@ impl App {
                     _ => (),
                 }
 
-            })
-            .unwrap();
+            });
+
+        // The pipelines of a running compilation keep the device alive, they are dropped before the renderer
+        if let Some(compilation) = compilation {
+            compilation.wait();
+        }
+        run.map_err(|e| Error::Window(format!("Event loop failed: {}", e)))?;
 
         // Wait for all render operations to finish before exiting
         // This ensures we can safely start dropping gpu resources
-        self.renderer.device.wait_idle();
+        self.renderer.device.wait_idle()?;
+        result
     }
 }
\ No newline at end of file
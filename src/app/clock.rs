@@ -0,0 +1,53 @@
+use std::time::Instant;
+
+/// Time passed to the shaders, which can be paused and moved around.
+pub struct Clock {
+    start: Instant,
+    /// Offset added to the time since `start`, changed when the clock is paused or set.
+    offset: f32,
+    paused_at: Option<f32>,
+}
+
+impl Clock {
+    pub fn new() -> Clock {
+        Clock {
+            start: Instant::now(),
+            offset: 0.0,
+            paused_at: None,
+        }
+    }
+
+    /// The current time in seconds.
+    pub fn time(&self) -> f32 {
+        self.paused_at.unwrap_or_else(|| self.start.elapsed().as_secs_f32() + self.offset)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        match (paused, self.paused_at) {
+            (true, None) => self.paused_at = Some(self.time()),
+            (false, Some(time)) => {
+                self.paused_at = None;
+                self.set_time(time);
+            },
+            _ => {}
+        }
+    }
+
+    /// Jump to `time`, the clock keeps running from there unless it's paused.
+    pub fn set_time(&mut self, time: f32) {
+        match self.paused_at {
+            Some(_) => self.paused_at = Some(time),
+            None => self.offset = time - self.start.elapsed().as_secs_f32(),
+        }
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,150 +1,1290 @@
 use crate::vulkan::PipelineErr;
-use std::collections::HashMap;
+use crate::Error;
+use std::collections::{HashMap, HashSet};
+use std::fs;
 use std::mem::size_of;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use ash::vk;
 use glam::{UVec2, UVec3};
+use half::f16;
 use crate::app::{Renderer};
-use crate::app::renderer::PushConstants;
-use crate::vulkan::{CommandBuffer, ComputePipeline, DescriptorSetLayout, Image};
+use crate::app::params::{glsl_declaration, validate_params, Param};
+use crate::app::project::Project;
+use crate::app::renderer::{EngineUniforms, PushConstants};
+use gpu_allocator::MemoryLocation;
+use serde::Deserialize;
+use crate::vulkan::{load_shader_code, Buffer, CommandBuffer, ComputePipeline, DescriptorSetLayout, Device, Image, ShaderCache};
 
-#[derive(Clone)]
+/// Storage format of an image resource.
+/// Each format is accessible in the shader through its own image array, see `IMAGE_BINDINGS`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageFormat {
+    #[default]
+    Rgba8,
+    Rgba16f,
+    Rgba32f,
+    Rg16f,
+    Rg32f,
+    R16f,
+    R32f,
+    Rgba32ui,
+    R32ui,
+    Rgba32i,
+    R32i,
+}
+
+/// The data type of a single channel of an image format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChannelType {
+    Unorm8,
+    Float16,
+    Float32,
+    Uint32,
+    Int32,
+}
+
+impl ImageFormat {
+    pub fn vk_format(&self) -> vk::Format {
+        match self {
+            ImageFormat::Rgba8 => vk::Format::R8G8B8A8_UNORM,
+            ImageFormat::Rgba16f => vk::Format::R16G16B16A16_SFLOAT,
+            ImageFormat::Rgba32f => vk::Format::R32G32B32A32_SFLOAT,
+            ImageFormat::Rg16f => vk::Format::R16G16_SFLOAT,
+            ImageFormat::Rg32f => vk::Format::R32G32_SFLOAT,
+            ImageFormat::R16f => vk::Format::R16_SFLOAT,
+            ImageFormat::R32f => vk::Format::R32_SFLOAT,
+            ImageFormat::Rgba32ui => vk::Format::R32G32B32A32_UINT,
+            ImageFormat::R32ui => vk::Format::R32_UINT,
+            ImageFormat::Rgba32i => vk::Format::R32G32B32A32_SINT,
+            ImageFormat::R32i => vk::Format::R32_SINT,
+        }
+    }
+
+    /// The GLSL layout format qualifier, e.g. `rgba16f`.
+    pub fn glsl_qualifier(&self) -> &'static str {
+        match self {
+            ImageFormat::Rgba8 => "rgba8",
+            ImageFormat::Rgba16f => "rgba16f",
+            ImageFormat::Rgba32f => "rgba32f",
+            ImageFormat::Rg16f => "rg16f",
+            ImageFormat::Rg32f => "rg32f",
+            ImageFormat::R16f => "r16f",
+            ImageFormat::R32f => "r32f",
+            ImageFormat::Rgba32ui => "rgba32ui",
+            ImageFormat::R32ui => "r32ui",
+            ImageFormat::Rgba32i => "rgba32i",
+            ImageFormat::R32i => "r32i",
+        }
+    }
+
+    /// The GLSL image type matching the format, e.g. `uimage2D` for unsigned integer formats.
+    pub fn glsl_image_type(&self) -> &'static str {
+        match self.channel_type() {
+            ChannelType::Uint32 => "uimage2D",
+            ChannelType::Int32 => "iimage2D",
+            _ => "image2D",
+        }
+    }
+
+    pub fn channel_type(&self) -> ChannelType {
+        match self {
+            ImageFormat::Rgba8 => ChannelType::Unorm8,
+            ImageFormat::Rgba16f | ImageFormat::Rg16f | ImageFormat::R16f => ChannelType::Float16,
+            ImageFormat::Rgba32f | ImageFormat::Rg32f | ImageFormat::R32f => ChannelType::Float32,
+            ImageFormat::Rgba32ui | ImageFormat::R32ui => ChannelType::Uint32,
+            ImageFormat::Rgba32i | ImageFormat::R32i => ChannelType::Int32,
+        }
+    }
+
+    /// Whether the channels are integers, these can only be blitted to images of the same channel type.
+    pub fn is_integer(&self) -> bool {
+        matches!(self.channel_type(), ChannelType::Uint32 | ChannelType::Int32)
+    }
+
+    pub fn channel_count(&self) -> u32 {
+        match self {
+            ImageFormat::Rgba8 | ImageFormat::Rgba16f | ImageFormat::Rgba32f | ImageFormat::Rgba32ui | ImageFormat::Rgba32i => 4,
+            ImageFormat::Rg16f | ImageFormat::Rg32f => 2,
+            ImageFormat::R16f | ImageFormat::R32f | ImageFormat::R32ui | ImageFormat::R32i => 1,
+        }
+    }
+
+    /// Size of a single pixel in bytes.
+    pub fn pixel_size(&self) -> u32 {
+        let channel_size = match self.channel_type() {
+            ChannelType::Unorm8 => 1,
+            ChannelType::Float16 => 2,
+            ChannelType::Float32 | ChannelType::Uint32 | ChannelType::Int32 => 4,
+        };
+        channel_size * self.channel_count()
+    }
+
+    /// Decode the bytes of a single pixel into rgba values, unorm channels are mapped to `[0, 1]`.
+    /// Missing color channels are set to zero and a missing alpha channel to one.
+    pub fn decode_pixel(&self, bytes: &[u8]) -> [f32; 4] {
+        let channel_size = (self.pixel_size() / self.channel_count()) as usize;
+        let mut rgba = [0.0, 0.0, 0.0, 1.0];
+        for (value, c) in rgba.iter_mut().zip(bytes.chunks_exact(channel_size)) {
+            *value = match self.channel_type() {
+                ChannelType::Unorm8 => c[0] as f32 / 255.0,
+                ChannelType::Float16 => f16::from_le_bytes([c[0], c[1]]).to_f32(),
+                ChannelType::Float32 => f32::from_le_bytes([c[0], c[1], c[2], c[3]]),
+                ChannelType::Uint32 => u32::from_le_bytes([c[0], c[1], c[2], c[3]]) as f32,
+                ChannelType::Int32 => i32::from_le_bytes([c[0], c[1], c[2], c[3]]) as f32,
+            };
+        }
+        rgba
+    }
+
+    /// The value images are cleared to at the start of a frame.
+    pub fn clear_value(&self) -> vk::ClearColorValue {
+        match self.channel_type() {
+            ChannelType::Uint32 => vk::ClearColorValue { uint32: [0, 0, 0, 0] },
+            ChannelType::Int32 => vk::ClearColorValue { int32: [0, 0, 0, 0] },
+            _ => vk::ClearColorValue { float32: [1.0, 0.0, 0.0, 1.0] },
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ImageSize {
+    /// A fixed size in pixels.
+    Absolute( u32, u32 ),
+    /// A scale of the output resolution, e.g. `0.5` for a half resolution buffer.
+    Relative( f32 ),
+}
+
+impl Default for ImageSize {
+    fn default() -> ImageSize {
+        ImageSize::Relative(1.0)
+    }
+}
+
+impl ImageSize {
+    pub fn resolve(&self, resolution: UVec2) -> UVec2 {
+        match *self {
+            ImageSize::Absolute(width, height) => UVec2::new(width, height),
+            ImageSize::Relative(scale) => UVec2::new(
+                ((resolution.x as f32 * scale).round() as u32).max(1),
+                ((resolution.y as f32 * scale).round() as u32).max(1)
+            ),
+        }
+    }
+}
+
+/// How an image's contents carry over between frames.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Persistence {
+    /// Cleared at the start of every frame.
+    #[default]
+    Transient,
+    /// Keeps its contents across frames, until the history is reset.
+    Persistent,
+    /// Double buffered. Index `<id>` is written this frame, while index `PREVIOUS_<id>`
+    /// holds the image written during the previous frame.
+    PingPong,
+}
+
+/// Declaration of an image resource.
+/// Images which are used by a pass but not declared are full resolution, transient `Rgba8` images.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct ImageResource {
     pub id: u32,
+    #[serde(default)]
+    pub format: ImageFormat,
+    #[serde(default)]
+    pub size: ImageSize,
+    /// Amount of mip levels, each level is bound as a separate storage image, see `MIP_OFFSET_<id>`.
+    #[serde(default = "default_mip_levels")]
+    pub mip_levels: u32,
+    #[serde(default)]
+    pub persistence: Persistence,
+}
+
+fn default_mip_levels() -> u32 {
+    1
+}
+
+impl ImageResource {
+    pub fn new(id: u32) -> ImageResource {
+        ImageResource {
+            id,
+            format: ImageFormat::Rgba8,
+            size: ImageSize::Relative(1.0),
+            mip_levels: 1,
+            persistence: Persistence::Transient,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BufferSize {
+    /// A size in bytes. The element count is the amount of 32-bit words.
+    Bytes( u64 ),
+    /// An amount of elements of `stride` bytes each.
+    Elements { count: u64, stride: u64 },
+}
+
+impl BufferSize {
+    pub fn byte_size(&self) -> u64 {
+        match *self {
+            BufferSize::Bytes(size) => size,
+            BufferSize::Elements { count, stride } => count * stride,
+        }
+    }
+
+    pub fn element_count(&self) -> u64 {
+        match *self {
+            BufferSize::Bytes(size) => size / 4,
+            BufferSize::Elements { count, .. } => count,
+        }
+    }
+}
+
+/// Declaration of a storage buffer resource.
+/// Buffers keep their contents across frames, until the history is reset.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BufferResource {
+    pub id: u32,
+    pub size: BufferSize,
+    /// Contents the buffer is filled with at startup and on a history reset, zeroes when `None`.
+    pub initial_data: Option<Vec<u8>>,
 }
 
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum DispatchConfig
 {
     Count( u32, u32, u32 ),
     FullScreen,
+    /// Read the workgroup counts from a buffer resource, as three consecutive `uint`s at byte `offset`.
+    /// The buffer is typically written by an earlier pass.
+    Indirect { buffer: u32, offset: u64 },
+}
+
+/// Workgroup counts of a pass, resolved from its `DispatchConfig`.
+pub enum Dispatch {
+    Direct( UVec3 ),
+    Indirect { buffer: u32, offset: u64 },
 }
 
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Pass {
     pub shader: String,
     pub dispatches: DispatchConfig,
+    #[serde(default)]
     pub input_resources: Vec<u32>,
+    #[serde(default)]
     pub output_resources: Vec<u32>,
+    #[serde(default)]
+    pub input_buffers: Vec<u32>,
+    #[serde(default)]
+    pub output_buffers: Vec<u32>,
+    /// Local workgroup size, passed to the shader as `WORKGROUP_SIZE_X`, `WORKGROUP_SIZE_Y` and `WORKGROUP_SIZE_Z`.
+    /// Defaults to `WORKGROUP_SIZE` x `WORKGROUP_SIZE` x 1 when `None`.
+    pub workgroup_size: Option<(u32, u32, u32)>,
+    /// Entry point of HLSL, WGSL and SPIR-V shaders, `main` when `None`. GLSL shaders always start at `main`.
+    pub entry_point: Option<String>,
+}
+
+/// Identifies a resource in the dependency graph between passes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum ResourceId {
+    Image( u32 ),
+    Buffer( u32 ),
+}
+
+impl Pass {
+    fn indirect_buffer(&self) -> Option<u32> {
+        match self.dispatches {
+            DispatchConfig::Indirect { buffer, .. } => Some(buffer),
+            _ => None,
+        }
+    }
+
+    fn reads(&self) -> impl Iterator<Item = ResourceId> + '_ {
+        self.input_resources.iter().map(|&id| ResourceId::Image(id))
+            .chain(self.input_buffers.iter().map(|&id| ResourceId::Buffer(id)))
+            .chain(self.indirect_buffer().map(ResourceId::Buffer))
+    }
+
+    fn writes(&self) -> impl Iterator<Item = ResourceId> + '_ {
+        self.output_resources.iter().map(|&id| ResourceId::Image(id))
+            .chain(self.output_buffers.iter().map(|&id| ResourceId::Buffer(id)))
+    }
 }
 
+/// The images presented to the window, or written by the headless renderer.
+/// Composed images are placed in cells the size of the first image, other images are scaled to fit.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Output {
+    /// The image with the highest id.
+    #[default]
+    Last,
+    Image( u32 ),
+    /// Images side by side in a single row, to compare variants of an algorithm.
+    Split( Vec<u32> ),
+    /// Images in a grid with the given amount of columns, filled row by row.
+    Grid( Vec<u32>, u32 ),
+}
+
+impl Output {
+    /// The presented image ids and the amount of columns they are arranged in.
+    fn resolve(&self, image_count: u32) -> Result<(Vec<u32>, u32), String> {
+        let (images, columns) = match self {
+            Output::Last => (Vec::from([ image_count - 1 ]), 1),
+            Output::Image(id) => (Vec::from([ *id ]), 1),
+            Output::Split(ids) => (ids.clone(), ids.len() as u32),
+            Output::Grid(ids, columns) => (ids.clone(), *columns),
+        };
+
+        if images.is_empty() {
+            return Err("The output contains no images".to_string());
+        }
+        if columns == 0 {
+            return Err("A grid output needs at least one column".to_string());
+        }
+        if let Some(id) = images.iter().find(|&&id| id >= image_count) {
+            return Err(format!("Output image {} isn't used by any pass or declared as an image resource", id));
+        }
+
+        let columns = columns.min(images.len() as u32);
+        Ok((images, columns))
+    }
+}
+
+/// Whether images of both formats can be blitted into each other, integer formats are only blitted to the same channel type.
+fn blit_compatible(a: ImageFormat, b: ImageFormat) -> bool {
+    a.channel_type() == b.channel_type() || (!a.is_integer() && !b.is_integer())
+}
+
+/// The passes and resources the orchestrator runs, built in code or read from a project file with `DrawConfig::from_file`.
 pub struct DrawConfig {
     pub passes: Vec<Pass>,
+    pub images: Vec<ImageResource>,
+    pub buffers: Vec<BufferResource>,
+    /// Shader parameters, their values are set through `ParamHandle`.
+    pub params: Vec<Param>,
+    pub output: Output,
+    /// Directories searched for included files, after the directory of the including file.
+    pub include_dirs: Vec<String>,
+    /// Directory compiled shaders and the Vulkan pipeline cache are stored in, so unchanged shaders start faster.
+    /// Defaults to `kiyo` in the temporary directory, `None` disables caching.
+    /// A project file disables caching with `cache_dir = false`.
+    pub cache_dir: Option<String>,
+}
+
+impl Default for DrawConfig {
+    fn default() -> DrawConfig {
+        DrawConfig::new()
+    }
 }
 
 impl DrawConfig {
+    /// Read the passes and resources of a project file, see `Project`. Relative paths are resolved from the file's directory.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<DrawConfig, Error> {
+        Project::from_file(path).map(|project| project.draw_config)
+    }
+
     pub fn new() -> DrawConfig {
         DrawConfig {
             passes: Vec::new(),
+            images: Vec::new(),
+            buffers: Vec::new(),
+            params: Vec::new(),
+            output: Output::Last,
+            include_dirs: Vec::new(),
+            cache_dir: Some(std::env::temp_dir().join("kiyo").to_string_lossy().to_string()),
         }
     }
 }
 
 pub struct ShaderPass {
+    /// Path of the pass' shader.
+    pub shader: String,
+    /// The workgroup size the pass specifies, see `Pass::workgroup_size`.
+    pub workgroup_size: Option<(u32, u32, u32)>,
+    /// The entry point the pass specifies, see `Pass::entry_point`.
+    pub entry_point: Option<String>,
+    /// Disabled passes aren't dispatched, their output images keep their previous or cleared contents.
+    pub enabled: bool,
     pub compute_pipeline: ComputePipeline,
-    pub dispatches: glam::UVec3,
+    pub dispatches: Dispatch,
+    /// Whether the dispatch covers the first output image, it is recomputed when the images are resized.
+    pub full_screen: bool,
     pub in_images: Vec<u32>,
     pub out_images: Vec<u32>,
+    /// Images which need a memory barrier before this pass can be dispatched.
+    pub barrier_images: Vec<u32>,
+    /// Buffers which need a memory barrier before this pass can be dispatched.
+    pub barrier_buffers: Vec<u32>,
 }
 
-pub struct DrawOrchestrator {
-    pub compute_descriptor_set_layout: DescriptorSetLayout,
-    pub images: Vec<Image>,
-    pub passes: Vec<ShaderPass>,
+impl ShaderPass {
+    /// The pass' shader and every file it includes.
+    pub fn source_files(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        fs::canonicalize(&self.shader).ok().into_iter()
+            .chain(self.compute_pipeline.includes().iter().cloned())
+    }
 }
 
-impl DrawOrchestrator {
-    pub fn new(renderer: &mut Renderer, resolution: UVec2, draw_config: &DrawConfig) -> Result<DrawOrchestrator, PipelineErr> {
+/// What the shaders of all passes are compiled with.
+#[derive(Clone)]
+pub struct ShaderSettings {
+    /// Macros defined for every pass' shader, see the shader environment variables in the readme.
+    pub macros: Vec<(String, String)>,
+    /// Directories searched for included files, see `DrawConfig::include_dirs`.
+    pub include_dirs: Vec<PathBuf>,
+    /// `None` when caching is disabled, see `DrawConfig::cache_dir`.
+    pub cache: Option<ShaderCache>,
+}
+
+impl ShaderSettings {
+    /// Write the device's pipeline cache after pipelines were created, failing to do so only costs startup time.
+    fn save_pipeline_cache(&self, device: &Device) {
+        if let Some(cache) = &self.cache {
+            if let Err(e) = cache.save_pipelines(device) {
+                log::warn!("Failed to save the pipeline cache: {}", e);
+            }
+        }
+    }
+}
+
+/// The images and buffers used by the passes of a draw config, and the descriptor slots they are bound to.
+struct ResourceLayout {
+    image_resources: Vec<ImageResource>,
+    buffer_resources: Vec<BufferResource>,
+    /// Amount of image descriptors, including the history images and mip levels.
+    descriptor_count: u32,
+    /// The `PREVIOUS_<id>`, `MIP_OFFSET_<id>` and `PREVIOUS_MIP_OFFSET_<id>` descriptor slots.
+    slot_macros: Vec<(String, u32)>,
+    output_images: Vec<u32>,
+    output_columns: u32,
+}
 
+impl ResourceLayout {
+    fn new(draw_config: &DrawConfig) -> Result<ResourceLayout, PipelineErr> {
         let image_count = draw_config.passes.iter()
-            .map(|p| p.output_resources.iter())
-            .flatten().max().unwrap() + 1;
+            .flat_map(|p| p.input_resources.iter().chain(p.output_resources.iter()))
+            .chain(draw_config.images.iter().map(|i| &i.id))
+            .max()
+            .ok_or_else(|| PipelineErr::Config("The draw config doesn't use any images".to_string()))? + 1;
+
+        let image_resources = (0..image_count).map(|id| {
+            draw_config.images.iter()
+                .find(|i| i.id == id)
+                .cloned()
+                .unwrap_or_else(|| ImageResource::new(id))
+        }).collect::<Vec<ImageResource>>();
+
+        let ping_pong_resources = image_resources.iter()
+            .filter(|r| r.persistence == Persistence::PingPong)
+            .collect::<Vec<&ImageResource>>();
+
+        // Descriptor slots are laid out as: the current images, the previous frame's images,
+        // followed by the mip levels beyond the first of both, in that same order
+        let mut descriptor_count = image_count;
+        let mut slot_macros = Vec::new();
+        for r in &ping_pong_resources {
+            slot_macros.push((format!("PREVIOUS_{}", r.id), descriptor_count));
+            descriptor_count += 1;
+        }
+        let mip_resources = image_resources.iter()
+            .map(|r| ("MIP_OFFSET", r))
+            .chain(ping_pong_resources.iter().map(|&r| ("PREVIOUS_MIP_OFFSET", r)));
+        for (prefix, r) in mip_resources {
+            if r.mip_levels > 1 {
+                slot_macros.push((format!("{}_{}", prefix, r.id), descriptor_count));
+                descriptor_count += r.mip_levels - 1;
+            }
+        }
+
+        let buffer_count = draw_config.passes.iter()
+            .flat_map(|p| p.input_buffers.iter().chain(p.output_buffers.iter()).copied().chain(p.indirect_buffer()))
+            .chain(draw_config.buffers.iter().map(|b| b.id))
+            .max().map_or(0, |id| id + 1);
+
+        let buffer_resources = (0..buffer_count).map(|id| {
+            draw_config.buffers.iter()
+                .find(|b| b.id == id)
+                .cloned()
+                .ok_or_else(|| PipelineErr::Config(format!("Buffer {} is used by a pass, but isn't declared in the draw config", id)))
+        }).collect::<Result<Vec<BufferResource>, PipelineErr>>()?;
+        for r in &buffer_resources {
+            let size = r.size.byte_size();
+            if size == 0 {
+                return Err(PipelineErr::Config(format!("Buffer {} has a size of 0 bytes", r.id)));
+            }
+            if let Some(data) = r.initial_data.as_ref().filter(|data| data.len() as u64 > size) {
+                return Err(PipelineErr::Config(format!(
+                    "The initial data of buffer {} is {} bytes, larger than the buffer's {} bytes",
+                    r.id, data.len(), size
+                )));
+            }
+        }
+
+        let (output_images, output_columns) = draw_config.output.resolve(image_count).map_err(PipelineErr::Config)?;
+        let output_format = image_resources[output_images[0] as usize].format;
+        if let Some(&id) = output_images.iter().find(|&&id| !blit_compatible(output_format, image_resources[id as usize].format)) {
+            return Err(PipelineErr::Config(format!(
+                "Output image {} with format {:?} can't be composed with format {:?}",
+                id, image_resources[id as usize].format, output_format
+            )));
+        }
+
+        Ok(ResourceLayout {
+            image_resources,
+            buffer_resources,
+            descriptor_count,
+            slot_macros,
+            output_images,
+            output_columns,
+        })
+    }
+}
+
+/// The pipelines of all passes of a draw config, compiled before the orchestrator is created.
+/// Compiling doesn't need the renderer, so it can run on another thread, see `DrawOrchestrator::from_compiled`.
+pub struct CompiledPasses {
+    layout: ResourceLayout,
+    descriptor_set_layout: Arc<DescriptorSetLayout>,
+    shader_settings: ShaderSettings,
+    /// The pipeline of every pass, in the order of `DrawConfig::passes`.
+    pipelines: Vec<ComputePipeline>,
+}
+
+impl CompiledPasses {
+    pub fn new(device: &Device, draw_config: &DrawConfig) -> Result<CompiledPasses, Error> {
+        let layout = ResourceLayout::new(draw_config)?;
+        let buffer_count = layout.buffer_resources.len() as u32;
 
         // Layout
-        let layout_bindings = &[
+        let mut layout_bindings = vec![
             vk::DescriptorSetLayoutBinding::default()
                 .binding(0)
                 .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
-                .descriptor_count(image_count)
+                .descriptor_count(layout.descriptor_count)
                 .stage_flags(vk::ShaderStageFlags::COMPUTE | vk::ShaderStageFlags::FRAGMENT)
         ];
-        let compute_descriptor_set_layout = DescriptorSetLayout::new_push_descriptor(
-            &renderer.device,
-            layout_bindings
+        if buffer_count > 0 {
+            layout_bindings.push(
+                vk::DescriptorSetLayoutBinding::default()
+                    .binding(1)
+                    .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                    .descriptor_count(buffer_count)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+            );
+        }
+        layout_bindings.push(
+            vk::DescriptorSetLayoutBinding::default()
+                .binding(2)
+                .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::COMPUTE)
         );
+        validate_params(&draw_config.params).map_err(PipelineErr::Config)?;
+        if !draw_config.params.is_empty() {
+            layout_bindings.push(
+                vk::DescriptorSetLayoutBinding::default()
+                    .binding(3)
+                    .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                    .descriptor_count(1)
+                    .stage_flags(vk::ShaderStageFlags::COMPUTE)
+            );
+        }
+        let descriptor_set_layout = Arc::new(DescriptorSetLayout::new_push_descriptor(
+            device,
+            &layout_bindings
+        )?);
+
+        // Declare an image array for every format in use, all aliasing the same binding
+        let mut formats: Vec<ImageFormat> = Vec::new();
+        for r in &layout.image_resources {
+            if !formats.contains(&r.format) {
+                formats.push(r.format);
+            }
+        }
+        let image_bindings = formats.iter()
+            .map(|f| format!(
+                "layout( binding = 0, {} ) uniform {} images_{}[NUM_IMAGES];",
+                f.glsl_qualifier(),
+                f.glsl_image_type(),
+                f.glsl_qualifier()
+            ))
+            .collect::<Vec<String>>()
+            .join(" ");
+
+        let mut shader_macros = Vec::from([
+            ("NUM_IMAGES".to_string(), layout.descriptor_count.to_string()),
+            ("WORKGROUP_SIZE".to_string(), DrawOrchestrator::default_workgroup_size(device).to_string()),
+            ("IMAGE_BINDINGS".to_string(), image_bindings),
+        ]);
+        if !draw_config.params.is_empty() {
+            shader_macros.push(("PARAM_BINDINGS".to_string(), glsl_declaration(&draw_config.params)));
+        }
+        for (name, slot) in &layout.slot_macros {
+            shader_macros.push((name.clone(), slot.to_string()));
+        }
+        shader_macros.push(("NUM_BUFFERS".to_string(), buffer_count.to_string()));
+        for r in &layout.buffer_resources {
+            shader_macros.push((format!("BUFFER_LEN_{}", r.id), r.size.element_count().to_string()));
+        }
+
+        let shader_settings = ShaderSettings {
+            macros: shader_macros,
+            include_dirs: draw_config.include_dirs.iter().map(PathBuf::from).collect(),
+            cache: draw_config.cache_dir.as_ref().map(ShaderCache::new),
+        };
+        let pipelines = draw_config.passes.iter()
+            .map(|c| DrawOrchestrator::create_pipeline(device, &descriptor_set_layout, &c.shader, c.entry_point.as_deref(), c.workgroup_size, &shader_settings))
+            .collect::<Result<Vec<ComputePipeline>, Error>>()?;
+
+        Ok(CompiledPasses {
+            layout,
+            descriptor_set_layout,
+            shader_settings,
+            pipelines,
+        })
+    }
+}
+
+/// The passes to recompile after their shaders changed, see `DrawOrchestrator::reload_job`.
+/// Like `CompiledPasses`, it can be compiled on another thread while the orchestrator keeps drawing.
+pub struct ReloadJob {
+    device: Device,
+    descriptor_set_layout: Arc<DescriptorSetLayout>,
+    shader_settings: ShaderSettings,
+    passes: Vec<ReloadPass>,
+}
+
+/// An affected pass of a `ReloadJob`, with what its pipeline is compiled from.
+struct ReloadPass {
+    index: usize,
+    shader: String,
+    entry_point: Option<String>,
+    workgroup_size: Option<(u32, u32, u32)>,
+}
+
+impl ReloadJob {
+    /// Compile the pipelines of the affected passes. When a shader fails to compile, none of them are returned.
+    pub fn compile(self) -> Result<ReloadedPasses, Error> {
+        let pipelines = self.passes.iter()
+            .map(|p| {
+                let pipeline = DrawOrchestrator::create_pipeline(&self.device, &self.descriptor_set_layout, &p.shader, p.entry_point.as_deref(), p.workgroup_size, &self.shader_settings)?;
+                Ok((p.index, pipeline))
+            })
+            .collect::<Result<Vec<(usize, ComputePipeline)>, Error>>()?;
+        Ok(ReloadedPasses { pipelines })
+    }
+}
+
+/// The pipelines compiled by a `ReloadJob`, swapped in by `DrawOrchestrator::apply_reload`.
+pub struct ReloadedPasses {
+    pipelines: Vec<(usize, ComputePipeline)>,
+}
+
+pub struct DrawOrchestrator {
+    pub compute_descriptor_set_layout: Arc<DescriptorSetLayout>,
+    pub shader_settings: ShaderSettings,
+    /// The image written during the current frame, indexed by image id.
+    pub images: Vec<Image>,
+    /// The images written during the previous frame, for each ping-pong image id.
+    pub history_images: Vec<(u32, Image)>,
+    /// The declaration of each image, indexed by image id.
+    pub image_resources: Vec<ImageResource>,
+    /// Storage buffers, indexed by buffer id.
+    pub buffers: Vec<Buffer>,
+    /// Host visible copies of the initial buffer contents, indexed by buffer id.
+    pub initial_buffers: Vec<Option<Buffer>>,
+    /// Per-frame engine values, updated by the renderer at the start of every frame.
+    pub engine_uniforms: Buffer,
+    /// The resolution relative image sizes are based on.
+    pub resolution: UVec2,
+    pub params: Vec<Param>,
+    /// Uniform buffer holding the parameter values, `None` without parameters.
+    pub param_buffer: Option<Buffer>,
+    pub passes: Vec<ShaderPass>,
+    /// When set, persistent images and buffers are reset at the start of the next frame.
+    pub clear_history: bool,
+    /// Ids of the presented images, see `Output`.
+    pub output_images: Vec<u32>,
+    /// Amount of columns the output images are arranged in.
+    pub output_columns: u32,
+    /// The image the output images are composed into, `None` when a single image is presented.
+    pub composite: Option<Image>,
+}
+
+impl DrawOrchestrator {
+    pub fn new(renderer: &mut Renderer, resolution: UVec2, draw_config: &DrawConfig) -> Result<DrawOrchestrator, Error> {
+        // Merging into the device's pipeline cache can't overlap with creating pipelines, so it's only done here
+        if let Some(cache) = draw_config.cache_dir.as_ref().map(ShaderCache::new) {
+            if let Err(e) = cache.load_pipelines(&renderer.device) {
+                log::warn!("Failed to load the pipeline cache: {}", e);
+            }
+        }
+        let compiled = CompiledPasses::new(&renderer.device, draw_config)?;
+        Self::from_compiled(renderer, resolution, draw_config, compiled)
+    }
+
+    /// Create the images, buffers and passes of `draw_config`, using the pipelines compiled from it.
+    pub fn from_compiled(renderer: &mut Renderer, resolution: UVec2, draw_config: &DrawConfig, compiled: CompiledPasses) -> Result<DrawOrchestrator, Error> {
+        let CompiledPasses { layout, descriptor_set_layout, shader_settings, pipelines } = compiled;
+        let ResourceLayout { image_resources, buffer_resources, output_images, output_columns, .. } = layout;
+        let output_format = image_resources[output_images[0] as usize].format;
+        let ping_pong_resources = image_resources.iter()
+            .filter(|r| r.persistence == Persistence::PingPong)
+            .collect::<Vec<&ImageResource>>();
 
         // Images
-        let images = (0..image_count).map(|_| {
-            Image::new(
+        let images = image_resources.iter()
+            .map(|r| Self::create_image(renderer, r, resolution))
+            .collect::<Result<Vec<Image>, Error>>()?;
+        let composite = Self::create_composite(renderer, &images, &output_images, output_columns, output_format)?;
+        let history_images = ping_pong_resources.iter()
+            .map(|&r| Ok((r.id, Self::create_image(renderer, r, resolution)?)))
+            .collect::<Result<Vec<(u32, Image)>, Error>>()?;
+
+        // Buffers, the initial contents are uploaded at the start of the first frame
+        let buffers = buffer_resources.iter().map(|r| {
+            Buffer::new(
+                &renderer.device,
+                &mut renderer.allocator,
+                r.size.byte_size(),
+                vk::BufferUsageFlags::STORAGE_BUFFER | vk::BufferUsageFlags::INDIRECT_BUFFER | vk::BufferUsageFlags::TRANSFER_SRC | vk::BufferUsageFlags::TRANSFER_DST,
+                MemoryLocation::GpuOnly
+            )
+        }).collect::<Result<Vec<Buffer>, Error>>()?;
+        let initial_buffers = buffer_resources.iter().map(|r| {
+            r.initial_data.as_ref().map(|data| {
+                let mut staging_buffer = Buffer::new(
+                    &renderer.device,
+                    &mut renderer.allocator,
+                    r.size.byte_size(),
+                    vk::BufferUsageFlags::TRANSFER_SRC,
+                    MemoryLocation::CpuToGpu
+                )?;
+                let mapped = staging_buffer.mapped_slice_mut()?;
+                mapped[..data.len()].copy_from_slice(data);
+                mapped[data.len()..].fill(0);
+                Ok(staging_buffer)
+            }).transpose()
+        }).collect::<Result<Vec<Option<Buffer>>, Error>>()?;
+
+        let engine_uniforms = Buffer::new(
+            &renderer.device,
+            &mut renderer.allocator,
+            size_of::<EngineUniforms>() as u64,
+            vk::BufferUsageFlags::UNIFORM_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
+            MemoryLocation::GpuOnly
+        )?;
+
+        let param_buffer = (!draw_config.params.is_empty()).then(|| {
+            Buffer::new(
                 &renderer.device,
                 &mut renderer.allocator,
-                resolution.x,
-                resolution.y,
-                vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::TRANSFER_SRC | vk::ImageUsageFlags::TRANSFER_DST
+                renderer.params.pack(&draw_config.params).len() as u64,
+                vk::BufferUsageFlags::UNIFORM_BUFFER | vk::BufferUsageFlags::TRANSFER_DST,
+                MemoryLocation::GpuOnly
             )
-        }).collect::<Vec<Image>>();
+        }).transpose()?;
 
         // Transition images
-        let image_command_buffer = Arc::new(CommandBuffer::new(&renderer.device, &renderer.command_pool));
-        image_command_buffer.begin();
+        let image_command_buffer = Arc::new(CommandBuffer::new(&renderer.device, &renderer.command_pool)?);
+        image_command_buffer.begin()?;
         {
-            for image in &images {
-                renderer.transition_image(&image_command_buffer, &image.handle(), vk::ImageLayout::UNDEFINED, vk::ImageLayout::GENERAL, vk::PipelineStageFlags::TOP_OF_PIPE, vk::PipelineStageFlags::BOTTOM_OF_PIPE, vk::AccessFlags::empty(), vk::AccessFlags::empty());
+            for image in images.iter().chain(history_images.iter().map(|(_, i)| i)) {
+                renderer.transition_image(&image_command_buffer, image.handle(), vk::ImageLayout::UNDEFINED, vk::ImageLayout::GENERAL, vk::PipelineStageFlags::TOP_OF_PIPE, vk::PipelineStageFlags::BOTTOM_OF_PIPE, vk::AccessFlags::empty(), vk::AccessFlags::empty());
             }
         }
-        image_command_buffer.end();
-        renderer.device.submit_single_time_command(renderer.queue, image_command_buffer);
-
-        let push_constant_ranges = &[
-            vk::PushConstantRange::default()
-                .stage_flags(vk::ShaderStageFlags::COMPUTE)
-                .offset(0)
-                .size(size_of::<PushConstants>() as u32),
-        ];
-
-        let workgroup_size = 32;
-        let full_screen_dispatches = UVec3::new(
-            (resolution.x as f32 / workgroup_size as f32).ceil() as u32,
-            (resolution.y as f32 / workgroup_size as f32).ceil() as u32,
-            1
-        );
+        image_command_buffer.end()?;
+        renderer.device.submit_single_time_command(renderer.queue, &image_command_buffer)?;
 
-        let mut macros: HashMap<&str, &dyn ToString> = HashMap::new();
-        macros.insert("NUM_IMAGES", &image_count);
-        macros.insert("WORKGROUP_SIZE", &workgroup_size);
+        let pass_barriers = Self::resolve_pass_barriers(&draw_config.passes);
 
         // Passes
         let passes = draw_config.passes
             .iter()
-            .map(|c| {
-                let compute_pipeline = ComputePipeline::new(
-                    &renderer.device,
-                    c.shader.to_string(),
-                    &[&compute_descriptor_set_layout],
-                    push_constant_ranges,
-                    &macros
-                )?;
+            .zip(pass_barriers)
+            .zip(pipelines)
+            .map(|((c, barriers), compute_pipeline)| {
+                let local_size = compute_pipeline.local_size();
 
                 let dispatches = match c.dispatches {
                     DispatchConfig::Count(x, y, z) => {
-                        UVec3::new(x, y, z)
+                        Dispatch::Direct(UVec3::new(x, y, z))
                     }
                     DispatchConfig::FullScreen => {
-                        full_screen_dispatches
+                        Dispatch::Direct(Self::full_screen_dispatch(&images, &c.output_resources, resolution, local_size))
+                    }
+                    DispatchConfig::Indirect { buffer, offset } => {
+                        // VkDispatchIndirectCommand is three 32-bit workgroup counts
+                        let buffer_size = buffer_resources[buffer as usize].size.byte_size();
+                        if offset % 4 != 0 || offset + 12 > buffer_size {
+                            return Err(PipelineErr::Config(format!(
+                                "Indirect dispatch offset {} of buffer {} is unaligned or out of bounds",
+                                offset, buffer
+                            )).into());
+                        }
+                        Dispatch::Indirect { buffer, offset }
                     }
                 };
 
+                let mut barrier_images = Vec::new();
+                let mut barrier_buffers = Vec::new();
+                for resource in barriers {
+                    match resource {
+                        ResourceId::Image(id) => barrier_images.push(id),
+                        ResourceId::Buffer(id) => barrier_buffers.push(id),
+                    }
+                }
+
                 Ok(ShaderPass {
+                    shader: c.shader.clone(),
+                    workgroup_size: c.workgroup_size,
+                    entry_point: c.entry_point.clone(),
+                    enabled: true,
                     compute_pipeline,
-                    dispatches: dispatches,
+                    dispatches,
+                    full_screen: matches!(c.dispatches, DispatchConfig::FullScreen),
                     in_images: c.input_resources.clone(),
                     out_images: c.output_resources.clone(),
+                    barrier_images,
+                    barrier_buffers,
                 })
             })
-            .collect::<Result<Vec<ShaderPass>, PipelineErr>>()?;
+            .collect::<Result<Vec<ShaderPass>, Error>>()?;
+        shader_settings.save_pipeline_cache(&renderer.device);
 
         Ok(DrawOrchestrator {
-            compute_descriptor_set_layout,
+            shader_settings,
+            compute_descriptor_set_layout: descriptor_set_layout,
             images,
-            passes
+            history_images,
+            image_resources,
+            buffers,
+            initial_buffers,
+            engine_uniforms,
+            resolution,
+            params: draw_config.params.clone(),
+            param_buffer,
+            passes,
+            clear_history: true,
+            output_images,
+            output_columns,
+            composite,
+        })
+    }
+
+    /// The largest square workgroup up to 32x32 the device supports.
+    fn default_workgroup_size(device: &Device) -> u32 {
+        let limits = device.limits();
+        let mut workgroup_size: u32 = 32;
+        while workgroup_size > 1 && (
+            workgroup_size * workgroup_size > limits.max_compute_work_group_invocations ||
+            workgroup_size > limits.max_compute_work_group_size[0] ||
+            workgroup_size > limits.max_compute_work_group_size[1]
+        ) {
+            workgroup_size /= 2;
+        }
+        workgroup_size
+    }
+
+    /// Compile the pipeline of a pass, `workgroup_size` is the size the pass specifies, if any.
+    fn create_pipeline(
+        device: &Device,
+        descriptor_set_layout: &DescriptorSetLayout,
+        shader: &str,
+        entry_point: Option<&str>,
+        workgroup_size: Option<(u32, u32, u32)>,
+        shader_settings: &ShaderSettings
+    ) -> Result<ComputePipeline, Error> {
+        let default_size = Self::default_workgroup_size(device);
+        let (size_x, size_y, size_z) = workgroup_size.unwrap_or((default_size, default_size, 1));
+        let mut macros: HashMap<&str, &dyn ToString> = shader_settings.macros.iter()
+            .map(|(name, value)| (name.as_str(), value as &dyn ToString))
+            .collect();
+        macros.insert("WORKGROUP_SIZE_X", &size_x);
+        macros.insert("WORKGROUP_SIZE_Y", &size_y);
+        macros.insert("WORKGROUP_SIZE_Z", &size_z);
+
+        let push_constant_ranges = &[
+            vk::PushConstantRange::default()
+                .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                .offset(0)
+                .size(size_of::<PushConstants>() as u32),
+        ];
+
+        let shader_code = load_shader_code(
+            shader.to_string(),
+            shaderc::ShaderKind::Compute,
+            entry_point.unwrap_or("main"),
+            &macros,
+            &shader_settings.include_dirs,
+            shader_settings.cache.as_ref()
+        )?;
+        let compute_pipeline = ComputePipeline::from_shader(device, shader, shader_code, &[descriptor_set_layout], push_constant_ranges)?;
+
+        // The shader may declare its own local size instead of using the macros
+        let local_size = compute_pipeline.local_size();
+        if let Some(size) = workgroup_size.filter(|s| [s.0, s.1, s.2] != local_size) {
+            return Err(PipelineErr::Validation(format!(
+                "{}: the pass specifies workgroup size {:?}, but the shader declares local size {:?}",
+                shader, size, local_size
+            )).into());
+        }
+        Ok(compute_pipeline)
+    }
+
+    /// Recompile the passes whose shader, or a file it includes, is one of the `changed` files, keeping all images and buffers.
+    /// Returns the amount of recompiled passes. When a shader fails to compile, none of the passes are changed.
+    pub fn reload_shaders(&mut self, renderer: &mut Renderer, changed: &[PathBuf]) -> Result<usize, Error> {
+        match self.reload_job(renderer, changed) {
+            Some(job) => {
+                let reloaded = job.compile()?;
+                self.apply_reload(renderer, reloaded)
+            },
+            None => Ok(0),
+        }
+    }
+
+    /// The passes whose shader, or a file it includes, is one of the `changed` files. `None` when no pass is affected.
+    pub fn reload_job(&self, renderer: &Renderer, changed: &[PathBuf]) -> Option<ReloadJob> {
+        let changed = changed.iter()
+            .filter_map(|path| fs::canonicalize(path).ok())
+            .collect::<Vec<PathBuf>>();
+        let passes = self.passes.iter().enumerate()
+            .filter(|(_, pass)| pass.source_files().any(|path| changed.contains(&path)))
+            .map(|(index, pass)| ReloadPass {
+                index,
+                shader: pass.shader.clone(),
+                entry_point: pass.entry_point.clone(),
+                workgroup_size: pass.workgroup_size,
+            })
+            .collect::<Vec<ReloadPass>>();
+        if passes.is_empty() {
+            return None;
+        }
+
+        Some(ReloadJob {
+            device: renderer.device.clone(),
+            descriptor_set_layout: self.compute_descriptor_set_layout.clone(),
+            shader_settings: self.shader_settings.clone(),
+            passes,
         })
     }
-}
\ No newline at end of file
+
+    /// Swap in the pipelines of a `ReloadJob` created by this orchestrator, returns the amount of recompiled passes.
+    pub fn apply_reload(&mut self, renderer: &mut Renderer, reloaded: ReloadedPasses) -> Result<usize, Error> {
+        // Frames in flight may still be using the old pipelines
+        renderer.device.wait_idle()?;
+        let count = reloaded.pipelines.len();
+        for (i, compute_pipeline) in reloaded.pipelines {
+            let pass = &mut self.passes[i];
+            pass.compute_pipeline = compute_pipeline;
+            if pass.full_screen {
+                let local_size = pass.compute_pipeline.local_size();
+                pass.dispatches = Dispatch::Direct(Self::full_screen_dispatch(&self.images, &pass.out_images, self.resolution, local_size));
+            }
+        }
+        self.shader_settings.save_pipeline_cache(&renderer.device);
+        Ok(count)
+    }
+
+    /// The image which is presented, the composite image when several images are composed.
+    pub fn output_image(&self) -> &Image {
+        match &self.composite {
+            Some(composite) => composite,
+            None => &self.images[self.output_images[0] as usize],
+        }
+    }
+
+    pub fn output_format(&self) -> ImageFormat {
+        self.image_resources[self.output_images[0] as usize].format
+    }
+
+    /// Every image paired with its declaration, in descriptor slot order.
+    pub fn all_images(&self) -> impl Iterator<Item = (&Image, &ImageResource)> {
+        self.images.iter()
+            .zip(self.image_resources.iter())
+            .chain(self.history_images.iter().map(|(id, image)| (image, &self.image_resources[*id as usize])))
+    }
+
+    fn create_image(renderer: &mut Renderer, r: &ImageResource, resolution: UVec2) -> Result<Image, Error> {
+        let size = r.size.resolve(resolution);
+        Image::new(
+            &renderer.device,
+            &mut renderer.allocator,
+            size.x,
+            size.y,
+            r.format.vk_format(),
+            r.mip_levels,
+            vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::TRANSFER_SRC | vk::ImageUsageFlags::TRANSFER_DST
+        )
+    }
+
+    /// The image several output images are composed into, sized to a grid of cells the size of the first image.
+    /// It's cleared once, the cells are overwritten every frame while empty cells keep the clear color.
+    fn create_composite(renderer: &mut Renderer, images: &[Image], output_images: &[u32], columns: u32, format: ImageFormat) -> Result<Option<Image>, Error> {
+        if output_images.len() < 2 {
+            return Ok(None);
+        }
+
+        let cell = &images[output_images[0] as usize];
+        let rows = (output_images.len() as u32).div_ceil(columns);
+        let composite = Image::new(
+            &renderer.device,
+            &mut renderer.allocator,
+            cell.width * columns,
+            cell.height * rows,
+            format.vk_format(),
+            1,
+            vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::TRANSFER_SRC | vk::ImageUsageFlags::TRANSFER_DST
+        )?;
+
+        let command_buffer = CommandBuffer::new(&renderer.device, &renderer.command_pool)?;
+        command_buffer.begin()?;
+        renderer.transition_image(&command_buffer, composite.handle(), vk::ImageLayout::UNDEFINED, vk::ImageLayout::TRANSFER_DST_OPTIMAL, vk::PipelineStageFlags::TOP_OF_PIPE, vk::PipelineStageFlags::TRANSFER, vk::AccessFlags::empty(), vk::AccessFlags::TRANSFER_WRITE);
+        unsafe {
+            renderer.device.handle().cmd_clear_color_image(
+                command_buffer.handle(),
+                *composite.handle(),
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &format.clear_value(),
+                &[vk::ImageSubresourceRange {
+                    aspect_mask: vk::ImageAspectFlags::COLOR,
+                    base_mip_level: 0,
+                    level_count: 1,
+                    base_array_layer: 0,
+                    layer_count: 1,
+                }]
+            );
+        }
+        renderer.transition_image(&command_buffer, composite.handle(), vk::ImageLayout::TRANSFER_DST_OPTIMAL, vk::ImageLayout::GENERAL, vk::PipelineStageFlags::TRANSFER, vk::PipelineStageFlags::TRANSFER, vk::AccessFlags::TRANSFER_WRITE, vk::AccessFlags::TRANSFER_WRITE);
+        command_buffer.end()?;
+        renderer.device.submit_single_time_command(renderer.queue, &command_buffer)?;
+
+        Ok(Some(composite))
+    }
+
+    /// Workgroup counts covering the first output image, which isn't necessarily at output resolution.
+    fn full_screen_dispatch(images: &[Image], output_resources: &[u32], resolution: UVec2, local_size: [u32; 3]) -> UVec3 {
+        let size = output_resources.first()
+            .map(|&id| {
+                let image = &images[id as usize];
+                UVec2::new(image.width, image.height)
+            })
+            .unwrap_or(resolution);
+        UVec3::new(
+            size.x.div_ceil(local_size[0]),
+            size.y.div_ceil(local_size[1]),
+            1
+        )
+    }
+
+    /// Recreate the images which are sized relative to the output resolution and recompute the full screen dispatches.
+    /// The contents of recreated images are cleared, including the history of persistent images.
+    pub fn resize(&mut self, renderer: &mut Renderer, resolution: UVec2) -> Result<(), Error> {
+
+        // Frames in flight may still be using the old images
+        renderer.device.wait_idle()?;
+        self.resolution = resolution;
+
+        let mut resized = Vec::new();
+        for r in &self.image_resources {
+            if let ImageSize::Relative(_) = r.size {
+                self.images[r.id as usize] = Self::create_image(renderer, r, resolution)?;
+                resized.push(r.id);
+            }
+        }
+        for (id, history_image) in &mut self.history_images {
+            if resized.contains(id) {
+                *history_image = Self::create_image(renderer, &self.image_resources[*id as usize], resolution)?;
+            }
+        }
+
+        let command_buffer = CommandBuffer::new(&renderer.device, &renderer.command_pool)?;
+        command_buffer.begin()?;
+        let resized_images = self.images.iter().enumerate()
+            .filter(|(id, _)| resized.contains(&(*id as u32)))
+            .chain(self.history_images.iter().filter(|(id, _)| resized.contains(id)).map(|(id, i)| (*id as usize, i)));
+        for (id, image) in resized_images {
+            renderer.transition_image(&command_buffer, image.handle(), vk::ImageLayout::UNDEFINED, vk::ImageLayout::TRANSFER_DST_OPTIMAL, vk::PipelineStageFlags::TOP_OF_PIPE, vk::PipelineStageFlags::TRANSFER, vk::AccessFlags::empty(), vk::AccessFlags::TRANSFER_WRITE);
+            unsafe {
+                renderer.device.handle().cmd_clear_color_image(
+                    command_buffer.handle(),
+                    *image.handle(),
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    &self.image_resources[id].format.clear_value(),
+                    &[vk::ImageSubresourceRange {
+                        aspect_mask: vk::ImageAspectFlags::COLOR,
+                        base_mip_level: 0,
+                        level_count: vk::REMAINING_MIP_LEVELS,
+                        base_array_layer: 0,
+                        layer_count: 1,
+                    }]
+                );
+            }
+            renderer.transition_image(&command_buffer, image.handle(), vk::ImageLayout::TRANSFER_DST_OPTIMAL, vk::ImageLayout::GENERAL, vk::PipelineStageFlags::TRANSFER, vk::PipelineStageFlags::COMPUTE_SHADER, vk::AccessFlags::TRANSFER_WRITE, vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE);
+        }
+        command_buffer.end()?;
+        renderer.device.submit_single_time_command(renderer.queue, &command_buffer)?;
+
+        self.composite = Self::create_composite(renderer, &self.images, &self.output_images, self.output_columns, self.output_format())?;
+
+        for pass in &mut self.passes {
+            if pass.full_screen {
+                let local_size = pass.compute_pipeline.local_size();
+                pass.dispatches = Dispatch::Direct(Self::full_screen_dispatch(&self.images, &pass.out_images, resolution, local_size));
+            }
+        }
+        Ok(())
+    }
+
+    /// Clear all persistent and ping-pong images and restore the initial buffer contents at the start of the next frame.
+    pub fn reset_history(&mut self) {
+        self.clear_history = true;
+    }
+
+    /// Called once a frame has been recorded, so the next frame reads this frame's ping-pong images as its history.
+    pub fn end_frame(&mut self) {
+        for (id, history_image) in &mut self.history_images {
+            std::mem::swap(&mut self.images[*id as usize], history_image);
+        }
+        self.clear_history = false;
+    }
+
+    /// Builds the dependency graph between passes from their input and output resources.
+    /// Returns, for every pass, the resources which have to be synchronized before it is dispatched.
+    ///
+    /// - Read after write: a pass reads a resource written by an earlier pass.
+    /// - Write after write / write after read: a pass writes a resource an earlier pass accessed.
+    ///
+    /// A barrier orders all previously recorded compute work, so pending reads are resolved
+    /// by any barrier. Pending writes are only made visible for the resources in the barrier.
+    /// Passes which don't share resources are left without barriers so they can overlap.
+    fn resolve_pass_barriers(passes: &[Pass]) -> Vec<Vec<ResourceId>> {
+        let mut pending_writes: HashSet<ResourceId> = HashSet::new();
+        let mut pending_reads: HashSet<ResourceId> = HashSet::new();
+
+        passes.iter().map(|pass| {
+            let mut barriers = pass.reads()
+                .chain(pass.writes())
+                .filter(|id| pending_writes.contains(id))
+                .collect::<Vec<ResourceId>>();
+            barriers.extend(
+                pass.writes().filter(|id| pending_reads.contains(id))
+            );
+            barriers.sort_unstable();
+            barriers.dedup();
+
+            if !barriers.is_empty() {
+                pending_reads.clear();
+                for id in &barriers {
+                    pending_writes.remove(id);
+                }
+            }
+
+            pending_reads.extend(pass.reads());
+            pending_writes.extend(pass.writes());
+
+            barriers
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ResourceId::{Buffer, Image};
+
+    fn pass(input_resources: &[u32], output_resources: &[u32]) -> Pass {
+        Pass {
+            shader: String::new(),
+            dispatches: DispatchConfig::FullScreen,
+            input_resources: input_resources.to_vec(),
+            output_resources: output_resources.to_vec(),
+            input_buffers: Vec::new(),
+            output_buffers: Vec::new(),
+            workgroup_size: None,
+            entry_point: None,
+        }
+    }
+
+    fn barriers(passes: &[Pass]) -> Vec<Vec<ResourceId>> {
+        DrawOrchestrator::resolve_pass_barriers(passes)
+    }
+
+    #[test]
+    fn synchronizes_blur_chain() {
+        let passes = [pass(&[], &[0]), pass(&[0], &[1]), pass(&[1], &[2])];
+        assert_eq!(barriers(&passes), [vec![], vec![Image(0)], vec![Image(1)]]);
+    }
+
+    #[test]
+    fn leaves_independent_passes_without_barriers() {
+        let passes = [pass(&[], &[0]), pass(&[], &[1]), pass(&[0, 1], &[2])];
+        assert_eq!(barriers(&passes), [vec![], vec![], vec![Image(0), Image(1)]]);
+    }
+
+    #[test]
+    fn synchronizes_write_after_write() {
+        let passes = [pass(&[], &[0]), pass(&[], &[0])];
+        assert_eq!(barriers(&passes), [vec![], vec![Image(0)]]);
+    }
+
+    #[test]
+    fn synchronizes_write_after_read() {
+        let passes = [pass(&[0], &[1]), pass(&[], &[0])];
+        assert_eq!(barriers(&passes), [vec![], vec![Image(0)]]);
+
+        // The barrier before the second pass already orders the read of the first one
+        let passes = [pass(&[0], &[1]), pass(&[1], &[2]), pass(&[], &[0])];
+        assert_eq!(barriers(&passes), [vec![], vec![Image(1)], vec![]]);
+    }
+
+    #[test]
+    fn synchronizes_persistent_image() {
+        // An accumulation pass reads and writes its persistent image, later passes read the result
+        let passes = [pass(&[0], &[0]), pass(&[0], &[0]), pass(&[0], &[1])];
+        assert_eq!(barriers(&passes), [vec![], vec![Image(0)], vec![Image(0)]]);
+    }
+
+    #[test]
+    fn synchronizes_buffers() {
+        let mut spawn = pass(&[], &[]);
+        spawn.output_buffers = vec![0];
+        let mut draw = pass(&[], &[0]);
+        draw.dispatches = DispatchConfig::Indirect { buffer: 0, offset: 0 };
+        let mut update = pass(&[], &[]);
+        update.input_buffers = vec![1];
+        update.output_buffers = vec![0];
+        assert_eq!(barriers(&[spawn, draw, update]), [vec![], vec![Buffer(0)], vec![Buffer(0)]]);
+    }
+
+    #[test]
+    fn resolves_output() {
+        assert_eq!(Output::Last.resolve(3).unwrap(), (vec![2], 1));
+        assert_eq!(Output::Image(1).resolve(3).unwrap(), (vec![1], 1));
+        assert_eq!(Output::Split(vec![0, 2]).resolve(3).unwrap(), (vec![0, 2], 2));
+        assert_eq!(Output::Grid(vec![0, 1, 2], 2).resolve(3).unwrap(), (vec![0, 1, 2], 2));
+        // Columns beyond the amount of images would only add empty cells
+        assert_eq!(Output::Grid(vec![0, 1], 4).resolve(3).unwrap(), (vec![0, 1], 2));
+    }
+
+    #[test]
+    fn rejects_invalid_output() {
+        assert_eq!(Output::Image(3).resolve(3).err().unwrap(), "Output image 3 isn't used by any pass or declared as an image resource");
+        assert_eq!(Output::Split(vec![0, 5]).resolve(3).err().unwrap(), "Output image 5 isn't used by any pass or declared as an image resource");
+        assert_eq!(Output::Split(vec![]).resolve(3).err().unwrap(), "The output contains no images");
+        assert_eq!(Output::Grid(vec![0, 1], 0).resolve(3).err().unwrap(), "A grid output needs at least one column");
+    }
+}
@@ -0,0 +1,134 @@
+use std::{fs, io};
+use std::path::{Path, PathBuf};
+use ash::vk;
+use glam::UVec2;
+use gpu_allocator::MemoryLocation;
+use image::{DynamicImage, ImageFormat, Rgba32FImage, RgbaImage};
+use log::info;
+use crate::app::{App, DrawOrchestrator, ParamHandle, Renderer};
+use crate::app::draw_orch::{self, ChannelType, DrawConfig};
+use crate::vulkan::Buffer;
+use crate::Error;
+
+/// File format of the frames written to disk.
+pub enum ImageFileFormat {
+    Png,
+    Exr,
+}
+
+impl ImageFileFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            ImageFileFormat::Png => "png",
+            ImageFileFormat::Exr => "exr",
+        }
+    }
+}
+
+pub struct HeadlessConfig {
+    pub width: u32,
+    pub height: u32,
+    /// Amount of frames to render before exiting.
+    pub frame_count: u32,
+    /// Time in seconds between subsequent frames, e.g. `1.0 / 60.0`.
+    pub time_step: f32,
+    /// Directory the frames are written to, it is created when it doesn't exist.
+    pub output_dir: String,
+    pub file_format: ImageFileFormat,
+}
+
+/// Offline renderer which runs the passes without a window and writes every frame to disk.
+/// Doesn't need a surface, so it also runs on software drivers such as lavapipe.
+pub struct HeadlessApp {
+    renderer: Renderer,
+    pub config: HeadlessConfig,
+}
+
+impl HeadlessApp {
+    pub fn new(config: HeadlessConfig) -> Result<HeadlessApp, Error> {
+
+        App::init_logger();
+
+        let renderer = Renderer::new_headless()?;
+
+        Ok(HeadlessApp {
+            renderer,
+            config,
+        })
+    }
+
+    /// Handle to the values of the shader parameters, see `DrawConfig::params`.
+    pub fn params(&self) -> ParamHandle {
+        self.renderer.params.clone()
+    }
+
+    pub fn run(mut self, draw_config: DrawConfig) -> Result<(), Error> {
+
+        let resolution = UVec2::new(self.config.width, self.config.height);
+        let mut orchestrator = DrawOrchestrator::new(&mut self.renderer, resolution, &draw_config)?;
+
+        fs::create_dir_all(&self.config.output_dir)
+            .map_err(|e| Error::Io(PathBuf::from(&self.config.output_dir), e))?;
+
+        let output_image = orchestrator.output_image();
+        let output_format = orchestrator.output_format();
+        let (width, height) = (output_image.width, output_image.height);
+        let target = Buffer::new(
+            &self.renderer.device,
+            &mut self.renderer.allocator,
+            (width * height * output_format.pixel_size()) as u64,
+            vk::BufferUsageFlags::TRANSFER_DST,
+            MemoryLocation::GpuToCpu
+        )?;
+
+        for frame in 0..self.config.frame_count {
+            // Use a fixed time step so the output is deterministic
+            let time = frame as f32 * self.config.time_step;
+            self.renderer.draw_offscreen(&mut orchestrator, time, &target)?;
+
+            let pixels = target.mapped_slice()?;
+            let path = self.frame_path(frame);
+            self.write_frame(&path, width, height, output_format, pixels)?;
+
+            info!("Wrote frame {}/{}: {}", frame + 1, self.config.frame_count, path.display());
+        }
+
+        // Wait for all render operations to finish before exiting
+        self.renderer.device.wait_idle()
+    }
+
+    fn frame_path(&self, frame: u32) -> PathBuf {
+        PathBuf::from(&self.config.output_dir)
+            .join(format!("frame_{:05}.{}", frame, self.config.file_format.extension()))
+    }
+
+    fn write_frame(&self, path: &Path, width: u32, height: u32, format: draw_orch::ImageFormat, pixels: &[u8]) -> Result<(), Error> {
+        let image = Self::decode_pixels(width, height, format, pixels)
+            .ok_or_else(|| Error::Io(path.to_path_buf(), io::Error::other("Readback buffer is too small for the output image")))?;
+
+        // Png output is clamped to 8 bits, exr keeps the full float range
+        match self.config.file_format {
+            ImageFileFormat::Png => {
+                image.to_rgba8().save_with_format(path, ImageFormat::Png)
+            },
+            ImageFileFormat::Exr => {
+                image.to_rgba32f().save_with_format(path, ImageFormat::OpenExr)
+            },
+        }.map_err(|e| Error::Io(path.to_path_buf(), io::Error::other(e)))
+    }
+
+    /// Convert the raw pixels of an image resource into an rgba image.
+    /// Missing color channels are set to zero and a missing alpha channel to one. `None` when `pixels` is too small.
+    fn decode_pixels(width: u32, height: u32, format: draw_orch::ImageFormat, pixels: &[u8]) -> Option<DynamicImage> {
+        let channel_type = format.channel_type();
+        if channel_type == ChannelType::Unorm8 {
+            return RgbaImage::from_raw(width, height, pixels.to_vec()).map(DynamicImage::ImageRgba8);
+        }
+
+        let rgba = pixels.chunks_exact(format.pixel_size() as usize)
+            .flat_map(|pixel| format.decode_pixel(pixel))
+            .collect::<Vec<f32>>();
+
+        Rgba32FImage::from_raw(width, height, rgba).map(DynamicImage::ImageRgba32F)
+    }
+}
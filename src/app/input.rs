@@ -0,0 +1,134 @@
+use winit::event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent};
+use winit::keyboard::{KeyCode, PhysicalKey};
+
+/// Keys tracked in the held keys bitmask, the index in this list is the bit index.
+/// Keys are identified by their physical position, so `KeyW` is the same key on every layout.
+pub const TRACKED_KEYS: [KeyCode; 47] = [
+    KeyCode::KeyA, KeyCode::KeyB, KeyCode::KeyC, KeyCode::KeyD, KeyCode::KeyE, KeyCode::KeyF, KeyCode::KeyG,
+    KeyCode::KeyH, KeyCode::KeyI, KeyCode::KeyJ, KeyCode::KeyK, KeyCode::KeyL, KeyCode::KeyM, KeyCode::KeyN,
+    KeyCode::KeyO, KeyCode::KeyP, KeyCode::KeyQ, KeyCode::KeyR, KeyCode::KeyS, KeyCode::KeyT, KeyCode::KeyU,
+    KeyCode::KeyV, KeyCode::KeyW, KeyCode::KeyX, KeyCode::KeyY, KeyCode::KeyZ,
+    KeyCode::Digit0, KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3, KeyCode::Digit4,
+    KeyCode::Digit5, KeyCode::Digit6, KeyCode::Digit7, KeyCode::Digit8, KeyCode::Digit9,
+    KeyCode::Space, KeyCode::Enter, KeyCode::Tab, KeyCode::Backspace,
+    KeyCode::ArrowLeft, KeyCode::ArrowRight, KeyCode::ArrowUp, KeyCode::ArrowDown,
+    KeyCode::ShiftLeft, KeyCode::ControlLeft, KeyCode::AltLeft,
+];
+
+/// Scroll distance of one line when the platform reports scrolling in pixels.
+const PIXELS_PER_LINE: f32 = 16.0;
+
+/// Mouse and keyboard state, tracked from window events.
+/// Positions are in window pixels, with the origin in the top left corner.
+#[derive(Default)]
+pub struct InputState {
+    cursor: [f32; 2],
+    /// Last cursor position while the left button was held.
+    drag: [f32; 2],
+    /// Cursor position when the left button was pressed.
+    click: [f32; 2],
+    /// Whether the left button was pressed during the current frame.
+    clicked: bool,
+    /// Bitmask of held mouse buttons: left, right, middle.
+    buttons: u32,
+    /// Scroll distance in lines during the current frame.
+    scroll: [f32; 2],
+    keys: u64,
+}
+
+/// Input state in the layout it is uploaded to the shaders, see `PushConstants`.
+#[derive(Clone, Copy, Default)]
+pub struct ShaderInput {
+    pub mouse_buttons: u32,
+    pub mouse: [f32; 4],
+    pub scroll: [f32; 2],
+    pub keys: [u32; 2],
+}
+
+impl InputState {
+    pub fn window_event(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor = [position.x as f32, position.y as f32];
+                if self.buttons & 1 != 0 {
+                    self.drag = self.cursor;
+                }
+            },
+            WindowEvent::MouseInput { state, button, .. } => {
+                let bit = match button {
+                    MouseButton::Left => 1,
+                    MouseButton::Right => 2,
+                    MouseButton::Middle => 4,
+                    _ => return,
+                };
+                match state {
+                    ElementState::Pressed => {
+                        self.buttons |= bit;
+                        if *button == MouseButton::Left {
+                            self.drag = self.cursor;
+                            self.click = self.cursor;
+                            self.clicked = true;
+                        }
+                    },
+                    ElementState::Released => self.buttons &= !bit,
+                }
+            },
+            WindowEvent::MouseWheel { delta, .. } => {
+                let (x, y) = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => (*x, *y),
+                    MouseScrollDelta::PixelDelta(p) => (p.x as f32 / PIXELS_PER_LINE, p.y as f32 / PIXELS_PER_LINE),
+                };
+                self.scroll[0] += x;
+                self.scroll[1] += y;
+            },
+            WindowEvent::KeyboardInput { event: KeyEvent { physical_key: PhysicalKey::Code(code), state, .. }, .. } => {
+                if let Some(bit) = TRACKED_KEYS.iter().position(|k| k == code) {
+                    match state {
+                        ElementState::Pressed => self.keys |= 1 << bit,
+                        ElementState::Released => self.keys &= !(1 << bit),
+                    }
+                }
+            },
+            // Release events are missed while the window isn't focused
+            WindowEvent::Focused(false) => {
+                self.buttons = 0;
+                self.keys = 0;
+            },
+            _ => {}
+        }
+    }
+
+    /// The cursor position in window pixels.
+    pub fn cursor(&self) -> [f32; 2] {
+        self.cursor
+    }
+
+    /// Reset the per-frame state, called once a frame has been drawn.
+    pub fn end_frame(&mut self) {
+        self.clicked = false;
+        self.scroll = [0.0, 0.0];
+    }
+
+    /// The input state for the shaders, `to_image` maps window positions to output image pixels.
+    ///
+    /// `mouse` follows Shadertoy's `iMouse`: `xy` is the cursor position while the left button is held,
+    /// `zw` the position where it was pressed. `z` is negative while the button is up,
+    /// `w` is only positive during the frame the button was pressed.
+    pub fn shader_input(&self, to_image: impl Fn([f32; 2]) -> [f32; 2]) -> ShaderInput {
+        let drag = to_image(self.drag);
+        let click = to_image(self.click);
+        let down = self.buttons & 1 != 0;
+
+        ShaderInput {
+            mouse_buttons: self.buttons,
+            mouse: [
+                drag[0],
+                drag[1],
+                if down { click[0] } else { -click[0] },
+                if self.clicked { click[1] } else { -click[1] },
+            ],
+            scroll: self.scroll,
+            keys: [self.keys as u32, (self.keys >> 32) as u32],
+        }
+    }
+}
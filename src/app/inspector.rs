@@ -0,0 +1,349 @@
+use std::collections::HashMap;
+use ash::vk;
+use ash::vk::{ImageAspectFlags, ImageSubresourceLayers, Offset3D};
+use bytemuck::{Pod, Zeroable};
+use gpu_allocator::MemoryLocation;
+use winit::keyboard::NamedKey;
+use crate::app::{DrawOrchestrator, Renderer};
+use crate::app::draw_orch::ImageFormat;
+use crate::vulkan::{compile_shader_source, Allocator, Buffer, CommandBuffer, ComputePipeline, DescriptorSetLayout, Device, Image};
+use crate::Error;
+
+/// Key which presents the next image.
+pub const CYCLE_IMAGE_KEY: NamedKey = NamedKey::F2;
+
+/// Key which presents the next channel view.
+pub const CYCLE_CHANNEL_KEY: NamedKey = NamedKey::F3;
+
+const SHADER: &str = r#"
+#version 450
+
+layout( local_size_x = 16, local_size_y = 16, local_size_z = 1 ) in;
+
+layout( binding = 0, SOURCE_FORMAT ) uniform readonly SOURCE_TYPE source_image;
+layout( binding = 1, rgba8 ) uniform writeonly image2D target_image;
+
+layout( push_constant ) uniform PushConstants
+{
+    vec2 range;
+    int channel;
+} constants;
+
+void main()
+{
+    ivec2 position = ivec2( gl_GlobalInvocationID.xy );
+    if( any( greaterThanEqual( position, imageSize( source_image ) ) ) )
+    {
+        return;
+    }
+
+    vec4 value = vec4( imageLoad( source_image, position ) );
+    value = ( value - constants.range.x ) / max( constants.range.y - constants.range.x, 1e-6 );
+    if( constants.channel >= 0 )
+    {
+        value = vec4( vec3( value[ constants.channel ] ), 1.0 );
+    }
+    imageStore( target_image, position, clamp( value, 0.0, 1.0 ) );
+}
+"#;
+
+/// Channels of the inspected image which are presented.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChannelView {
+    All,
+    /// A single channel, shown in grayscale.
+    R,
+    G,
+    B,
+    A,
+}
+
+impl ChannelView {
+    pub const VIEWS: [ChannelView; 5] = [ChannelView::All, ChannelView::R, ChannelView::G, ChannelView::B, ChannelView::A];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChannelView::All => "RGBA",
+            ChannelView::R => "R",
+            ChannelView::G => "G",
+            ChannelView::B => "B",
+            ChannelView::A => "A",
+        }
+    }
+
+    pub fn next(&self) -> ChannelView {
+        let index = Self::VIEWS.iter().position(|v| v == self).unwrap();
+        Self::VIEWS[(index + 1) % Self::VIEWS.len()]
+    }
+
+    /// Index of the channel in the inspector shader, -1 for all channels.
+    fn index(&self) -> i32 {
+        Self::VIEWS.iter().position(|v| v == self).unwrap() as i32 - 1
+    }
+}
+
+/// Value of a pixel read back from an image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PixelValue {
+    /// Id of the image, `None` for the output image.
+    pub image: Option<u32>,
+    pub position: [u32; 2],
+    /// The decoded channels, see `ImageFormat::decode_pixel`.
+    pub value: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct InspectorConstants {
+    range: [f32; 2],
+    channel: i32,
+}
+
+/// Pixel copied to a readback buffer, which can be read once its frame has finished.
+struct Readback {
+    image: Option<u32>,
+    position: [u32; 2],
+    format: ImageFormat,
+}
+
+/// Presents an intermediate image, a single channel or a remapped range instead of the output image,
+/// and reads back the value of the pixel under the cursor.
+pub struct ImageInspector {
+    /// Id of the image presented instead of the output image, `None` presents the output image.
+    pub image: Option<u32>,
+    pub channel: ChannelView,
+    /// Values mapped to black and white, for float images which aren't in `[0, 1]`.
+    pub range: (f32, f32),
+    /// The latest value under the cursor, `None` while the cursor is outside the presented image.
+    pub pixel: Option<PixelValue>,
+    descriptor_set_layout: DescriptorSetLayout,
+    /// Inspector pipelines for each source image format, compiled when first used.
+    pipelines: HashMap<ImageFormat, ComputePipeline>,
+    /// The presented rgba8 image, sized to the inspected image.
+    target: Option<Image>,
+    /// Host visible buffer for each frame in flight, the pixel under the cursor is copied to.
+    readback_buffers: Vec<Buffer>,
+    pending: Vec<Option<Readback>>,
+}
+
+impl ImageInspector {
+    pub fn new(device: &Device, allocator: &mut Allocator, frame_count: usize) -> Result<ImageInspector, Error> {
+        let layout_bindings = [0, 1].map(|binding| {
+            vk::DescriptorSetLayoutBinding::default()
+                .binding(binding)
+                .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::COMPUTE)
+        });
+        let descriptor_set_layout = DescriptorSetLayout::new_push_descriptor(device, &layout_bindings)?;
+
+        // Large enough for a single pixel of every format
+        let readback_buffers = (0..frame_count)
+            .map(|_| Buffer::new(device, allocator, 16, vk::BufferUsageFlags::TRANSFER_DST, MemoryLocation::GpuToCpu))
+            .collect::<Result<Vec<Buffer>, Error>>()?;
+
+        Ok(ImageInspector {
+            image: None,
+            channel: ChannelView::All,
+            range: (0.0, 1.0),
+            pixel: None,
+            descriptor_set_layout,
+            pipelines: HashMap::new(),
+            target: None,
+            readback_buffers,
+            pending: (0..frame_count).map(|_| None).collect(),
+        })
+    }
+
+    /// Whether the output image is replaced by the inspector's view.
+    pub fn is_active(&self) -> bool {
+        self.image.is_some() || self.channel != ChannelView::All || self.range != (0.0, 1.0)
+    }
+
+    /// Whether an image of the given format is presented through the inspector pass.
+    /// Integer images can't be blitted to the swapchain, so they're always converted.
+    fn converts(&self, format: ImageFormat) -> bool {
+        self.is_active() || format.is_integer()
+    }
+
+    /// Present the next image, the output image is presented again after the last one.
+    pub fn cycle_image(&mut self, image_count: u32) {
+        self.image = match self.image {
+            None => Some(0),
+            Some(id) if id + 1 < image_count => Some(id + 1),
+            Some(_) => None,
+        };
+    }
+
+    pub fn cycle_channel(&mut self) {
+        self.channel = self.channel.next();
+    }
+
+    /// Id of the inspected image, `None` for the output image or when the selected image doesn't exist.
+    fn inspected_id(&self, draw_orchestrator: &DrawOrchestrator) -> Option<u32> {
+        self.image.filter(|&id| (id as usize) < draw_orchestrator.images.len())
+    }
+
+    /// The inspected image and its format.
+    pub fn inspected_image<'a>(&self, draw_orchestrator: &'a DrawOrchestrator) -> (&'a Image, ImageFormat) {
+        match self.inspected_id(draw_orchestrator) {
+            Some(id) => (&draw_orchestrator.images[id as usize], draw_orchestrator.image_resources[id as usize].format),
+            None => (draw_orchestrator.output_image(), draw_orchestrator.output_format()),
+        }
+    }
+
+    /// Collect the previous readback of this frame and prepare the resources to inspect the current image.
+    /// `cursor` is the cursor position in pixels of the inspected image.
+    pub fn prepare(&mut self, device: &Device, allocator: &mut Allocator, draw_orchestrator: &DrawOrchestrator, frame_index: usize, cursor: Option<[u32; 2]>) -> Result<(), Error> {
+
+        // The frame's fence has been waited on, so its copy has finished
+        if let Some(readback) = self.pending[frame_index].take() {
+            let bytes = self.readback_buffers[frame_index].mapped_slice()?;
+            self.pixel = Some(PixelValue {
+                image: readback.image,
+                position: readback.position,
+                value: readback.format.decode_pixel(&bytes[..readback.format.pixel_size() as usize]),
+            });
+        }
+
+        let id = self.inspected_id(draw_orchestrator);
+        let (source, format) = self.inspected_image(draw_orchestrator);
+
+        let cursor = cursor.filter(|p| p[0] < source.width && p[1] < source.height);
+        if cursor.is_none() {
+            self.pixel = None;
+        }
+        self.pending[frame_index] = cursor.map(|position| Readback { image: id, position, format });
+
+        if !self.converts(format) {
+            return Ok(());
+        }
+
+        if !self.pipelines.contains_key(&format) {
+            let (qualifier, image_type) = (format.glsl_qualifier(), format.glsl_image_type());
+            let mut macros: HashMap<&str, &dyn ToString> = HashMap::new();
+            macros.insert("SOURCE_FORMAT", &qualifier);
+            macros.insert("SOURCE_TYPE", &image_type);
+            let code = compile_shader_source(SHADER, shaderc::ShaderKind::Compute, "inspector.comp", &macros)?;
+            let push_constant_range = vk::PushConstantRange::default()
+                .stage_flags(vk::ShaderStageFlags::COMPUTE)
+                .offset(0)
+                .size(size_of::<InspectorConstants>() as u32);
+            let pipeline = ComputePipeline::from_code(device, "inspector.comp", &code, "main", &[&self.descriptor_set_layout], &[push_constant_range])?;
+            self.pipelines.insert(format, pipeline);
+        }
+
+        let resized = self.target.as_ref().is_none_or(|t| t.width != source.width || t.height != source.height);
+        if resized {
+            // Frames in flight may still be presenting the old image
+            device.wait_idle()?;
+            self.target = Some(Image::new(
+                device,
+                allocator,
+                source.width,
+                source.height,
+                vk::Format::R8G8B8A8_UNORM,
+                1,
+                vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::TRANSFER_SRC
+            )?);
+        }
+        Ok(())
+    }
+
+    /// Record the pixel readback and, while active, the inspector pass.
+    /// Returns the image to present, which is in the `GENERAL` layout.
+    pub fn record<'a>(&'a self, renderer: &Renderer, command_buffer: &CommandBuffer, draw_orchestrator: &'a DrawOrchestrator, frame_index: usize) -> &'a Image {
+        let (source, format) = self.inspected_image(draw_orchestrator);
+
+        command_buffer.image_barrier(
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::SHADER_WRITE,
+            vk::AccessFlags::SHADER_READ | vk::AccessFlags::TRANSFER_READ,
+            vk::DependencyFlags::empty(),
+            source
+        );
+
+        if let Some(readback) = &self.pending[frame_index] {
+            let buffer = &self.readback_buffers[frame_index];
+            unsafe {
+                renderer.device.handle().cmd_copy_image_to_buffer(
+                    command_buffer.handle(),
+                    source.image,
+                    vk::ImageLayout::GENERAL,
+                    *buffer.handle(),
+                    &[vk::BufferImageCopy::default()
+                        .image_subresource(
+                            ImageSubresourceLayers::default()
+                                .aspect_mask(ImageAspectFlags::COLOR)
+                                .base_array_layer(0)
+                                .layer_count(1)
+                                .mip_level(0)
+                        )
+                        .image_offset(Offset3D::default().x(readback.position[0] as i32).y(readback.position[1] as i32))
+                        .image_extent(vk::Extent3D { width: 1, height: 1, depth: 1 })
+                    ]
+                );
+
+                // Make the copy visible to the host
+                renderer.device.handle().cmd_pipeline_barrier(
+                    command_buffer.handle(),
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::HOST,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &[vk::BufferMemoryBarrier::default()
+                        .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                        .dst_access_mask(vk::AccessFlags::HOST_READ)
+                        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                        .buffer(*buffer.handle())
+                        .offset(0)
+                        .size(vk::WHOLE_SIZE)
+                    ],
+                    &[]
+                );
+            }
+
+            // Later accesses of the image synchronize with compute work, so they have to wait for the copy as well
+            command_buffer.image_barrier(
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::AccessFlags::NONE,
+                vk::AccessFlags::NONE,
+                vk::DependencyFlags::empty(),
+                source
+            );
+        }
+
+        let target = match &self.target {
+            Some(target) if self.converts(format) => target,
+            _ => return draw_orchestrator.output_image(),
+        };
+        let pipeline = &self.pipelines[&format];
+
+        // The previous contents were already presented
+        renderer.transition_image(
+            command_buffer,
+            &target.image,
+            vk::ImageLayout::UNDEFINED,
+            vk::ImageLayout::GENERAL,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::NONE,
+            vk::AccessFlags::SHADER_WRITE
+        );
+
+        let constants = InspectorConstants {
+            range: [self.range.0, self.range.1],
+            channel: self.channel.index(),
+        };
+        let [local_x, local_y, _] = pipeline.local_size();
+        command_buffer.bind_pipeline(pipeline);
+        command_buffer.bind_push_descriptor_storage_images(pipeline, &[source, target]);
+        command_buffer.push_constants(pipeline, vk::ShaderStageFlags::COMPUTE, 0, bytemuck::bytes_of(&constants));
+        command_buffer.dispatch(target.width.div_ceil(local_x), target.height.div_ceil(local_y), 1);
+
+        target
+    }
+}
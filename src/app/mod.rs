@@ -3,9 +3,22 @@ pub mod draw_orch;
 pub mod renderer;
 pub mod window;
 pub mod cpal_wrapper;
+pub mod headless;
+pub mod input;
+pub mod clock;
+pub mod overlay;
+pub mod overlay_painter;
+pub mod inspector;
+pub mod params;
+pub mod project;
 
 pub use self::draw_orch::DrawOrchestrator;
 pub use self::app::App;
 pub use self::renderer::Renderer;
 pub use self::window::Window;
 pub use self::cpal_wrapper::StreamFactory;
+pub use self::headless::HeadlessApp;
+pub use self::input::InputState;
+pub use self::overlay::Overlay;
+pub use self::params::{Param, ParamHandle, ParamValue};
+pub use self::project::Project;
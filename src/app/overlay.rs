@@ -0,0 +1,291 @@
+use std::collections::VecDeque;
+use std::path::Path;
+use std::time::Instant;
+use egui::{CollapsingHeader, Color32, ComboBox, DragValue, RichText, ScrollArea, Shape, Slider, Stroke, Ui, ViewportId};
+use winit::event::{ElementState, KeyEvent, WindowEvent};
+use winit::keyboard::{Key, NamedKey};
+use crate::Error;
+use crate::app::{DrawOrchestrator, Renderer, Window};
+use crate::app::inspector::{ChannelView, ImageInspector};
+use crate::app::overlay_painter::OverlayPainter;
+use crate::app::params::{Param, ParamHandle, ParamValue};
+
+/// Key which shows and hides the overlay.
+pub const TOGGLE_KEY: NamedKey = NamedKey::F1;
+
+/// Amount of frames shown in the frame time graph.
+const FRAME_TIME_HISTORY: usize = 240;
+
+/// Size in points of the squares of the error screen's checkerboard.
+const CHECKER_SIZE: f32 = 32.0;
+
+/// egui panel drawn on top of the output image, with the frame times, passes, time controls, parameters and image inspector.
+pub struct Overlay {
+    pub visible: bool,
+    context: egui::Context,
+    state: egui_winit::State,
+    painter: OverlayPainter,
+    /// Duration of the latest frames in milliseconds.
+    frame_times: VecDeque<f32>,
+    last_update: Instant,
+    /// The latest time reached, the end of the time slider.
+    max_time: f32,
+    /// Set while the error screen is drawn instead of the overlay, see `update_error`.
+    showing_error: bool,
+}
+
+impl Overlay {
+    pub fn new(renderer: &Renderer, window: &Window) -> Result<Overlay, Error> {
+        let context = egui::Context::default();
+        let state = egui_winit::State::new(
+            context.clone(),
+            ViewportId::ROOT,
+            window.winit_window(),
+            Some(window.winit_window().scale_factor() as f32),
+            Some(renderer.device.limits().max_image_dimension2_d as usize)
+        );
+        let swapchain = renderer.swapchain.as_ref().ok_or(Error::Headless)?;
+        let painter = OverlayPainter::new(&renderer.device, swapchain.get_format().format)?;
+
+        Ok(Overlay {
+            visible: false,
+            context,
+            state,
+            painter,
+            frame_times: VecDeque::with_capacity(FRAME_TIME_HISTORY),
+            last_update: Instant::now(),
+            max_time: 0.0,
+            showing_error: false,
+        })
+    }
+
+    /// Handle a window event, returns whether the overlay consumed it.
+    pub fn window_event(&mut self, window: &Window, event: &WindowEvent) -> bool {
+        if let WindowEvent::KeyboardInput { event: KeyEvent { logical_key: Key::Named(key), state: ElementState::Pressed, repeat: false, .. }, .. } = event {
+            if *key == TOGGLE_KEY {
+                self.visible = !self.visible;
+                return true;
+            }
+        }
+
+        if !self.visible && !self.showing_error {
+            return false;
+        }
+        self.state.on_window_event(window.winit_window(), event).consumed
+    }
+
+    /// The painter drawing the overlay or the error screen, `None` while neither is shown.
+    pub fn painter(&mut self) -> Option<&mut OverlayPainter> {
+        (self.visible || self.showing_error).then_some(&mut self.painter)
+    }
+
+    /// Run the overlay's UI, called once before every frame.
+    pub fn update(&mut self, window: &Window, renderer: &mut Renderer, orchestrator: &mut DrawOrchestrator) {
+        self.showing_error = false;
+        let now = Instant::now();
+        if self.frame_times.len() == FRAME_TIME_HISTORY {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(now.duration_since(self.last_update).as_secs_f32() * 1000.0);
+        self.last_update = now;
+        self.max_time = self.max_time.max(renderer.clock.time());
+
+        if !self.visible {
+            return;
+        }
+
+        let raw_input = self.state.take_egui_input(window.winit_window());
+        let context = self.context.clone();
+        let output = context.run(raw_input, |ctx| {
+            egui::Window::new("kiyo")
+                .default_pos([10.0, 10.0])
+                .default_width(280.0)
+                .show(ctx, |ui| self.ui(ui, renderer, orchestrator));
+        });
+
+        self.state.handle_platform_output(window.winit_window(), output.platform_output);
+        let primitives = context.tessellate(output.shapes, output.pixels_per_point);
+        self.painter.set_frame(output.textures_delta, primitives, output.pixels_per_point);
+    }
+
+    /// Run the error screen instead of the overlay, a checkerboard with the compile `log` on top.
+    /// Called once before every frame while the shaders don't compile.
+    pub fn update_error(&mut self, window: &Window, log: &str) {
+        self.showing_error = true;
+
+        let raw_input = self.state.take_egui_input(window.winit_window());
+        let output = self.context.run(raw_input, |ctx| {
+            checkerboard(ctx);
+            egui::Window::new("Shader error")
+                .default_pos([10.0, 10.0])
+                .default_width(640.0)
+                .show(ctx, |ui| {
+                    ui.label("The passes start as soon as the shaders compile.");
+                    ScrollArea::vertical().show(ui, |ui| {
+                        ui.label(RichText::new(log).monospace().color(ui.visuals().error_fg_color));
+                    });
+                });
+        });
+
+        self.state.handle_platform_output(window.winit_window(), output.platform_output);
+        let primitives = self.context.tessellate(output.shapes, output.pixels_per_point);
+        self.painter.set_frame(output.textures_delta, primitives, output.pixels_per_point);
+    }
+
+    fn ui(&self, ui: &mut Ui, renderer: &mut Renderer, orchestrator: &mut DrawOrchestrator) {
+        CollapsingHeader::new("Frame time").default_open(true).show(ui, |ui| {
+            frame_time_graph(ui, &self.frame_times);
+        });
+
+        CollapsingHeader::new("Time").default_open(true).show(ui, |ui| {
+            let clock = &mut renderer.clock;
+            ui.horizontal(|ui| {
+                let paused = clock.is_paused();
+                if ui.button(if paused { "Play" } else { "Pause" }).clicked() {
+                    clock.set_paused(!paused);
+                }
+                if ui.button("Restart").clicked() {
+                    clock.set_time(0.0);
+                }
+            });
+
+            let mut time = clock.time();
+            if ui.add(Slider::new(&mut time, 0.0..=self.max_time.max(1.0)).suffix(" s")).changed() {
+                clock.set_time(time);
+            }
+        });
+
+        CollapsingHeader::new("Passes").default_open(true).show(ui, |ui| {
+            for pass in &mut orchestrator.passes {
+                let name = Path::new(&pass.shader).file_name().map_or(pass.shader.clone(), |n| n.to_string_lossy().to_string());
+                ui.checkbox(&mut pass.enabled, name).on_hover_text(&pass.shader);
+            }
+        });
+
+        if !orchestrator.params.is_empty() {
+            CollapsingHeader::new("Parameters").default_open(true).show(ui, |ui| {
+                for param in &orchestrator.params {
+                    param_widget(ui, param, &renderer.params);
+                }
+                if ui.button("Reset").clicked() {
+                    renderer.params.reset();
+                }
+            });
+        }
+
+        CollapsingHeader::new("Inspector").default_open(false).show(ui, |ui| {
+            inspector_widget(ui, &mut renderer.inspector, orchestrator);
+        });
+    }
+}
+
+fn frame_time_graph(ui: &mut Ui, frame_times: &VecDeque<f32>) {
+    let average = frame_times.iter().sum::<f32>() / frame_times.len().max(1) as f32;
+    let max = frame_times.iter().copied().fold(0.0, f32::max);
+    ui.label(format!("{:.2} ms ({:.0} fps), max {:.2} ms", average, 1000.0 / average.max(0.001), max));
+
+    let (response, painter) = ui.allocate_painter(egui::vec2(ui.available_width(), 60.0), egui::Sense::hover());
+    let rect = response.rect;
+    painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+
+    // Show at least 30 fps worth of range, so small variations don't fill the graph
+    let scale = max.max(1000.0 / 30.0);
+    let points = frame_times.iter().enumerate().map(|(i, time)| {
+        egui::pos2(
+            rect.left() + rect.width() * i as f32 / (FRAME_TIME_HISTORY - 1) as f32,
+            rect.bottom() - rect.height() * time / scale
+        )
+    }).collect();
+    painter.add(Shape::line(points, Stroke::new(1.0, ui.visuals().text_color())));
+}
+
+fn checkerboard(ctx: &egui::Context) {
+    let painter = ctx.layer_painter(egui::LayerId::background());
+    let rect = ctx.screen_rect();
+    let columns = (rect.width() / CHECKER_SIZE).ceil() as usize;
+    let rows = (rect.height() / CHECKER_SIZE).ceil() as usize;
+    for y in 0..rows {
+        for x in 0..columns {
+            let color = if (x + y) % 2 == 0 { Color32::from_gray(48) } else { Color32::from_gray(80) };
+            let min = rect.min + egui::vec2(x as f32, y as f32) * CHECKER_SIZE;
+            painter.rect_filled(egui::Rect::from_min_size(min, egui::vec2(CHECKER_SIZE, CHECKER_SIZE)), 0.0, color);
+        }
+    }
+}
+
+fn inspector_widget(ui: &mut Ui, inspector: &mut ImageInspector, orchestrator: &DrawOrchestrator) {
+    let image_name = |image: Option<u32>| match image {
+        Some(id) => match orchestrator.image_resources.get(id as usize) {
+            Some(r) => format!("Image {} ({:?})", id, r.format),
+            None => format!("Image {}", id),
+        },
+        None => "Output".to_string(),
+    };
+    ComboBox::from_label("Image")
+        .selected_text(image_name(inspector.image))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(&mut inspector.image, None, image_name(None));
+            for id in 0..orchestrator.images.len() as u32 {
+                ui.selectable_value(&mut inspector.image, Some(id), image_name(Some(id)));
+            }
+        });
+
+    ui.horizontal(|ui| {
+        for view in ChannelView::VIEWS {
+            ui.selectable_value(&mut inspector.channel, view, view.name());
+        }
+    });
+
+    ui.horizontal(|ui| {
+        ui.label("Range");
+        ui.add(DragValue::new(&mut inspector.range.0).speed(0.01));
+        ui.add(DragValue::new(&mut inspector.range.1).speed(0.01));
+        if ui.button("Reset").clicked() {
+            inspector.range = (0.0, 1.0);
+        }
+    });
+
+    match inspector.pixel {
+        Some(pixel) => ui.label(format!(
+            "{} at {}, {}: {:.4} {:.4} {:.4} {:.4}",
+            image_name(pixel.image), pixel.position[0], pixel.position[1],
+            pixel.value[0], pixel.value[1], pixel.value[2], pixel.value[3]
+        )),
+        None => ui.label("Move the cursor over the image to read its pixels"),
+    };
+}
+
+/// Slider, or drag value without a range, for every component of a parameter.
+fn param_widget(ui: &mut Ui, param: &Param, values: &ParamHandle) {
+    let mut value = values.value(param);
+    let changed = ui.horizontal(|ui| {
+        ui.label(&param.name);
+        match &mut value {
+            ParamValue::Float(v) => components_widget(ui, std::slice::from_mut(v), param.range),
+            ParamValue::Vec2(v) => components_widget(ui, v, param.range),
+            ParamValue::Vec3(v) => components_widget(ui, v, param.range),
+            ParamValue::Vec4(v) => components_widget(ui, v, param.range),
+            ParamValue::Int(v) => match param.range {
+                Some((min, max)) => ui.add(Slider::new(v, min as i32..=max as i32)).changed(),
+                None => ui.add(DragValue::new(v)).changed(),
+            },
+            ParamValue::Bool(v) => ui.checkbox(v, "").changed(),
+            ParamValue::Color(v) => ui.color_edit_button_rgba_unmultiplied(v).changed(),
+        }
+    }).inner;
+
+    if changed {
+        values.set(&param.name, value);
+    }
+}
+
+fn components_widget(ui: &mut Ui, components: &mut [f32], range: Option<(f32, f32)>) -> bool {
+    let mut changed = false;
+    for value in components {
+        changed |= match range {
+            Some((min, max)) => ui.add(Slider::new(value, min..=max)).changed(),
+            None => ui.add(DragValue::new(value).speed(0.01)).changed(),
+        };
+    }
+    changed
+}
@@ -0,0 +1,407 @@
+// The overlay is painted by kiyo itself instead of through `egui-ash`:
+// - egui-ash 0.4 is built on ash 0.37 and gpu-allocator 0.25, while kiyo uses ash 0.38 and gpu-allocator 0.27,
+//   so its renderer can't take kiyo's device, allocator or command buffers.
+// - It only renders through `egui_ash::run`, which creates its own event loop, window and swapchain.
+// egui is 0.25 to match egui-winit 0.25, the version built on kiyo's winit 0.29.
+use std::collections::HashMap;
+use std::mem::offset_of;
+use ash::vk;
+use ash::vk::{ImageAspectFlags, ImageSubresourceLayers};
+use egui::epaint::{Primitive, Vertex};
+use egui::{ClippedPrimitive, ImageData, TextureFilter, TextureId, TexturesDelta};
+use gpu_allocator::MemoryLocation;
+use crate::Error;
+use crate::vulkan::{compile_shader_source, Allocator, Buffer, CommandBuffer, DescriptorSetLayout, Device, Framebuffer, GraphicsPipeline, GraphicsPipelineState, Image, RenderPass, Sampler, Swapchain};
+
+const VERTEX_SHADER: &str = r#"
+#version 450
+
+layout( location = 0 ) in vec2 in_position;
+layout( location = 1 ) in vec2 in_uv;
+layout( location = 2 ) in vec4 in_color;
+
+layout( location = 0 ) out vec2 out_uv;
+layout( location = 1 ) out vec4 out_color;
+
+layout( push_constant ) uniform PushConstants
+{
+    vec2 screen_size;
+} constants;
+
+void main()
+{
+    out_uv = in_uv;
+    out_color = in_color;
+    gl_Position = vec4( 2.0 * in_position / constants.screen_size - 1.0, 0.0, 1.0 );
+}
+"#;
+
+const FRAGMENT_SHADER: &str = r#"
+#version 450
+
+layout( location = 0 ) in vec2 in_uv;
+layout( location = 1 ) in vec4 in_color;
+
+layout( location = 0 ) out vec4 out_color;
+
+layout( binding = 0 ) uniform sampler2D tex;
+
+void main()
+{
+    vec4 color = in_color * texture( tex, in_uv );
+#ifdef SRGB_FRAMEBUFFER
+    // egui's colors are in gamma space, the framebuffer expects linear colors
+    color.rgb = pow( color.rgb, vec3( 2.2 ) );
+#endif
+    out_color = color;
+}
+"#;
+
+struct Texture {
+    image: Image,
+    filter: TextureFilter,
+}
+
+/// Pixels to copy into a texture before drawing.
+struct TextureUpload {
+    id: TextureId,
+    staging: Buffer,
+    offset: [u32; 2],
+    size: [u32; 2],
+    /// Whether the texture was just created, and has no contents to keep.
+    new: bool,
+}
+
+struct MeshDraw {
+    texture: TextureId,
+    scissor: vk::Rect2D,
+    index_count: u32,
+    first_index: u32,
+    vertex_offset: i32,
+}
+
+/// Resources used by a single frame in flight.
+#[derive(Default)]
+struct FrameResources {
+    vertex_buffer: Option<Buffer>,
+    index_buffer: Option<Buffer>,
+    uploads: Vec<TextureUpload>,
+    draws: Vec<MeshDraw>,
+}
+
+/// Draws egui's output on top of the swapchain image.
+pub struct OverlayPainter {
+    render_pass: RenderPass,
+    pipeline: GraphicsPipeline,
+    _descriptor_set_layout: DescriptorSetLayout,
+    linear_sampler: Sampler,
+    nearest_sampler: Sampler,
+    framebuffers: Vec<Framebuffer>,
+    /// The swapchain the framebuffers were created for.
+    framebuffer_swapchain: vk::SwapchainKHR,
+    textures: HashMap<TextureId, Texture>,
+    frames: Vec<FrameResources>,
+    textures_delta: TexturesDelta,
+    primitives: Vec<ClippedPrimitive>,
+    pixels_per_point: f32,
+}
+
+impl OverlayPainter {
+    pub fn new(device: &Device, surface_format: vk::Format) -> Result<OverlayPainter, Error> {
+        let render_pass = RenderPass::new_overlay(device, surface_format)?;
+
+        let descriptor_set_layout = DescriptorSetLayout::new_push_descriptor(
+            device,
+            &[vk::DescriptorSetLayoutBinding::default()
+                .binding(0)
+                .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+                .descriptor_count(1)
+                .stage_flags(vk::ShaderStageFlags::FRAGMENT)]
+        )?;
+
+        let srgb_framebuffer = matches!(surface_format, vk::Format::R8G8B8A8_SRGB | vk::Format::B8G8R8A8_SRGB);
+        let mut macros: HashMap<&str, &dyn ToString> = HashMap::new();
+        if srgb_framebuffer {
+            macros.insert("SRGB_FRAMEBUFFER", &1);
+        }
+        let vertex_shader_code = compile_shader_source(VERTEX_SHADER, shaderc::ShaderKind::Vertex, "overlay.vert", &macros)?;
+        let fragment_shader_code = compile_shader_source(FRAGMENT_SHADER, shaderc::ShaderKind::Fragment, "overlay.frag", &macros)?;
+
+        let pipeline = GraphicsPipeline::with_state(
+            device,
+            &render_pass,
+            &vertex_shader_code,
+            &fragment_shader_code,
+            &[&descriptor_set_layout],
+            &GraphicsPipelineState {
+                vertex_bindings: &[vk::VertexInputBindingDescription::default()
+                    .binding(0)
+                    .stride(size_of::<Vertex>() as u32)
+                    .input_rate(vk::VertexInputRate::VERTEX)],
+                vertex_attributes: &[
+                    vk::VertexInputAttributeDescription::default()
+                        .location(0)
+                        .format(vk::Format::R32G32_SFLOAT)
+                        .offset(offset_of!(Vertex, pos) as u32),
+                    vk::VertexInputAttributeDescription::default()
+                        .location(1)
+                        .format(vk::Format::R32G32_SFLOAT)
+                        .offset(offset_of!(Vertex, uv) as u32),
+                    vk::VertexInputAttributeDescription::default()
+                        .location(2)
+                        .format(vk::Format::R8G8B8A8_UNORM)
+                        .offset(offset_of!(Vertex, color) as u32),
+                ],
+                cull_mode: vk::CullModeFlags::NONE,
+                alpha_blending: true,
+                push_constant_ranges: &[vk::PushConstantRange::default()
+                    .stage_flags(vk::ShaderStageFlags::VERTEX)
+                    .offset(0)
+                    .size(size_of::<[f32; 2]>() as u32)],
+            }
+        )?;
+
+        Ok(OverlayPainter {
+            render_pass,
+            pipeline,
+            _descriptor_set_layout: descriptor_set_layout,
+            linear_sampler: Sampler::new(device, vk::Filter::LINEAR)?,
+            nearest_sampler: Sampler::new(device, vk::Filter::NEAREST)?,
+            framebuffers: Vec::new(),
+            framebuffer_swapchain: vk::SwapchainKHR::null(),
+            textures: HashMap::new(),
+            frames: Vec::new(),
+            textures_delta: TexturesDelta::default(),
+            primitives: Vec::new(),
+            pixels_per_point: 1.0,
+        })
+    }
+
+    /// Set the output of the latest egui frame, which is drawn until the next one is set.
+    pub fn set_frame(&mut self, textures_delta: TexturesDelta, primitives: Vec<ClippedPrimitive>, pixels_per_point: f32) {
+        self.textures_delta.append(textures_delta);
+        self.primitives = primitives;
+        self.pixels_per_point = pixels_per_point;
+    }
+
+    /// Create the buffers and texture uploads of a frame, once its previous use has finished executing.
+    pub fn prepare(&mut self, device: &Device, allocator: &mut Allocator, frame_index: usize, swapchain: &Swapchain) -> Result<(), Error> {
+        if self.framebuffer_swapchain != swapchain.handle() {
+            // The renderer waits for the device to be idle before recreating the swapchain
+            self.framebuffers = swapchain.get_image_views().iter().map(|&view| {
+                Framebuffer::new(device, swapchain.get_extent(), &self.render_pass, vec![view])
+            }).collect::<Result<Vec<Framebuffer>, Error>>()?;
+            self.framebuffer_swapchain = swapchain.handle();
+        }
+
+        if self.frames.len() <= frame_index {
+            self.frames.resize_with(frame_index + 1, FrameResources::default);
+        }
+        self.frames[frame_index].uploads.clear();
+
+        // Textures may still be sampled by other frames in flight, also for partial updates, which are copied into the
+        // live texture. Changing them is rare, e.g. when the font atlas grows, so just wait
+        let changed = self.textures_delta.set.iter().any(|(id, _)| self.textures.contains_key(id));
+        if changed || !self.textures_delta.free.is_empty() {
+            device.wait_idle()?;
+        }
+        for id in std::mem::take(&mut self.textures_delta.free) {
+            self.textures.remove(&id);
+        }
+        for (id, delta) in std::mem::take(&mut self.textures_delta.set) {
+            let pixels = match &delta.image {
+                ImageData::Color(image) => image.pixels.iter().flat_map(|c| c.to_array()).collect::<Vec<u8>>(),
+                ImageData::Font(image) => image.srgba_pixels(None).flat_map(|c| c.to_array()).collect::<Vec<u8>>(),
+            };
+            let size = [delta.image.width() as u32, delta.image.height() as u32];
+
+            let new = delta.pos.is_none();
+            if new {
+                let image = Image::new(
+                    device,
+                    allocator,
+                    size[0],
+                    size[1],
+                    vk::Format::R8G8B8A8_UNORM,
+                    1,
+                    vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST
+                )?;
+                self.textures.insert(id, Texture { image, filter: delta.options.magnification });
+            }
+
+            let mut staging = Buffer::new(
+                device,
+                allocator,
+                pixels.len() as u64,
+                vk::BufferUsageFlags::TRANSFER_SRC,
+                MemoryLocation::CpuToGpu
+            )?;
+            staging.mapped_slice_mut()?.copy_from_slice(&pixels);
+
+            let [x, y] = delta.pos.unwrap_or([0, 0]);
+            self.frames[frame_index].uploads.push(TextureUpload {
+                id,
+                staging,
+                offset: [x as u32, y as u32],
+                size,
+                new,
+            });
+        }
+
+        // Gather the meshes into a single vertex and index buffer
+        let extent = swapchain.get_extent();
+        let mut vertices: Vec<Vertex> = Vec::new();
+        let mut indices: Vec<u32> = Vec::new();
+        let mut draws = Vec::new();
+        for primitive in &self.primitives {
+            let Primitive::Mesh(mesh) = &primitive.primitive else {
+                continue;
+            };
+
+            let clip = primitive.clip_rect;
+            let min_x = (clip.min.x * self.pixels_per_point).round().clamp(0.0, extent.width as f32) as u32;
+            let min_y = (clip.min.y * self.pixels_per_point).round().clamp(0.0, extent.height as f32) as u32;
+            let max_x = (clip.max.x * self.pixels_per_point).round().clamp(min_x as f32, extent.width as f32) as u32;
+            let max_y = (clip.max.y * self.pixels_per_point).round().clamp(min_y as f32, extent.height as f32) as u32;
+            if mesh.indices.is_empty() || min_x == max_x || min_y == max_y || !self.textures.contains_key(&mesh.texture_id) {
+                continue;
+            }
+
+            draws.push(MeshDraw {
+                texture: mesh.texture_id,
+                scissor: vk::Rect2D {
+                    offset: vk::Offset2D { x: min_x as i32, y: min_y as i32 },
+                    extent: vk::Extent2D { width: max_x - min_x, height: max_y - min_y },
+                },
+                index_count: mesh.indices.len() as u32,
+                first_index: indices.len() as u32,
+                vertex_offset: vertices.len() as i32,
+            });
+            vertices.extend_from_slice(&mesh.vertices);
+            indices.extend_from_slice(&mesh.indices);
+        }
+
+        let frame = &mut self.frames[frame_index];
+        Self::write_buffer(device, allocator, &mut frame.vertex_buffer, vk::BufferUsageFlags::VERTEX_BUFFER, bytemuck::cast_slice(&vertices))?;
+        Self::write_buffer(device, allocator, &mut frame.index_buffer, vk::BufferUsageFlags::INDEX_BUFFER, bytemuck::cast_slice(&indices))?;
+        frame.draws = draws;
+        Ok(())
+    }
+
+    /// Write `data` to the start of `buffer`, growing it when it's too small.
+    fn write_buffer(device: &Device, allocator: &mut Allocator, buffer: &mut Option<Buffer>, usage: vk::BufferUsageFlags, data: &[u8]) -> Result<(), Error> {
+        let buffer = match buffer.take() {
+            Some(b) if b.size >= data.len() as u64 => buffer.insert(b),
+            _ => {
+                let size = (data.len() as u64).next_power_of_two().max(4096);
+                buffer.insert(Buffer::new(device, allocator, size, usage, MemoryLocation::CpuToGpu)?)
+            },
+        };
+        buffer.mapped_slice_mut()?[..data.len()].copy_from_slice(data);
+        Ok(())
+    }
+
+    /// Record the texture uploads and draws of a frame prepared with `prepare`.
+    /// Expects the swapchain image in the `TRANSFER_DST_OPTIMAL` layout, and leaves it ready for presentation.
+    pub fn record(&self, device: &Device, command_buffer: &CommandBuffer, frame_index: usize, image_index: usize) {
+        let frame = &self.frames[frame_index];
+
+        for upload in &frame.uploads {
+            let texture = &self.textures[&upload.id];
+            let old_layout = match upload.new {
+                true => vk::ImageLayout::UNDEFINED,
+                false => vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            };
+            Self::texture_barrier(device, command_buffer, &texture.image, old_layout, vk::ImageLayout::TRANSFER_DST_OPTIMAL);
+            unsafe {
+                device.handle().cmd_copy_buffer_to_image(
+                    command_buffer.handle(),
+                    *upload.staging.handle(),
+                    texture.image.image,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    &[vk::BufferImageCopy::default()
+                        .image_subresource(
+                            ImageSubresourceLayers::default()
+                                .aspect_mask(ImageAspectFlags::COLOR)
+                                .base_array_layer(0)
+                                .layer_count(1)
+                                .mip_level(0)
+                        )
+                        .image_offset(vk::Offset3D { x: upload.offset[0] as i32, y: upload.offset[1] as i32, z: 0 })
+                        .image_extent(vk::Extent3D { width: upload.size[0], height: upload.size[1], depth: 1 })
+                    ]
+                );
+            }
+            Self::texture_barrier(device, command_buffer, &texture.image, vk::ImageLayout::TRANSFER_DST_OPTIMAL, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+        }
+
+        let framebuffer = &self.framebuffers[image_index];
+        let extent = framebuffer.get_extent();
+        command_buffer.begin_render_pass(&self.render_pass, framebuffer);
+
+        if let (Some(vertex_buffer), Some(index_buffer)) = (&frame.vertex_buffer, &frame.index_buffer) {
+            command_buffer.bind_pipeline(&self.pipeline);
+            command_buffer.set_viewport(vk::Viewport::default()
+                .width(extent.width as f32)
+                .height(extent.height as f32)
+                .max_depth(1.0)
+            );
+            let screen_size = [extent.width as f32 / self.pixels_per_point, extent.height as f32 / self.pixels_per_point];
+            command_buffer.push_constants(&self.pipeline, vk::ShaderStageFlags::VERTEX, 0, bytemuck::cast_slice(&screen_size));
+            command_buffer.bind_vertex_buffer(vertex_buffer);
+            command_buffer.bind_index_buffer(index_buffer, vk::IndexType::UINT32);
+
+            for draw in &frame.draws {
+                let texture = &self.textures[&draw.texture];
+                let sampler = match texture.filter {
+                    TextureFilter::Linear => &self.linear_sampler,
+                    TextureFilter::Nearest => &self.nearest_sampler,
+                };
+                command_buffer.bind_push_descriptor_sampled_image(&self.pipeline, &texture.image, sampler.handle());
+                command_buffer.set_scissor(draw.scissor);
+                command_buffer.draw_indexed(draw.index_count, draw.first_index, draw.vertex_offset);
+            }
+        }
+
+        command_buffer.end_render_pass();
+    }
+
+    fn texture_barrier(device: &Device, command_buffer: &CommandBuffer, image: &Image, old_layout: vk::ImageLayout, new_layout: vk::ImageLayout) {
+        let (src_stage, src_access, dst_stage, dst_access) = match new_layout {
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL => (
+                vk::PipelineStageFlags::FRAGMENT_SHADER, vk::AccessFlags::NONE,
+                vk::PipelineStageFlags::TRANSFER, vk::AccessFlags::TRANSFER_WRITE
+            ),
+            _ => (
+                vk::PipelineStageFlags::TRANSFER, vk::AccessFlags::TRANSFER_WRITE,
+                vk::PipelineStageFlags::FRAGMENT_SHADER, vk::AccessFlags::SHADER_READ
+            ),
+        };
+        let image_memory_barrier = vk::ImageMemoryBarrier::default()
+            .old_layout(old_layout)
+            .new_layout(new_layout)
+            .src_access_mask(src_access)
+            .dst_access_mask(dst_access)
+            .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+            .image(image.image)
+            .subresource_range(vk::ImageSubresourceRange {
+                aspect_mask: ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            });
+        unsafe {
+            device.handle().cmd_pipeline_barrier(
+                command_buffer.handle(),
+                src_stage,
+                dst_stage,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[image_memory_barrier]
+            );
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use serde::Deserialize;
+
+/// Value of a shader parameter, the variant decides its GLSL type.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ParamValue {
+    Float( f32 ),
+    Vec2( [f32; 2] ),
+    Vec3( [f32; 3] ),
+    Vec4( [f32; 4] ),
+    Int( i32 ),
+    Bool( bool ),
+    /// An rgba color, declared as a `vec4`.
+    Color( [f32; 4] ),
+}
+
+impl ParamValue {
+    pub fn glsl_type(&self) -> &'static str {
+        match self {
+            ParamValue::Float(_) => "float",
+            ParamValue::Vec2(_) => "vec2",
+            ParamValue::Vec3(_) => "vec3",
+            ParamValue::Vec4(_) | ParamValue::Color(_) => "vec4",
+            ParamValue::Int(_) => "int",
+            ParamValue::Bool(_) => "bool",
+        }
+    }
+
+    /// Alignment in bytes following the std140 layout rules.
+    fn alignment(&self) -> usize {
+        match self {
+            ParamValue::Float(_) | ParamValue::Int(_) | ParamValue::Bool(_) => 4,
+            ParamValue::Vec2(_) => 8,
+            ParamValue::Vec3(_) | ParamValue::Vec4(_) | ParamValue::Color(_) => 16,
+        }
+    }
+
+    fn is_same_type(&self, other: &ParamValue) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+
+    /// Clamp every component to `[min, max]`, booleans are left untouched.
+    fn clamped(self, (min, max): (f32, f32)) -> ParamValue {
+        match self {
+            ParamValue::Float(v) => ParamValue::Float(v.clamp(min, max)),
+            ParamValue::Vec2(v) => ParamValue::Vec2(v.map(|c| c.clamp(min, max))),
+            ParamValue::Vec3(v) => ParamValue::Vec3(v.map(|c| c.clamp(min, max))),
+            ParamValue::Vec4(v) => ParamValue::Vec4(v.map(|c| c.clamp(min, max))),
+            ParamValue::Color(v) => ParamValue::Color(v.map(|c| c.clamp(min, max))),
+            ParamValue::Int(v) => ParamValue::Int(v.clamp(min as i32, max as i32)),
+            ParamValue::Bool(v) => ParamValue::Bool(v),
+        }
+    }
+
+    fn write_std140(&self, bytes: &mut Vec<u8>) {
+        match self {
+            ParamValue::Float(v) => bytes.extend_from_slice(&v.to_le_bytes()),
+            ParamValue::Vec2(v) => v.iter().for_each(|c| bytes.extend_from_slice(&c.to_le_bytes())),
+            ParamValue::Vec3(v) => v.iter().for_each(|c| bytes.extend_from_slice(&c.to_le_bytes())),
+            ParamValue::Vec4(v) | ParamValue::Color(v) => v.iter().for_each(|c| bytes.extend_from_slice(&c.to_le_bytes())),
+            ParamValue::Int(v) => bytes.extend_from_slice(&v.to_le_bytes()),
+            ParamValue::Bool(v) => bytes.extend_from_slice(&(*v as u32).to_le_bytes()),
+        }
+    }
+}
+
+/// Declaration of a named shader parameter.
+/// Parameters are accessible in every shader as `params.<name>`, see `PARAM_BINDINGS`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Param {
+    pub name: String,
+    pub default: ParamValue,
+    /// Inclusive range of every component, values outside of it are clamped.
+    pub range: Option<(f32, f32)>,
+}
+
+impl Param {
+    pub fn new(name: &str, default: ParamValue) -> Param {
+        Param {
+            name: name.to_string(),
+            default,
+            range: None,
+        }
+    }
+
+    pub fn with_range(mut self, min: f32, max: f32) -> Param {
+        self.range = Some((min, max));
+        self
+    }
+}
+
+/// Shared handle to the current parameter values, which can be updated while the app is running.
+/// Clones refer to the same values, so a handle can be moved to another thread.
+#[derive(Clone, Default)]
+pub struct ParamHandle {
+    values: Arc<Mutex<HashMap<String, ParamValue>>>,
+}
+
+impl ParamHandle {
+    /// Set the value of a parameter. It is applied at the start of the next frame.
+    /// Values of a different type than the declared default are ignored.
+    pub fn set(&self, name: &str, value: ParamValue) {
+        self.values.lock().unwrap().insert(name.to_string(), value);
+    }
+
+    /// The value set for a parameter, `None` when it still has its default value.
+    pub fn get(&self, name: &str) -> Option<ParamValue> {
+        self.values.lock().unwrap().get(name).copied()
+    }
+
+    /// Restore the default value of every parameter.
+    pub fn reset(&self) {
+        self.values.lock().unwrap().clear();
+    }
+
+    /// The value of a declared parameter, falling back to its default and clamped to its range.
+    pub fn value(&self, param: &Param) -> ParamValue {
+        let value = self.get(&param.name)
+            .filter(|v| v.is_same_type(&param.default))
+            .unwrap_or(param.default);
+        match param.range {
+            Some(range) => value.clamped(range),
+            None => value,
+        }
+    }
+
+    /// Pack the current values of `params` into a std140 uniform block.
+    pub fn pack(&self, params: &[Param]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for param in params {
+            let value = self.value(param);
+            bytes.resize(bytes.len().next_multiple_of(value.alignment()), 0);
+            value.write_std140(&mut bytes);
+        }
+        // A uniform block's size is a multiple of a vec4
+        bytes.resize(bytes.len().next_multiple_of(16), 0);
+        bytes
+    }
+}
+
+/// Check that parameter names are valid and unique GLSL identifiers, and that ranges aren't empty.
+pub fn validate_params(params: &[Param]) -> Result<(), String> {
+    for (i, param) in params.iter().enumerate() {
+        let mut chars = param.name.chars();
+        let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            return Err(format!("Parameter name '{}' isn't a valid GLSL identifier", param.name));
+        }
+        if params[..i].iter().any(|p| p.name == param.name) {
+            return Err(format!("Parameter '{}' is declared more than once", param.name));
+        }
+        // Clamping to an empty range or a NaN bound panics
+        if let Some((min, max)) = param.range.filter(|&(min, max)| min.is_nan() || max.is_nan() || min > max) {
+            return Err(format!("Parameter '{}' has an empty range [{}, {}]", param.name, min, max));
+        }
+    }
+    Ok(())
+}
+
+/// The GLSL declaration of the parameter block, at binding 3.
+pub fn glsl_declaration(params: &[Param]) -> String {
+    let members = params.iter()
+        .map(|p| format!("{} {};", p.default.glsl_type(), p.name))
+        .collect::<Vec<String>>()
+        .join(" ");
+    format!("layout( binding = 3, std140 ) uniform Params {{ {} }} params;", members)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vulkan::compile_shader_source;
+
+    const OP_MEMBER_DECORATE: u32 = 72;
+    const DECORATION_OFFSET: u32 = 35;
+
+    /// Offsets of the members of the uniform blocks in `code`, in member order.
+    fn member_offsets(code: &[u32]) -> Vec<u32> {
+        let mut offsets = Vec::new();
+        let mut offset = 5;
+        while offset < code.len() {
+            let (word_count, opcode) = ((code[offset] >> 16) as usize, code[offset] & 0xffff);
+            if let (OP_MEMBER_DECORATE, &[_, member, DECORATION_OFFSET, value]) = (opcode, &code[offset + 1..offset + word_count]) {
+                offsets.push((member, value));
+            }
+            offset += word_count;
+        }
+        offsets.sort();
+        offsets.into_iter().map(|(_, value)| value).collect()
+    }
+
+    fn read(bytes: &[u8], offset: usize) -> [u8; 4] {
+        bytes[offset..offset + 4].try_into().unwrap()
+    }
+
+    #[test]
+    fn packs_std140() {
+        let params = [
+            Param::new("speed", ParamValue::Float( 0.5 )),
+            Param::new("offset", ParamValue::Vec3( [1.0, 2.0, 3.0] )),
+            Param::new("steps", ParamValue::Int( 7 )),
+            Param::new("enabled", ParamValue::Bool( true )),
+            Param::new("center", ParamValue::Vec2( [4.0, 5.0] )),
+            Param::new("tint", ParamValue::Color( [0.1, 0.2, 0.3, 1.0] )),
+            Param::new("scale", ParamValue::Float( 9.0 )).with_range(0.0, 2.0),
+        ];
+        let source = format!("#version 450\nlayout( local_size_x = 1 ) in;\n{}\nvoid main() {{}}\n", glsl_declaration(&params));
+        let code = compile_shader_source(&source, shaderc::ShaderKind::Compute, "params.comp", &HashMap::new()).unwrap();
+        let offsets = member_offsets(&code);
+        assert_eq!(offsets, [0, 16, 28, 32, 40, 48, 64]);
+
+        let handle = ParamHandle::default();
+        handle.set("steps", ParamValue::Int( 3 ));
+        let bytes = handle.pack(&params);
+        assert_eq!(bytes.len(), 80);
+        let at = |member: usize| offsets[member] as usize;
+        assert_eq!(read(&bytes, at(0)), 0.5f32.to_le_bytes());
+        assert_eq!(read(&bytes, at(1) + 8), 3.0f32.to_le_bytes());
+        assert_eq!(read(&bytes, at(2)), 3i32.to_le_bytes());
+        assert_eq!(read(&bytes, at(3)), 1u32.to_le_bytes());
+        assert_eq!(read(&bytes, at(4) + 4), 5.0f32.to_le_bytes());
+        assert_eq!(read(&bytes, at(5) + 12), 1.0f32.to_le_bytes());
+        assert_eq!(read(&bytes, at(6)), 2.0f32.to_le_bytes());
+    }
+
+    #[test]
+    fn rejects_empty_ranges() {
+        let params = [Param::new("range", ParamValue::Int( 2 )).with_range(8.0, 0.0)];
+        assert_eq!(validate_params(&params).err().unwrap(), "Parameter 'range' has an empty range [8, 0]");
+
+        let params = [Param::new("scale", ParamValue::Float( 1.0 )).with_range(0.0, f32::NAN)];
+        assert_eq!(validate_params(&params).err().unwrap(), "Parameter 'scale' has an empty range [0, NaN]");
+
+        let params = [Param::new("scale", ParamValue::Float( 1.0 )).with_range(1.0, 1.0)];
+        assert!(validate_params(&params).is_ok());
+    }
+}
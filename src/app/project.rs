@@ -0,0 +1,266 @@
+use std::f32::consts::TAU;
+use std::fs;
+use std::path::Path;
+use serde::{Deserialize, Deserializer};
+use crate::app::app::AppConfig;
+use crate::app::draw_orch::{BufferResource, DrawConfig, ImageResource, Output, Pass};
+use crate::app::params::Param;
+use crate::Error;
+
+/// An app described by a TOML file instead of code, run with `App::run_project` or the `kiyo` binary.
+/// See the project files section of the readme for the format.
+pub struct Project {
+    pub app_config: AppConfig,
+    pub draw_config: DrawConfig,
+    /// Tones played while the app runs, `None` without audio.
+    pub audio: Option<Audio>,
+}
+
+/// The keys of a project file, the tables and arrays of tables map onto `AppConfig` and `DrawConfig`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProjectFile {
+    #[serde(default)]
+    window: AppConfig,
+    passes: Vec<Pass>,
+    #[serde(default)]
+    images: Vec<ImageResource>,
+    #[serde(default)]
+    buffers: Vec<BufferResource>,
+    #[serde(default)]
+    params: Vec<Param>,
+    #[serde(default)]
+    output: Output,
+    #[serde(default)]
+    include_dirs: Vec<String>,
+    #[serde(default = "default_cache_dir", deserialize_with = "deserialize_cache_dir")]
+    cache_dir: Option<String>,
+    audio: Option<Audio>,
+}
+
+fn default_cache_dir() -> Option<String> {
+    DrawConfig::new().cache_dir
+}
+
+/// TOML has no null, so `false` stands for `None` and `true` for the default directory.
+fn deserialize_cache_dir<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum CacheDir {
+        Dir( String ),
+        Enabled( bool ),
+    }
+    match CacheDir::deserialize(deserializer)? {
+        CacheDir::Dir(dir) => Ok(Some(dir)),
+        CacheDir::Enabled(true) => Ok(default_cache_dir()),
+        CacheDir::Enabled(false) => Ok(None),
+    }
+}
+
+impl Project {
+    /// Read a project file. Shaders, include directories and the cache directory are relative to the file's directory.
+    /// Errors point at the line and key of the file which is invalid.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Project, Error> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path).map_err(|e| Error::Io(path.to_path_buf(), e))?;
+        let file: ProjectFile = toml::from_str(&content).map_err(|e| Error::Project(path.to_path_buf(), e.to_string()))?;
+
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let resolve = |p: &str| dir.join(p).to_string_lossy().to_string();
+        let passes = file.passes.into_iter()
+            .map(|pass| Pass { shader: resolve(&pass.shader), ..pass })
+            .collect();
+
+        Ok(Project {
+            app_config: file.window,
+            draw_config: DrawConfig {
+                passes,
+                images: file.images,
+                buffers: file.buffers,
+                params: file.params,
+                output: file.output,
+                include_dirs: file.include_dirs.iter().map(|d| resolve(d)).collect(),
+                cache_dir: file.cache_dir.as_deref().map(resolve),
+            },
+            audio: file.audio,
+        })
+    }
+}
+
+/// Audio of a project file, the tones are summed and played on both channels.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Audio {
+    pub tones: Vec<Tone>,
+}
+
+impl Audio {
+    /// The left and right sample at `t` seconds, see the `audio_func` of `App::run`.
+    pub fn sample(&self, t: f32) -> (f32, f32) {
+        let value = self.tones.iter().map(|tone| tone.sample(t)).sum::<f32>();
+        (value, value)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Tone {
+    /// Frequency in Hz. The audio time restarts every second, so only whole frequencies loop without a click.
+    pub frequency: u32,
+    #[serde(default = "default_volume")]
+    pub volume: f32,
+    #[serde(default)]
+    pub waveform: Waveform,
+}
+
+fn default_volume() -> f32 {
+    0.25
+}
+
+impl Tone {
+    fn sample(&self, t: f32) -> f32 {
+        let phase = (t * self.frequency as f32).fract();
+        let value = match self.waveform {
+            Waveform::Sine => (phase * TAU).sin(),
+            Waveform::Square => if phase < 0.5 { 1.0 } else { -1.0 },
+            Waveform::Sawtooth => phase * 2.0 - 1.0,
+            Waveform::Triangle => 1.0 - (phase * 4.0 - 2.0).abs(),
+        };
+        value * self.volume
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Waveform {
+    #[default]
+    Sine,
+    Square,
+    Sawtooth,
+    Triangle,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use crate::app::draw_orch::{BufferSize, DispatchConfig};
+    use crate::app::params::ParamValue;
+    use crate::test_dir::TestDir;
+
+    /// A project file in a directory of its own.
+    struct TestProject {
+        dir: TestDir,
+    }
+
+    impl TestProject {
+        fn new(name: &str, content: &str) -> TestProject {
+            let dir = TestDir::new(&format!("project-{}", name));
+            fs::write(dir.path.join("kiyo.toml"), content).unwrap();
+            TestProject { dir }
+        }
+
+        fn path(&self) -> PathBuf {
+            self.dir.path.join("kiyo.toml")
+        }
+
+        fn error(&self) -> String {
+            match Project::from_file(self.path()) {
+                Ok(_) => panic!("{} should be invalid", self.path().display()),
+                Err(e) => e.to_string(),
+            }
+        }
+    }
+
+    fn example(name: &str) -> Project {
+        Project::from_file(Path::new(env!("CARGO_MANIFEST_DIR")).join("examples").join(name).join("kiyo.toml")).unwrap()
+    }
+
+    #[test]
+    fn parses_blur_pass_example() {
+        let project = example("blur-pass");
+        assert_eq!((project.app_config.width, project.app_config.height), (1000, 1000));
+
+        let config = project.draw_config;
+        assert_eq!(config.passes.len(), 2);
+        assert!(config.passes[0].shader.ends_with("blur-pass/shaders/screen_shader.comp"));
+        assert!(matches!(config.passes[1].dispatches, DispatchConfig::FullScreen));
+        assert_eq!((config.passes[1].input_resources.as_slice(), config.passes[1].output_resources.as_slice()), ([0].as_slice(), [1].as_slice()));
+        assert_eq!(config.output, Output::Image(1));
+        assert_eq!(config.params[0].name, "range");
+        assert_eq!(config.params[0].default, ParamValue::Int(2));
+        assert_eq!(config.params[0].range, Some((0.0, 8.0)));
+        assert!(project.audio.is_none());
+    }
+
+    #[test]
+    fn parses_particles_example() {
+        let config = example("particles").draw_config;
+        assert_eq!(config.passes.len(), 2);
+        assert!(matches!(config.passes[0].dispatches, DispatchConfig::Count(1, 1, 1)));
+        assert!(matches!(config.passes[1].dispatches, DispatchConfig::Indirect { buffer: 0, offset: 0 }));
+        assert_eq!(config.passes[1].workgroup_size, Some((256, 1, 1)));
+        assert_eq!(config.buffers[0].id, 0);
+        assert!(matches!(config.buffers[0].size, BufferSize::Elements { count: 4, stride: 4 }));
+        assert_eq!(config.output, Output::Last);
+    }
+
+    #[test]
+    fn rejects_unknown_key() {
+        let project = TestProject::new("unknown-key", "[[passes]]\nshader = \"screen.comp\"\ndispatches = \"full-screen\"\nouptut_resources = [ 0 ]\n");
+        let error = project.error();
+        assert!(error.starts_with(&project.path().display().to_string()), "{}", error);
+        assert!(error.contains("line 4"), "{}", error);
+        assert!(error.contains("unknown field `ouptut_resources`"), "{}", error);
+    }
+
+    #[test]
+    fn rejects_unknown_variant() {
+        let project = TestProject::new("unknown-variant", "[[passes]]\nshader = \"screen.comp\"\ndispatches = \"full-screen\"\n\n[[images]]\nid = 0\nformat = \"rgb8\"\n");
+        let error = project.error();
+        assert!(error.contains("line 7"), "{}", error);
+        assert!(error.contains("format = \"rgb8\""), "{}", error);
+        assert!(error.contains("unknown variant `rgb8`"), "{}", error);
+    }
+
+    #[test]
+    fn resolves_paths_from_project_directory() {
+        let project = TestProject::new("paths", "include_dirs = [ \"include\" ]\ncache_dir = \"cache\"\n\n[[passes]]\nshader = \"shaders/screen.comp\"\ndispatches = \"full-screen\"\n");
+        let config = Project::from_file(project.path()).unwrap().draw_config;
+        assert_eq!(PathBuf::from(&config.passes[0].shader), project.dir.path.join("shaders/screen.comp"));
+        assert_eq!(config.include_dirs.iter().map(PathBuf::from).collect::<Vec<_>>(), [project.dir.path.join("include")]);
+        assert_eq!(config.cache_dir.map(PathBuf::from), Some(project.dir.path.join("cache")));
+    }
+
+    #[test]
+    fn deserializes_cache_dir() {
+        let cache_dir = |value: &str| {
+            let project = TestProject::new("cache-dir", &format!("{}passes = []\n", value));
+            Project::from_file(project.path()).unwrap().draw_config.cache_dir
+        };
+        assert_eq!(cache_dir(""), default_cache_dir());
+        assert_eq!(cache_dir("cache_dir = true\n"), default_cache_dir());
+        assert_eq!(cache_dir("cache_dir = false\n"), None);
+        assert!(cache_dir("cache_dir = \"shaders/cache\"\n").is_some_and(|dir| dir.ends_with("shaders/cache")));
+    }
+
+    #[test]
+    fn samples_tones() {
+        let tone = |waveform| Tone { frequency: 1, volume: 0.5, waveform };
+        assert!((tone(Waveform::Sine).sample(0.25) - 0.5).abs() < 1e-6);
+        assert!(tone(Waveform::Sine).sample(0.0).abs() < 1e-6);
+        assert_eq!(tone(Waveform::Square).sample(0.25), 0.5);
+        assert_eq!(tone(Waveform::Square).sample(0.75), -0.5);
+        assert_eq!(tone(Waveform::Sawtooth).sample(0.0), -0.5);
+        assert_eq!(tone(Waveform::Sawtooth).sample(0.75), 0.25);
+        assert_eq!(tone(Waveform::Triangle).sample(0.0), -0.5);
+        assert_eq!(tone(Waveform::Triangle).sample(0.5), 0.5);
+
+        // Only the fraction of a period counts, so whole frequencies repeat every second
+        let a4 = Tone { frequency: 440, volume: 1.0, waveform: Waveform::Square };
+        assert_eq!(a4.sample(0.1 / 440.0), a4.sample(1.0 + 0.1 / 440.0));
+
+        let audio = Audio { tones: vec![tone(Waveform::Square), tone(Waveform::Sawtooth)] };
+        assert_eq!(audio.sample(0.75), (-0.25, -0.25));
+    }
+}
@@ -1,11 +1,20 @@
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use ash::vk;
 use ash::vk::{FenceCreateFlags, ImageAspectFlags, ImageSubresourceLayers, Offset3D, PhysicalDevice, Queue};
 use bytemuck::{Pod, Zeroable};
 use gpu_allocator::vulkan::{AllocatorCreateDesc};
+use log::error;
 use crate::app::{DrawOrchestrator, Window};
-use crate::vulkan::{Allocator, CommandBuffer, CommandPool, Device, Instance, Surface, Swapchain};
+use crate::app::app::{FilterMode, ScalingMode};
+use crate::app::clock::Clock;
+use crate::app::input::{InputState, ShaderInput};
+use crate::app::inspector::ImageInspector;
+use crate::app::overlay_painter::OverlayPainter;
+use crate::app::params::ParamHandle;
+use crate::app::draw_orch::{Dispatch, Persistence};
+use crate::Error;
+use crate::vulkan::{Allocator, Buffer, CommandBuffer, CommandPool, Device, Image, Instance, Surface, Swapchain};
 
 pub struct Renderer {
     pub render_finished_semaphores: Vec<vk::Semaphore>,
@@ -13,102 +22,283 @@ pub struct Renderer {
     pub command_buffers: Vec<CommandBuffer>,
     pub command_pool: CommandPool,
     pub queue: Queue,
-    pub swapchain: Swapchain,
+    /// `None` for headless renderers.
+    pub swapchain: Option<Swapchain>,
+    /// Set when the swapchain no longer matches the surface, it has to be recreated with `recreate_swapchain`.
+    pub swapchain_out_of_date: bool,
     pub entry: ash::Entry,
-    pub surface: Surface,
+    /// `None` for headless renderers.
+    pub surface: Option<Surface>,
     pub frame_index: usize,
     pub in_flight_fences: Vec<vk::Fence>,
     pub allocator: Allocator,
     pub device: Device,
     pub physical_device: PhysicalDevice,
     pub instance: Instance,
-    pub start_time: Instant,
+    /// Time passed to the shaders, it can be paused or set from the overlay.
+    pub clock: Clock,
+    /// Frames rendered since startup.
+    pub frame_counter: u32,
+    /// Time of the previous frame, to compute the delta time.
+    pub last_frame_time: f32,
+    /// Audio sample rate passed to the shaders, 0 without audio.
+    pub sample_rate: f32,
+    /// Values of the orchestrator's shader parameters, uploaded every frame.
+    pub params: ParamHandle,
+    /// How the output image is scaled to the swapchain.
+    pub scaling: ScalingMode,
+    pub filter: FilterMode,
+    /// Chooses the image which is presented, instead of the output image.
+    pub inspector: ImageInspector,
 }
 
+/// Push constants of every pass, matching this GLSL block:
+/// ```glsl
+/// layout( push_constant ) uniform PushConstants
+/// {
+///     float time;
+///     int in_image;
+///     int out_image;
+///     uint mouse_buttons;
+///     vec4 mouse;
+///     vec2 scroll;
+///     uvec2 keys;
+/// } constants;
+/// ```
+/// Fields are only appended, so shaders may declare a prefix of the block.
 #[repr(C)]
 #[derive(Copy, Clone, Pod, Zeroable)]
 pub struct PushConstants {
     pub time: f32,
     pub in_image: i32,
     pub out_image: i32,
+    /// Bitmask of held mouse buttons: left 1, right 2, middle 4.
+    pub mouse_buttons: u32,
+    /// Shadertoy style `iMouse` in output image pixels, see `InputState::shader_input`.
+    pub mouse: [f32; 4],
+    /// Scroll distance in lines during this frame.
+    pub scroll: [f32; 2],
+    /// Bitmask of held keys, indexed by `input::TRACKED_KEYS`.
+    pub keys: [u32; 2],
+}
+
+/// Per-frame engine values, matching the std140 `Engine` block in `include/kiyo/engine.glsl`.
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+pub struct EngineUniforms {
+    pub time: f32,
+    pub delta_time: f32,
+    pub frame: u32,
+    pub sample_rate: f32,
+    pub resolution: [u32; 2],
+    pub output_resolution: [u32; 2],
+    /// Year, month, day and seconds since midnight, in UTC.
+    pub date: [f32; 4],
+}
+
+/// Command buffers and synchronization primitives, one of each per frame in flight.
+struct FrameResources {
+    command_buffers: Vec<CommandBuffer>,
+    image_available_semaphores: Vec<vk::Semaphore>,
+    render_finished_semaphores: Vec<vk::Semaphore>,
+    in_flight_fences: Vec<vk::Fence>,
+}
+
+/// Date offscreen frames start at, 2000-01-01 in UTC, so their output doesn't depend on when they're rendered.
+const OFFSCREEN_START_DATE: Duration = Duration::from_secs(946684800);
+
+/// The date `since_epoch` after 1970-01-01 as year, month, day and seconds since midnight, in UTC.
+fn date(since_epoch: Duration) -> [f32; 4] {
+    let days = (since_epoch.as_secs() / 86400) as i64;
+    let seconds = (since_epoch.as_secs_f64() % 86400.0) as f32;
+
+    // Civil date from days since 1970-01-01, see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    [year as f32, month as f32, day as f32, seconds]
 }
 
 impl Renderer {
-    pub fn new(window: &Window, vsync: bool) -> Renderer {
+    pub fn new(window: &Window, vsync: bool) -> Result<Renderer, Error> {
         let entry = ash::Entry::linked();
-        let instance = Instance::new(&entry, window.display_handle());
-        let surface = Surface::new(&entry, &instance, &window);
-        let (physical_device, queue_family_index) = instance.create_physical_device(&entry, &surface);
-        let device = Device::new(&instance, physical_device, queue_family_index);
+        let instance = Instance::new(&entry, Some(window.display_handle()))?;
+        let surface = Surface::new(&entry, &instance, window)?;
+        let (physical_device, queue_family_index) = instance.create_physical_device(&entry, Some(&surface))?;
+        let device = Device::new(&instance, physical_device, queue_family_index, true)?;
         let queue = device.get_queue(0);
-        let command_pool = CommandPool::new(&device, queue_family_index);
-
-        let allocator = Allocator::new(&AllocatorCreateDesc {
-            instance: instance.handle().clone(),
-            device: device.handle().clone(),
-            physical_device,
-            debug_settings: Default::default(),
-            buffer_device_address: false,  // Ideally, check the BufferDeviceAddressFeatures struct.
-            allocation_sizes: Default::default(),
-        });
+        let command_pool = CommandPool::new(&device, queue_family_index)?;
+        let mut allocator = Self::create_allocator(&instance, &device, physical_device)?;
 
         let present_mode = if vsync {
             vk::PresentModeKHR::FIFO
         } else {
             vk::PresentModeKHR::IMMEDIATE
         };
-        let swapchain = Swapchain::new(&instance, &physical_device, &device, &window, &surface, present_mode);
-        Self::transition_swapchain_images(&device, &command_pool, &queue, &swapchain);
+        let swapchain = Swapchain::new(&instance, &physical_device, &device, window, &surface, present_mode, None)?;
+        Self::transition_swapchain_images(&device, &command_pool, &queue, &swapchain)?;
 
-        let command_buffers = (0..swapchain.get_image_count()).map(|_| {
-            CommandBuffer::new(&device, &command_pool)
-        }).collect::<Vec<CommandBuffer>>();
+        let FrameResources { command_buffers, image_available_semaphores, render_finished_semaphores, in_flight_fences } =
+            Self::create_frame_resources(&device, &command_pool, swapchain.get_image_count())?;
+        let inspector = ImageInspector::new(&device, &mut allocator, command_buffers.len())?;
 
-        let image_available_semaphores = (0..swapchain.get_image_count()).map(|_| unsafe {
-            let semaphore_create_info = vk::SemaphoreCreateInfo::default();
-            device.handle().create_semaphore(&semaphore_create_info, None)
-                .expect("Failed to create semaphore")
-        }).collect::<Vec<vk::Semaphore>>();
-
-        let render_finished_semaphores = (0..swapchain.get_image_count()).map(|_| unsafe {
-            let semaphore_create_info = vk::SemaphoreCreateInfo::default();
-            device.handle().create_semaphore(&semaphore_create_info, None)
-                .expect("Failed to create semaphore")
-        }).collect::<Vec<vk::Semaphore>>();
+        Ok(Self {
+            entry,
+            device,
+            physical_device,
+            instance,
+            allocator,
+            surface: Some(surface),
+            queue,
+            swapchain: Some(swapchain),
+            swapchain_out_of_date: false,
+            render_finished_semaphores,
+            image_available_semaphores,
+            in_flight_fences,
+            command_pool,
+            command_buffers,
+            frame_index: 0,
+            clock: Clock::new(),
+            frame_counter: 0,
+            last_frame_time: 0.0,
+            sample_rate: 0.0,
+            params: ParamHandle::default(),
+            scaling: ScalingMode::Stretch,
+            filter: FilterMode::Nearest,
+            inspector,
+        })
+    }
 
-        let in_flight_fences = (0..swapchain.get_image_count()).map(|_| {
-            unsafe {
-                let fence_create_info = vk::FenceCreateInfo::default()
-                    .flags(FenceCreateFlags::SIGNALED);
-                device.handle().create_fence(&fence_create_info, None)
-                    .expect("Failed to create fence")
-            }
-        }).collect::<Vec<vk::Fence>>();
+    /// Create a renderer without a window, surface or swapchain.
+    /// Frames are rendered with `draw_offscreen` and have to be read back by the caller.
+    pub fn new_headless() -> Result<Renderer, Error> {
+        let entry = ash::Entry::linked();
+        let instance = Instance::new(&entry, None)?;
+        let (physical_device, queue_family_index) = instance.create_physical_device(&entry, None)?;
+        let device = Device::new(&instance, physical_device, queue_family_index, false)?;
+        let queue = device.get_queue(0);
+        let command_pool = CommandPool::new(&device, queue_family_index)?;
+        let mut allocator = Self::create_allocator(&instance, &device, physical_device)?;
 
-        let start_time = std::time::Instant::now();
+        // Offscreen frames are rendered one at a time
+        let FrameResources { command_buffers, image_available_semaphores, render_finished_semaphores, in_flight_fences } =
+            Self::create_frame_resources(&device, &command_pool, 1)?;
+        let inspector = ImageInspector::new(&device, &mut allocator, 1)?;
 
-        Self {
+        Ok(Self {
             entry,
             device,
             physical_device,
             instance,
             allocator,
-            surface,
+            surface: None,
             queue,
-            swapchain,
+            swapchain: None,
+            swapchain_out_of_date: false,
             render_finished_semaphores,
             image_available_semaphores,
             in_flight_fences,
             command_pool,
             command_buffers,
             frame_index: 0,
-            start_time,
+            clock: Clock::new(),
+            frame_counter: 0,
+            last_frame_time: 0.0,
+            sample_rate: 0.0,
+            params: ParamHandle::default(),
+            scaling: ScalingMode::Stretch,
+            filter: FilterMode::Nearest,
+            inspector,
+        })
+    }
+
+    fn create_allocator(instance: &Instance, device: &Device, physical_device: PhysicalDevice) -> Result<Allocator, Error> {
+        Allocator::new(&AllocatorCreateDesc {
+            instance: instance.handle().clone(),
+            device: device.handle().clone(),
+            physical_device,
+            debug_settings: Default::default(),
+            buffer_device_address: false,  // Ideally, check the BufferDeviceAddressFeatures struct.
+            allocation_sizes: Default::default(),
+        })
+    }
+
+    /// Create the command buffers and synchronization primitives for each frame in flight.
+    fn create_frame_resources(device: &Device, command_pool: &CommandPool, frame_count: u32) -> Result<FrameResources, Error> {
+        let command_buffers = (0..frame_count).map(|_| {
+            CommandBuffer::new(device, command_pool)
+        }).collect::<Result<Vec<CommandBuffer>, Error>>()?;
+
+        let image_available_semaphores = (0..frame_count).map(|_| unsafe {
+            let semaphore_create_info = vk::SemaphoreCreateInfo::default();
+            device.handle().create_semaphore(&semaphore_create_info, None)
+                .map_err(Error::vulkan("Failed to create semaphore"))
+        }).collect::<Result<Vec<vk::Semaphore>, Error>>()?;
+
+        let render_finished_semaphores = (0..frame_count).map(|_| unsafe {
+            let semaphore_create_info = vk::SemaphoreCreateInfo::default();
+            device.handle().create_semaphore(&semaphore_create_info, None)
+                .map_err(Error::vulkan("Failed to create semaphore"))
+        }).collect::<Result<Vec<vk::Semaphore>, Error>>()?;
+
+        let in_flight_fences = (0..frame_count).map(|_| {
+            unsafe {
+                let fence_create_info = vk::FenceCreateInfo::default()
+                    .flags(FenceCreateFlags::SIGNALED);
+                device.handle().create_fence(&fence_create_info, None)
+                    .map_err(Error::vulkan("Failed to create fence"))
+            }
+        }).collect::<Result<Vec<vk::Fence>, Error>>()?;
+
+        Ok(FrameResources {
+            command_buffers,
+            image_available_semaphores,
+            render_finished_semaphores,
+            in_flight_fences,
+        })
+    }
+
+    /// Recreate the swapchain at the current size of the window, e.g. after a resize.
+    pub fn recreate_swapchain(&mut self, window: &Window) -> Result<(), Error> {
+        let extent = window.get_extent();
+        if extent.width == 0 || extent.height == 0 {
+            // Minimized, wait until the window has a size again
+            return Ok(());
         }
+
+        let surface = self.surface.as_ref().ok_or(Error::Headless)?;
+        let old_swapchain = self.swapchain.take().ok_or(Error::Headless)?;
+
+        // The old swapchain images may still be in use by frames in flight
+        self.device.wait_idle()?;
+
+        let swapchain = Swapchain::new(
+            &self.instance,
+            &self.physical_device,
+            &self.device,
+            window,
+            surface,
+            old_swapchain.get_present_mode(),
+            Some(&old_swapchain)
+        )?;
+        drop(old_swapchain);
+        Self::transition_swapchain_images(&self.device, &self.command_pool, &self.queue, &swapchain)?;
+
+        self.swapchain = Some(swapchain);
+        self.swapchain_out_of_date = false;
+        Ok(())
     }
 
-    fn transition_swapchain_images(device: &Device, command_pool: &CommandPool, queue: &Queue, swapchain: &Swapchain) {
-        let image_command_buffer = Arc::new(CommandBuffer::new(device, command_pool));
-        image_command_buffer.begin();
+    fn transition_swapchain_images(device: &Device, command_pool: &CommandPool, queue: &Queue, swapchain: &Swapchain) -> Result<(), Error> {
+        let image_command_buffer = Arc::new(CommandBuffer::new(device, command_pool)?);
+        image_command_buffer.begin()?;
         swapchain.get_images().iter().for_each(|image| {
             let image_memory_barrier = vk::ImageMemoryBarrier::default()
                 .old_layout(vk::ImageLayout::UNDEFINED)
@@ -137,25 +327,80 @@ impl Renderer {
                 )
             }
         });
-        image_command_buffer.end();
-        device.submit_single_time_command(*queue, image_command_buffer);
+        image_command_buffer.end()?;
+        device.submit_single_time_command(*queue, &image_command_buffer)
     }
     
-    fn record_command_buffer(&mut self, frame_index: usize, image_index: usize, draw_orchestrator: &mut DrawOrchestrator) {
+    /// Gather the engine values for a frame rendered at `time` and advance the frame counter.
+    fn next_frame_uniforms(&mut self, draw_orchestrator: &DrawOrchestrator, time: f32, date: [f32; 4]) -> EngineUniforms {
+        let output_image = draw_orchestrator.output_image();
+        let uniforms = EngineUniforms {
+            time,
+            // Moving the clock back doesn't run the simulation backwards
+            delta_time: if self.frame_counter == 0 { 0.0 } else { (time - self.last_frame_time).max(0.0) },
+            frame: self.frame_counter,
+            sample_rate: self.sample_rate,
+            resolution: draw_orchestrator.resolution.to_array(),
+            output_resolution: [output_image.width, output_image.height],
+            date,
+        };
 
-        let command_buffer = &self.command_buffers[frame_index];
+        self.frame_counter += 1;
+        self.last_frame_time = time;
+        uniforms
+    }
+
+    /// Record the compute passes of the orchestrator, starting from cleared images.
+    fn record_passes(&self, command_buffer: &CommandBuffer, draw_orchestrator: &DrawOrchestrator, uniforms: &EngineUniforms, input: &ShaderInput) {
+
+        // Upload the engine values and parameters, after the previous frame is done reading them
+        let engine_uniforms = &draw_orchestrator.engine_uniforms;
+        let mut uniform_buffers = vec![(2, engine_uniforms)];
+        let mut uniform_data = vec![bytemuck::bytes_of(uniforms).to_vec()];
+        if let Some(param_buffer) = &draw_orchestrator.param_buffer {
+            uniform_buffers.push((3, param_buffer));
+            uniform_data.push(self.params.pack(&draw_orchestrator.params));
+        }
+
+        let updated_buffers = uniform_buffers.iter().map(|(_, b)| *b).collect::<Vec<&Buffer>>();
+        command_buffer.memory_barriers(
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::NONE,
+            vk::AccessFlags::TRANSFER_WRITE,
+            &[],
+            &updated_buffers
+        );
+        for ((_, buffer), data) in uniform_buffers.iter().zip(&uniform_data) {
+            unsafe {
+                self.device.handle().cmd_update_buffer(command_buffer.handle(), *buffer.handle(), 0, data);
+            }
+        }
+        command_buffer.memory_barriers(
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::TRANSFER_WRITE,
+            vk::AccessFlags::UNIFORM_READ,
+            &[],
+            &updated_buffers
+        );
 
-        command_buffer.begin();
+        let mut kept_images = Vec::new();
+        for (i, r) in draw_orchestrator.all_images() {
+            if r.persistence != Persistence::Transient && !draw_orchestrator.clear_history {
+                kept_images.push(i);
+                continue;
+            }
 
-        for i in &draw_orchestrator.images {
+            // The previous frame may still be accessing the image
             self.transition_image(
                 command_buffer,
                 &i.image,
                 vk::ImageLayout::GENERAL,
                 vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                vk::PipelineStageFlags::TOP_OF_PIPE,
+                vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::TRANSFER,
                 vk::PipelineStageFlags::TRANSFER,
-                vk::AccessFlags::NONE,
+                vk::AccessFlags::SHADER_WRITE | vk::AccessFlags::TRANSFER_WRITE,
                 vk::AccessFlags::TRANSFER_WRITE
             );
 
@@ -165,13 +410,11 @@ impl Renderer {
                         command_buffer.handle(),
                         i.image,
                         vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-                        &vk::ClearColorValue {
-                            float32: [1.0, 0.0, 0.0, 1.0]
-                        },
+                        &r.format.clear_value(),
                         &[vk::ImageSubresourceRange {
                             aspect_mask: ImageAspectFlags::COLOR,
                             base_mip_level: 0,
-                            level_count: 1,
+                            level_count: vk::REMAINING_MIP_LEVELS,
                             base_array_layer: 0,
                             layer_count: 1,
                         }]
@@ -186,42 +429,262 @@ impl Renderer {
                 vk::PipelineStageFlags::TRANSFER,
                 vk::PipelineStageFlags::COMPUTE_SHADER,
                 vk::AccessFlags::TRANSFER_WRITE,
-                vk::AccessFlags::SHADER_WRITE
+                vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE
+            );
+        }
+
+        // Restore the initial buffer contents
+        let buffers = draw_orchestrator.buffers.iter().collect::<Vec<&Buffer>>();
+        if draw_orchestrator.clear_history && !buffers.is_empty() {
+            command_buffer.memory_barriers(
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::AccessFlags::SHADER_WRITE,
+                vk::AccessFlags::TRANSFER_WRITE,
+                &[],
+                &buffers
+            );
+
+            for (buffer, initial_buffer) in draw_orchestrator.buffers.iter().zip(&draw_orchestrator.initial_buffers) {
+                unsafe {
+                    match initial_buffer {
+                        Some(initial_buffer) => {
+                            self.device.handle().cmd_copy_buffer(
+                                command_buffer.handle(),
+                                *initial_buffer.handle(),
+                                *buffer.handle(),
+                                &[vk::BufferCopy::default().size(buffer.size)]
+                            );
+                        },
+                        None => {
+                            self.device.handle().cmd_fill_buffer(command_buffer.handle(), *buffer.handle(), 0, vk::WHOLE_SIZE, 0);
+                        }
+                    }
+                }
+            }
+
+            command_buffer.memory_barriers(
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::DRAW_INDIRECT,
+                vk::AccessFlags::TRANSFER_WRITE,
+                vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE | vk::AccessFlags::INDIRECT_COMMAND_READ,
+                &[],
+                &buffers
+            );
+        }
+        let kept_buffers = if draw_orchestrator.clear_history { Vec::new() } else { buffers };
+
+        // Make the previous frame's writes to persistent images and buffers visible
+        if !kept_images.is_empty() || !kept_buffers.is_empty() {
+            command_buffer.memory_barriers(
+                vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::DRAW_INDIRECT,
+                vk::AccessFlags::SHADER_WRITE,
+                vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE | vk::AccessFlags::INDIRECT_COMMAND_READ,
+                &kept_images,
+                &kept_buffers
             );
         }
 
         // Compute images
-        let current_time = self.start_time.elapsed().as_secs_f32();
+        let bound_images = draw_orchestrator.all_images().map(|(i, _)| i).collect::<Vec<&Image>>();
+        let bound_buffers = draw_orchestrator.buffers.iter().collect::<Vec<&Buffer>>();
         for p in &draw_orchestrator.passes {
+            // Wait for earlier passes which access the same resources
+            if !p.barrier_images.is_empty() || !p.barrier_buffers.is_empty() {
+                let barrier_images = p.barrier_images.iter()
+                    .map(|&id| &draw_orchestrator.images[id as usize])
+                    .collect::<Vec<&Image>>();
+                let barrier_buffers = p.barrier_buffers.iter()
+                    .map(|&id| &draw_orchestrator.buffers[id as usize])
+                    .collect::<Vec<&Buffer>>();
+
+                // Indirect arguments are read before the shader runs
+                let (dst_stage, dst_access) = match p.dispatches {
+                    Dispatch::Direct(_) => (
+                        vk::PipelineStageFlags::COMPUTE_SHADER,
+                        vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE
+                    ),
+                    Dispatch::Indirect { .. } => (
+                        vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::DRAW_INDIRECT,
+                        vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE | vk::AccessFlags::INDIRECT_COMMAND_READ
+                    ),
+                };
+                command_buffer.memory_barriers(
+                    vk::PipelineStageFlags::COMPUTE_SHADER,
+                    dst_stage,
+                    vk::AccessFlags::SHADER_WRITE,
+                    dst_access,
+                    &barrier_images,
+                    &barrier_buffers
+                );
+            }
+
+            // Barriers of disabled passes are still recorded, as later passes rely on them
+            if !p.enabled {
+                continue;
+            }
+
             command_buffer.bind_pipeline(&p.compute_pipeline);
             let push_constants = PushConstants {
-                time: current_time,
+                time: uniforms.time,
                 in_image: p.in_images.first().map(|&x| x as i32).unwrap_or(-1),
                 out_image: p.out_images.first().map(|&x| x as i32).unwrap_or(-1),
+                mouse_buttons: input.mouse_buttons,
+                mouse: input.mouse,
+                scroll: input.scroll,
+                keys: input.keys,
             };
             command_buffer.push_constants(&p.compute_pipeline, vk::ShaderStageFlags::COMPUTE, 0, &bytemuck::cast_slice(std::slice::from_ref(&push_constants)));
-            command_buffer.bind_push_descriptor_images(&p.compute_pipeline, &draw_orchestrator.images);
-            command_buffer.dispatch(p.dispatches.x, p.dispatches.y, p.dispatches.z);
+            command_buffer.bind_push_descriptor_resources(&p.compute_pipeline, &bound_images, &bound_buffers, &uniform_buffers);
+            match p.dispatches {
+                Dispatch::Direct(count) => {
+                    command_buffer.dispatch(count.x, count.y, count.z);
+                }
+                Dispatch::Indirect { buffer, offset } => {
+                    command_buffer.dispatch_indirect(&draw_orchestrator.buffers[buffer as usize], offset);
+                }
+            }
+        };
 
-            // TODO: Add synchronization between passes
+        self.record_composition(command_buffer, draw_orchestrator);
+    }
+
+    /// Blit the output images into their cells of the composite image, when several images are presented.
+    fn record_composition(&self, command_buffer: &CommandBuffer, draw_orchestrator: &DrawOrchestrator) {
+        let composite = match &draw_orchestrator.composite {
+            Some(composite) => composite,
+            None => return,
         };
+        let mut sources = draw_orchestrator.output_images.clone();
+        sources.sort();
+        sources.dedup();
 
-        // Copy to swapchain
+        // Not every format supports linear filtering
+        let linear = self.filter == FilterMode::Linear
+            && self.supports_linear_blit(composite.format)
+            && sources.iter().all(|&id| self.supports_linear_blit(draw_orchestrator.images[id as usize].format));
+        let filter = if linear { vk::Filter::LINEAR } else { vk::Filter::NEAREST };
 
-        let output_image = draw_orchestrator.images.last().expect("No images found to output");
+        for &id in &sources {
+            self.transition_image(
+                command_buffer,
+                &draw_orchestrator.images[id as usize].image,
+                vk::ImageLayout::GENERAL,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                vk::PipelineStageFlags::COMPUTE_SHADER,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::AccessFlags::SHADER_WRITE,
+                vk::AccessFlags::TRANSFER_READ
+            );
+        }
 
+        // Empty cells were cleared when the composite was created, so its contents are kept
         self.transition_image(
             command_buffer,
-            &output_image.image,
+            &composite.image,
             vk::ImageLayout::GENERAL,
-            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::TRANSFER,
             vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::SHADER_READ | vk::AccessFlags::TRANSFER_READ,
+            vk::AccessFlags::TRANSFER_WRITE
+        );
+
+        let columns = draw_orchestrator.output_columns;
+        let rows = (draw_orchestrator.output_images.len() as u32).div_ceil(columns);
+        let (cell_width, cell_height) = (composite.width / columns, composite.height / rows);
+        let subresource = ImageSubresourceLayers::default()
+            .aspect_mask(ImageAspectFlags::COLOR)
+            .base_array_layer(0)
+            .layer_count(1)
+            .mip_level(0);
+
+        for (cell, &id) in draw_orchestrator.output_images.iter().enumerate() {
+            let source = &draw_orchestrator.images[id as usize];
+            let x = (cell as u32 % columns * cell_width) as i32;
+            let y = (cell as u32 / columns * cell_height) as i32;
+            unsafe {
+                self.device.handle().cmd_blit_image(
+                    command_buffer.handle(),
+                    source.image,
+                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    composite.image,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    &[vk::ImageBlit::default()
+                        .src_offsets([
+                            Offset3D::default(),
+                            Offset3D::default().x(source.width as i32).y(source.height as i32).z(1)
+                        ])
+                        .dst_offsets([
+                            Offset3D::default().x(x).y(y),
+                            Offset3D::default().x(x + cell_width as i32).y(y + cell_height as i32).z(1)
+                        ])
+                        .src_subresource(subresource)
+                        .dst_subresource(subresource)
+                    ],
+                    filter,
+                );
+            }
+        }
+
+        // Later passes and the presentation synchronize with compute work
+        for &id in &sources {
+            self.transition_image(
+                command_buffer,
+                &draw_orchestrator.images[id as usize].image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                vk::ImageLayout::GENERAL,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::TRANSFER,
+                vk::AccessFlags::TRANSFER_READ,
+                vk::AccessFlags::NONE
+            );
+        }
+        self.transition_image(
+            command_buffer,
+            &composite.image,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::ImageLayout::GENERAL,
             vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::COMPUTE_SHADER | vk::PipelineStageFlags::TRANSFER,
             vk::AccessFlags::TRANSFER_WRITE,
+            vk::AccessFlags::SHADER_READ | vk::AccessFlags::TRANSFER_READ
+        );
+    }
+
+    fn record_command_buffer(&self, frame_index: usize, image_index: usize, draw_orchestrator: &DrawOrchestrator, uniforms: &EngineUniforms, input: &ShaderInput, overlay: Option<&OverlayPainter>) -> Result<(), Error> {
+
+        let command_buffer = &self.command_buffers[frame_index];
+        let swapchain = self.swapchain.as_ref().ok_or(Error::Headless)?;
+
+        command_buffer.begin()?;
+
+        self.record_passes(command_buffer, draw_orchestrator, uniforms, input);
+
+        // Copy to swapchain, the inspector may present another image than the output image
+
+        let presented_image = self.inspector.record(self, command_buffer, draw_orchestrator, frame_index);
+        let dst_offsets = self.present_region(presented_image.width, presented_image.height, swapchain.get_extent());
+
+        // Not every format supports linear filtering
+        let filter = match self.filter {
+            FilterMode::Linear if self.supports_linear_blit(presented_image.format) => vk::Filter::LINEAR,
+            _ => vk::Filter::NEAREST,
+        };
+
+        self.transition_image(
+            command_buffer,
+            &presented_image.image,
+            vk::ImageLayout::GENERAL,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::SHADER_WRITE,
             vk::AccessFlags::TRANSFER_READ
         );
 
-        let swapchain_image = self.swapchain.get_images()[image_index];
+        let swapchain_image = swapchain.get_images()[image_index];
 
         // Transition the swapchain image
         self.transition_image(
@@ -256,19 +719,16 @@ impl Renderer {
             // Use a blit, as a copy doesn't synchronize properly to the swapchain on MoltenVK
             self.device.handle().cmd_blit_image(
                 command_buffer.handle(),
-                output_image.image,
+                presented_image.image,
                 vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
                 swapchain_image,
                 vk::ImageLayout::TRANSFER_DST_OPTIMAL,
                 &[vk::ImageBlit::default()
                     .src_offsets([
                         Offset3D::default(),
-                        Offset3D::default().x(output_image.width as i32).y(output_image.height as i32).z(1)
-                    ])
-                    .dst_offsets([
-                        Offset3D::default(),
-                        Offset3D::default().x(output_image.width as i32).y(output_image.height as i32).z(1)
+                        Offset3D::default().x(presented_image.width as i32).y(presented_image.height as i32).z(1)
                     ])
+                    .dst_offsets(dst_offsets)
                     .src_subresource(
                         ImageSubresourceLayers::default()
                             .aspect_mask(ImageAspectFlags::COLOR)
@@ -284,25 +744,28 @@ impl Renderer {
                             .mip_level(0)
                     )
                 ],
-                vk::Filter::NEAREST,
+                filter,
             );
         }
 
-        // Transfer back to default states
-        self.transition_image(
-            command_buffer,
-            &swapchain_image,
-            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
-            vk::ImageLayout::PRESENT_SRC_KHR,
-            vk::PipelineStageFlags::TRANSFER,
-            vk::PipelineStageFlags::BOTTOM_OF_PIPE,
-            vk::AccessFlags::TRANSFER_WRITE,
-            vk::AccessFlags::NONE
-        );
+        // Transfer back to default states, the overlay's render pass transitions the swapchain image itself
+        match overlay {
+            Some(overlay) => overlay.record(&self.device, command_buffer, frame_index, image_index),
+            None => self.transition_image(
+                command_buffer,
+                &swapchain_image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                vk::ImageLayout::PRESENT_SRC_KHR,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                vk::AccessFlags::TRANSFER_WRITE,
+                vk::AccessFlags::NONE
+            ),
+        }
 
         self.transition_image(
             command_buffer,
-            &output_image.image,
+            &presented_image.image,
             vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
             vk::ImageLayout::GENERAL,
             vk::PipelineStageFlags::TRANSFER,
@@ -311,7 +774,49 @@ impl Renderer {
             vk::AccessFlags::NONE
         );
 
-        command_buffer.end();
+        command_buffer.end()
+    }
+
+    /// Maps window positions to pixels of an image of the given size, through the region it's presented in.
+    fn window_to_image(&self, width: u32, height: u32, extent: vk::Extent2D) -> impl Fn([f32; 2]) -> [f32; 2] {
+        let [min, max] = self.present_region(width, height, extent);
+        let scale_x = width as f32 / (max.x - min.x).max(1) as f32;
+        let scale_y = height as f32 / (max.y - min.y).max(1) as f32;
+        move |p| [(p[0] - min.x as f32) * scale_x, (p[1] - min.y as f32) * scale_y]
+    }
+
+    /// The region of the swapchain image the output image is blitted to, following the scaling mode.
+    fn present_region(&self, width: u32, height: u32, extent: vk::Extent2D) -> [Offset3D; 2] {
+        let scale_x = extent.width as f32 / width as f32;
+        let scale_y = extent.height as f32 / height as f32;
+        let fit_scale = scale_x.min(scale_y);
+
+        let (region_width, region_height) = match self.scaling {
+            ScalingMode::Stretch => (extent.width, extent.height),
+            ScalingMode::Integer if fit_scale >= 1.0 => {
+                let scale = fit_scale.floor() as u32;
+                (width * scale, height * scale)
+            },
+            // Integer scaling falls back to fitting when the image is larger than the window
+            ScalingMode::Fit | ScalingMode::Fixed(..) | ScalingMode::Integer => {
+                ((width as f32 * fit_scale) as u32, (height as f32 * fit_scale) as u32)
+            },
+        };
+
+        // Center the region, leaving black borders
+        let x = (extent.width - region_width.min(extent.width)) / 2;
+        let y = (extent.height - region_height.min(extent.height)) / 2;
+        [
+            Offset3D::default().x(x as i32).y(y as i32),
+            Offset3D::default().x((x + region_width) as i32).y((y + region_height) as i32).z(1)
+        ]
+    }
+
+    fn supports_linear_blit(&self, format: vk::Format) -> bool {
+        let properties = unsafe {
+            self.instance.handle().get_physical_device_format_properties(self.physical_device, format)
+        };
+        properties.optimal_tiling_features.contains(vk::FormatFeatureFlags::SAMPLED_IMAGE_FILTER_LINEAR)
     }
 
     pub fn transition_image(
@@ -336,7 +841,7 @@ impl Renderer {
             .subresource_range(vk::ImageSubresourceRange {
                 aspect_mask: ImageAspectFlags::COLOR,
                 base_mip_level: 0,
-                level_count: 1,
+                level_count: vk::REMAINING_MIP_LEVELS,
                 base_array_layer: 0,
                 layer_count: 1,
             });
@@ -354,38 +859,242 @@ impl Renderer {
     }
 
 
-    pub fn draw_frame(&mut self, draw_orchestrator: &mut DrawOrchestrator) {
+    /// Wait until the current frame's command buffer can be reused and acquire the swapchain image to present to.
+    /// Returns `None` when the swapchain is out of date.
+    fn acquire_frame(&mut self) -> Result<Option<usize>, Error> {
 
         // Wait for the current frame's command buffer to finish executing.
-        self.device.wait_for_fence(self.in_flight_fences[self.frame_index]);
+        self.device.wait_for_fence(self.in_flight_fences[self.frame_index])?;
 
-        let image_index = self.swapchain.acquire_next_image(self.image_available_semaphores[self.frame_index]) as usize;
-
-        self.record_command_buffer(self.frame_index, image_index, draw_orchestrator);
+        let swapchain = self.swapchain.as_ref().ok_or(Error::Headless)?;
+        match swapchain.acquire_next_image(self.image_available_semaphores[self.frame_index]) {
+            Ok((image_index, suboptimal)) => {
+                // A suboptimal swapchain can still be presented to, recreate it after this frame
+                self.swapchain_out_of_date |= suboptimal;
+                Ok(Some(image_index as usize))
+            },
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => {
+                self.swapchain_out_of_date = true;
+                Ok(None)
+            },
+            Err(e) => Err(Error::vulkan("Failed to acquire next image")(e)),
+        }
+    }
 
-        self.device.reset_fence(self.in_flight_fences[self.frame_index]);
+    /// Submit the current frame's command buffer and present the swapchain image it renders to.
+    fn present_frame(&mut self, image_index: usize) -> Result<(), Error> {
+        self.device.reset_fence(self.in_flight_fences[self.frame_index])?;
         self.device.submit_command_buffer(
             &self.queue,
             self.in_flight_fences[self.frame_index],
             self.image_available_semaphores[self.frame_index],
             self.render_finished_semaphores[self.frame_index],
             &self.command_buffers[self.frame_index]
+        )?;
+
+        let swapchain = self.swapchain.as_ref().ok_or(Error::Headless)?;
+        match swapchain.queue_present(self.queue, self.render_finished_semaphores[self.frame_index], image_index as u32) {
+            Ok(suboptimal) => self.swapchain_out_of_date |= suboptimal,
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => self.swapchain_out_of_date = true,
+            Err(e) => return Err(Error::vulkan("Failed to present queue")(e)),
+        }
+
+        self.frame_index = ( self.frame_index + 1 ) % self.command_buffers.len();
+        Ok(())
+    }
+
+    /// Render and present a frame, with the `overlay` drawn on top of the output image.
+    pub fn draw_frame(&mut self, draw_orchestrator: &mut DrawOrchestrator, input: &InputState, mut overlay: Option<&mut OverlayPainter>) -> Result<(), Error> {
+
+        let image_index = match self.acquire_frame()? {
+            Some(image_index) => image_index,
+            None => return Ok(()),
+        };
+
+        let current_time = self.clock.time();
+        let swapchain = self.swapchain.as_ref().ok_or(Error::Headless)?;
+
+        // Shaders receive positions in output image pixels, also while the inspector presents another image
+        let output_image = draw_orchestrator.output_image();
+        let extent = swapchain.get_extent();
+        let shader_input = input.shader_input(self.window_to_image(output_image.width, output_image.height, extent));
+
+        let (inspected_image, _) = self.inspector.inspected_image(draw_orchestrator);
+        let [x, y] = self.window_to_image(inspected_image.width, inspected_image.height, extent)(input.cursor());
+        let cursor = (x >= 0.0 && y >= 0.0).then_some([x as u32, y as u32]);
+        self.inspector.prepare(&self.device, &mut self.allocator, draw_orchestrator, self.frame_index, cursor)?;
+
+        if let Some(overlay) = overlay.as_deref_mut() {
+            overlay.prepare(&self.device, &mut self.allocator, self.frame_index, swapchain)?;
+        }
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        let uniforms = self.next_frame_uniforms(draw_orchestrator, current_time, date(now));
+        self.record_command_buffer(self.frame_index, image_index, draw_orchestrator, &uniforms, &shader_input, overlay.as_deref())?;
+        draw_orchestrator.end_frame();
+
+        self.present_frame(image_index)
+    }
+
+    /// Present a frame without running any passes, for when the shaders don't compile.
+    /// The window is cleared and the `overlay`, showing the error, is drawn on top.
+    pub fn draw_error_frame(&mut self, mut overlay: Option<&mut OverlayPainter>) -> Result<(), Error> {
+
+        let image_index = match self.acquire_frame()? {
+            Some(image_index) => image_index,
+            None => return Ok(()),
+        };
+
+        let swapchain = self.swapchain.as_ref().ok_or(Error::Headless)?;
+        if let Some(overlay) = overlay.as_deref_mut() {
+            overlay.prepare(&self.device, &mut self.allocator, self.frame_index, swapchain)?;
+        }
+
+        let command_buffer = &self.command_buffers[self.frame_index];
+        let swapchain_image = swapchain.get_images()[image_index];
+        command_buffer.begin()?;
+
+        self.transition_image(
+            command_buffer,
+            &swapchain_image,
+            vk::ImageLayout::PRESENT_SRC_KHR,
+            vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            vk::PipelineStageFlags::TOP_OF_PIPE,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::NONE,
+            vk::AccessFlags::TRANSFER_WRITE
         );
+        unsafe {
+            self.device.handle().cmd_clear_color_image(
+                command_buffer.handle(),
+                swapchain_image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &vk::ClearColorValue {
+                    float32: [0.0, 0.0, 0.0, 1.0]
+                },
+                &[vk::ImageSubresourceRange {
+                    aspect_mask: ImageAspectFlags::COLOR,
+                    base_mip_level: 0,
+                    level_count: 1,
+                    base_array_layer: 0,
+                    layer_count: 1,
+                }]
+            );
+        }
+        match overlay.as_deref() {
+            Some(overlay) => overlay.record(&self.device, command_buffer, self.frame_index, image_index),
+            None => self.transition_image(
+                command_buffer,
+                &swapchain_image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                vk::ImageLayout::PRESENT_SRC_KHR,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                vk::AccessFlags::TRANSFER_WRITE,
+                vk::AccessFlags::NONE
+            ),
+        }
 
-        self.swapchain.queue_present(
-            self.queue,
-            self.render_finished_semaphores[self.frame_index],
-            image_index as u32
+        command_buffer.end()?;
+
+        self.present_frame(image_index)
+    }
+
+    /// Render a frame at the given `time` and copy the output image into `target`.
+    /// Blocks until the frame has finished rendering, after which `target` can be read on the host.
+    pub fn draw_offscreen(&mut self, draw_orchestrator: &mut DrawOrchestrator, time: f32, target: &Buffer) -> Result<(), Error> {
+
+        // The date advances with the frame time, like the time, so the output is reproducible
+        let uniforms = self.next_frame_uniforms(draw_orchestrator, time, date(OFFSCREEN_START_DATE + Duration::from_secs_f32(time.max(0.0))));
+        let command_buffer = &self.command_buffers[0];
+
+        command_buffer.begin()?;
+
+        // There is no window to receive input from
+        self.record_passes(command_buffer, draw_orchestrator, &uniforms, &ShaderInput::default());
+
+        let output_image = draw_orchestrator.output_image();
+
+        self.transition_image(
+            command_buffer,
+            &output_image.image,
+            vk::ImageLayout::GENERAL,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::SHADER_WRITE,
+            vk::AccessFlags::TRANSFER_READ
+        );
+
+        unsafe {
+            self.device.handle().cmd_copy_image_to_buffer(
+                command_buffer.handle(),
+                output_image.image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                *target.handle(),
+                &[vk::BufferImageCopy::default()
+                    .image_subresource(
+                        ImageSubresourceLayers::default()
+                            .aspect_mask(ImageAspectFlags::COLOR)
+                            .base_array_layer(0)
+                            .layer_count(1)
+                            .mip_level(0)
+                    )
+                    .image_extent(vk::Extent3D {
+                        width: output_image.width,
+                        height: output_image.height,
+                        depth: 1,
+                    })
+                ]
+            );
+
+            // Make the copy visible to the host
+            self.device.handle().cmd_pipeline_barrier(
+                command_buffer.handle(),
+                vk::PipelineStageFlags::TRANSFER,
+                vk::PipelineStageFlags::HOST,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[vk::BufferMemoryBarrier::default()
+                    .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                    .dst_access_mask(vk::AccessFlags::HOST_READ)
+                    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .buffer(*target.handle())
+                    .offset(0)
+                    .size(vk::WHOLE_SIZE)
+                ],
+                &[]
+            );
+        }
+
+        self.transition_image(
+            command_buffer,
+            &output_image.image,
+            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+            vk::ImageLayout::GENERAL,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+            vk::AccessFlags::TRANSFER_READ,
+            vk::AccessFlags::NONE
         );
 
-        self.frame_index = ( self.frame_index + 1 ) % self.swapchain.get_image_views().len();
+        command_buffer.end()?;
+
+        self.device.submit_single_time_command(self.queue, command_buffer)?;
+
+        draw_orchestrator.end_frame();
+        Ok(())
     }
 }
 
 impl Drop for Renderer {
     fn drop(&mut self) {
         unsafe {
-            self.device.handle().device_wait_idle().unwrap();
+            // Destroying the semaphores and fences anyway is the best that can be done
+            if let Err(e) = self.device.handle().device_wait_idle() {
+                error!("Failed to wait for the device before destroying the renderer: {}", e);
+            }
             for semaphore in &self.render_finished_semaphores {
                 self.device.handle().destroy_semaphore(*semaphore, None);
             }
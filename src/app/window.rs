@@ -3,7 +3,9 @@ use winit::event::WindowEvent;
 use winit::event::{ElementState, KeyEvent};
 use winit::event_loop::{EventLoop, EventLoopWindowTarget};
 use winit::keyboard::{Key, NamedKey};
+use winit::window::Fullscreen;
 use winit::raw_window_handle::{HasDisplayHandle, HasWindowHandle, RawDisplayHandle, RawWindowHandle};
+use crate::Error;
 
 /// System window wrapper.
 /// Handles window events i.e. close, redraw, keyboard input.
@@ -12,17 +14,17 @@ pub struct Window {
 }
 
 impl Window {
-    pub fn create(event_loop: &EventLoop<()>, window_title: &str, width: u32, height: u32) -> Window {
+    pub fn create(event_loop: &EventLoop<()>, window_title: &str, width: u32, height: u32) -> Result<Window, Error> {
         let window = winit::window::WindowBuilder::new()
             .with_title(window_title)
-            .with_resizable(false)
+            .with_resizable(true)
             .with_inner_size(winit::dpi::LogicalSize::new(width, height))
             .build(event_loop)
-            .expect("Failed to create window.");
+            .map_err(|e| Error::Window(format!("Failed to create window: {}", e)))?;
 
-        Window {
+        Ok(Window {
             window,
-        }
+        })
     }
 
     pub fn window_handle(&self) -> RawWindowHandle {
@@ -33,12 +35,24 @@ impl Window {
         self.window.display_handle().unwrap().as_raw()
     }
 
+    pub fn winit_window(&self) -> &winit::window::Window {
+        &self.window
+    }
+
     pub fn get_extent(&self) -> Extent2D {
         let width = self.window.inner_size().width;
         let height = self.window.inner_size().height;
         Extent2D{ width, height }
     }
 
+    /// Switch between windowed and borderless fullscreen on the current monitor.
+    pub fn toggle_fullscreen(&self) {
+        match self.window.fullscreen() {
+            Some(_) => self.window.set_fullscreen(None),
+            None => self.window.set_fullscreen(Some(Fullscreen::Borderless(None))),
+        }
+    }
+
     pub fn window_event(&mut self, event: WindowEvent, elwt: &EventLoopWindowTarget<()>) {
         match event {
             WindowEvent::CloseRequested => {
@@ -59,6 +73,9 @@ impl Window {
                 Key::Character("q") => {
                     elwt.exit();
                 }
+                Key::Named(NamedKey::F11) => {
+                    self.toggle_fullscreen();
+                }
                 _ => {}
             },
             _ => {}
@@ -0,0 +1,83 @@
+use std::{fmt, io};
+use std::path::PathBuf;
+use ash::vk;
+use crate::vulkan::PipelineErr;
+
+/// Errors returned by kiyo, from setting up Vulkan to compiling shaders and presenting frames.
+#[derive(Debug)]
+pub enum Error {
+    /// Loading Vulkan or creating the instance failed.
+    Instance( String ),
+    /// No device supports the required features, or creating the logical device failed.
+    Device( String ),
+    /// A Vulkan call failed, with a description of what was attempted.
+    Vulkan( String, vk::Result ),
+    /// Allocating device memory failed.
+    Allocation( gpu_allocator::AllocationError ),
+    /// A file couldn't be read or written.
+    Io( PathBuf, io::Error ),
+    /// A shader failed to compile or doesn't match the configuration of its pass.
+    Pipeline( PipelineErr ),
+    /// The window's surface was lost, it can't be presented to anymore.
+    SurfaceLost,
+    /// A headless renderer was asked to present to a window, it has no surface or swapchain.
+    Headless,
+    /// Creating the window or running its event loop failed.
+    Window( String ),
+    /// Opening the audio output stream failed.
+    Audio( String ),
+    /// A project file isn't valid TOML or doesn't describe a project, the message points at the offending key.
+    Project( PathBuf, String ),
+}
+
+impl Error {
+    /// Maps a failed Vulkan call to an error, `action` describes what was attempted.
+    /// A lost surface is reported as `Error::SurfaceLost`.
+    pub(crate) fn vulkan(action: &str) -> impl FnOnce(vk::Result) -> Error + '_ {
+        move |result| match result {
+            vk::Result::ERROR_SURFACE_LOST_KHR => Error::SurfaceLost,
+            _ => Error::Vulkan(action.to_string(), result),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Instance(err) => write!(f, "Failed to create Vulkan instance: {}", err),
+            Error::Device(err) => write!(f, "Failed to create device: {}", err),
+            Error::Vulkan(action, result) => write!(f, "{}: {}", action, result),
+            Error::Allocation(err) => write!(f, "Failed to allocate memory: {}", err),
+            Error::Io(path, err) => write!(f, "{}: {}", path.display(), err),
+            Error::Pipeline(err) => write!(f, "{}", err),
+            Error::SurfaceLost => write!(f, "The window surface was lost"),
+            Error::Headless => write!(f, "The renderer is headless, it has no window to present to"),
+            Error::Window(err) => write!(f, "Window error: {}", err),
+            Error::Audio(err) => write!(f, "Audio error: {}", err),
+            Error::Project(path, err) => write!(f, "{}: {}", path.display(), err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Vulkan(_, result) => Some(result),
+            Error::Allocation(err) => Some(err),
+            Error::Io(_, err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<PipelineErr> for Error {
+    fn from(err: PipelineErr) -> Self {
+        Error::Pipeline(err)
+    }
+}
+
+impl From<gpu_allocator::AllocationError> for Error {
+    fn from(err: gpu_allocator::AllocationError) -> Self {
+        Error::Allocation(err)
+    }
+}
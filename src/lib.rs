@@ -1,4 +1,9 @@
 extern crate shaderc;
 
 pub mod vulkan;
-pub mod app;
\ No newline at end of file
+pub mod app;
+mod error;
+#[cfg(test)]
+mod test_dir;
+
+pub use self::error::Error;
@@ -0,0 +1,10 @@
+use kiyo::app::App;
+
+/// Runs a project file, `kiyo.toml` in the working directory when no path is given.
+fn main() {
+    let path = std::env::args().nth(1).unwrap_or_else(|| "kiyo.toml".to_string());
+    if let Err(e) = App::run_project(path) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,23 @@
+use std::fs;
+use std::path::PathBuf;
+
+/// A temporary directory of its own for a test, removed when the test ends.
+pub struct TestDir {
+    pub path: PathBuf,
+}
+
+impl TestDir {
+    /// Create an empty directory, `name` has to be unique among the tests.
+    pub fn new(name: &str) -> TestDir {
+        let path = std::env::temp_dir().join(format!("kiyo-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TestDir { path }
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
@@ -1,5 +1,6 @@
 use std::sync::{Arc, Mutex, MutexGuard};
 use gpu_allocator::vulkan::AllocatorCreateDesc;
+use crate::Error;
 
 pub struct AllocatorInner {
     pub allocator: gpu_allocator::vulkan::Allocator,
@@ -15,10 +16,10 @@ pub struct Allocator {
 }
 
 impl Allocator {
-    pub fn new(desc: &AllocatorCreateDesc) -> Self {
-        Self {
-            inner: Arc::new( Mutex::new(AllocatorInner { allocator: gpu_allocator::vulkan::Allocator::new(desc).expect("Failed to create allocator") } ) ),
-        }
+    pub fn new(desc: &AllocatorCreateDesc) -> Result<Self, Error> {
+        Ok(Self {
+            inner: Arc::new( Mutex::new(AllocatorInner { allocator: gpu_allocator::vulkan::Allocator::new(desc)? } ) ),
+        })
     }
 
     pub fn handle(&self) -> MutexGuard<'_, AllocatorInner> {
@@ -0,0 +1,96 @@
+use std::sync::{Arc, Mutex};
+use ash::vk;
+use gpu_allocator::MemoryLocation;
+use gpu_allocator::vulkan::{Allocation, AllocationScheme};
+use crate::Error;
+use crate::vulkan::{Allocator, Device};
+use crate::vulkan::allocator::AllocatorInner;
+use crate::vulkan::device::DeviceInner;
+
+pub struct Buffer {
+    pub device_dep: Arc<DeviceInner>,
+    pub allocator_dep: Arc<Mutex<AllocatorInner>>,
+    pub(crate) buffer: vk::Buffer,
+    pub size: u64,
+    pub allocation: Option<Allocation>,
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        unsafe {
+            if let Some(allocation) = self.allocation.take() {
+                self.allocator_dep.lock().unwrap().allocator.free(allocation).unwrap();
+            }
+            self.device_dep.device.destroy_buffer(self.buffer, None);
+        }
+    }
+}
+
+impl Buffer {
+    /// Create a buffer of `size` bytes.
+    /// Use `MemoryLocation::GpuToCpu` or `MemoryLocation::CpuToGpu` for buffers which need to be mapped.
+    pub fn new(device: &Device, allocator: &mut Allocator, size: u64, buffer_usage_flags: vk::BufferUsageFlags, location: MemoryLocation) -> Result<Buffer, Error> {
+
+        let create_info = vk::BufferCreateInfo::default()
+            .size(size)
+            .usage(buffer_usage_flags)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE);
+
+        let buffer = unsafe {
+            device.handle().create_buffer(&create_info, None)
+                .map_err(Error::vulkan("Failed to create buffer"))?
+        };
+
+        // Allocate memory
+        let requirements = unsafe { device.handle().get_buffer_memory_requirements(buffer) };
+        let allocation = allocator.handle().allocator
+            .allocate(&gpu_allocator::vulkan::AllocationCreateDesc {
+                name: "Buffer",
+                requirements,
+                location,
+                linear: true,
+                allocation_scheme: AllocationScheme::GpuAllocatorManaged,
+            });
+
+        // From here on the buffer is released by `drop` when a step fails
+        let mut buffer = Buffer {
+            buffer,
+            size,
+            allocation: None,
+            device_dep: device.inner.clone(),
+            allocator_dep: allocator.inner.clone(),
+        };
+        let allocation = buffer.allocation.insert(allocation?);
+
+        unsafe {
+            device.handle().bind_buffer_memory(buffer.buffer, allocation.memory(), allocation.offset())
+                .map_err(Error::vulkan("Failed to bind buffer memory"))?
+        }
+
+        Ok(buffer)
+    }
+
+    /// The host visible memory of the buffer, an error if the buffer lives in gpu only memory.
+    pub fn mapped_slice(&self) -> Result<&[u8], Error> {
+        self.allocation.as_ref()
+            .and_then(|allocation| allocation.mapped_slice())
+            .map(|slice| &slice[..self.size as usize])
+            .ok_or_else(Self::not_mapped)
+    }
+
+    pub fn mapped_slice_mut(&mut self) -> Result<&mut [u8], Error> {
+        let size = self.size as usize;
+        self.allocation.as_mut()
+            .and_then(|allocation| allocation.mapped_slice_mut())
+            .map(|slice| &mut slice[..size])
+            .ok_or_else(Self::not_mapped)
+    }
+
+    fn not_mapped() -> Error {
+        Error::Vulkan("Buffer isn't host visible".to_string(), vk::Result::ERROR_MEMORY_MAP_FAILED)
+    }
+
+    pub fn handle(&self) -> &vk::Buffer {
+        &self.buffer
+    }
+}
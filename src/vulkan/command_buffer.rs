@@ -1,7 +1,8 @@
 use std::sync::Arc;
 use ash::vk;
 use ash::vk::WriteDescriptorSet;
-use crate::vulkan::{CommandPool, Device, Framebuffer, Image, Pipeline, RenderPass};
+use crate::vulkan::{Buffer, CommandPool, Device, Framebuffer, Image, Pipeline, RenderPass};
+use crate::Error;
 use crate::vulkan::device::DeviceInner;
 
 pub struct CommandBuffer {
@@ -10,7 +11,7 @@ pub struct CommandBuffer {
 }
 
 impl CommandBuffer {
-    pub fn new(device: &Device, command_pool: &CommandPool) -> CommandBuffer {
+    pub fn new(device: &Device, command_pool: &CommandPool) -> Result<CommandBuffer, Error> {
         let command_buffer_allocate_info = vk::CommandBufferAllocateInfo::default()
             .command_pool(command_pool.handle())
             .level(vk::CommandBufferLevel::PRIMARY)
@@ -20,29 +21,29 @@ impl CommandBuffer {
             device.handle()
                 .allocate_command_buffers(&command_buffer_allocate_info)
                 .map(|command_buffers| command_buffers[0])
-                .expect("Failed to allocate command buffers")
+                .map_err(Error::vulkan("Failed to allocate command buffers"))?
         };
 
-        CommandBuffer {
+        Ok(CommandBuffer {
             device_dep: device.inner.clone(),
             command_buffer
-        }
+        })
     }
 
-    pub fn begin(&self) {
+    pub fn begin(&self) -> Result<(), Error> {
         let command_buffer_begin_info = vk::CommandBufferBeginInfo::default();
         unsafe {
             self.device_dep.device
                 .begin_command_buffer(self.command_buffer, &command_buffer_begin_info)
-                .expect("Failed to begin command buffer");
+                .map_err(Error::vulkan("Failed to begin command buffer"))
         }
     }
 
-    pub fn end(&self) {
+    pub fn end(&self) -> Result<(), Error> {
         unsafe {
             self.device_dep.device
                 .end_command_buffer(self.command_buffer)
-                .expect("Failed to end command buffer");
+                .map_err(Error::vulkan("Failed to end command buffer"))
         }
     }
 
@@ -65,20 +66,69 @@ impl CommandBuffer {
         }
     }
 
-    pub fn bind_push_descriptor_images(&self, pipeline: &dyn Pipeline, images: &Vec<Image>) {
+    /// Bind the first mip level of every image, followed by the remaining mip levels of every image.
+    pub fn bind_push_descriptor_images(&self, pipeline: &dyn Pipeline, images: &[&Image]) {
+        self.bind_push_descriptor_resources(pipeline, images, &[], &[]);
+    }
+
+    /// Bind storage images at binding 0, see `bind_push_descriptor_images`, storage buffers at binding 1
+    /// and each uniform buffer at its own binding.
+    pub fn bind_push_descriptor_resources(&self, pipeline: &dyn Pipeline, images: &[&Image], buffers: &[&Buffer], uniform_buffers: &[(u32, &Buffer)]) {
 
-        let bindings = images.iter().map(|image| {
+        let base_bindings = images.iter().map(|image| {
             vk::DescriptorImageInfo::default()
                 .image_layout(vk::ImageLayout::GENERAL)
                 .image_view(image.image_view)
                 .sampler(image.sampler)
-        }).collect::<Vec<vk::DescriptorImageInfo>>();
+        });
+        let mip_bindings = images.iter().flat_map(|image| {
+            image.mip_views.iter().map(|&mip_view| {
+                vk::DescriptorImageInfo::default()
+                    .image_layout(vk::ImageLayout::GENERAL)
+                    .image_view(mip_view)
+                    .sampler(image.sampler)
+            })
+        });
+        let image_bindings = base_bindings.chain(mip_bindings).collect::<Vec<vk::DescriptorImageInfo>>();
 
-        let write_descriptor_set = WriteDescriptorSet::default()
-            .dst_binding(0)
-            .dst_array_element(0)
-            .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
-            .image_info(&bindings);
+        let buffer_bindings = buffers.iter().map(|buffer| {
+            vk::DescriptorBufferInfo::default()
+                .buffer(*buffer.handle())
+                .offset(0)
+                .range(vk::WHOLE_SIZE)
+        }).collect::<Vec<vk::DescriptorBufferInfo>>();
+        let uniform_bindings = uniform_buffers.iter().map(|(_, buffer)| {
+            [vk::DescriptorBufferInfo::default()
+                .buffer(*buffer.handle())
+                .offset(0)
+                .range(vk::WHOLE_SIZE)]
+        }).collect::<Vec<[vk::DescriptorBufferInfo; 1]>>();
+
+        let mut write_descriptor_sets = vec![
+            WriteDescriptorSet::default()
+                .dst_binding(0)
+                .dst_array_element(0)
+                .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                .image_info(&image_bindings)
+        ];
+        if !buffer_bindings.is_empty() {
+            write_descriptor_sets.push(
+                WriteDescriptorSet::default()
+                    .dst_binding(1)
+                    .dst_array_element(0)
+                    .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+                    .buffer_info(&buffer_bindings)
+            );
+        }
+        for ((binding, _), buffer_info) in uniform_buffers.iter().zip(&uniform_bindings) {
+            write_descriptor_sets.push(
+                WriteDescriptorSet::default()
+                    .dst_binding(*binding)
+                    .dst_array_element(0)
+                    .descriptor_type(vk::DescriptorType::UNIFORM_BUFFER)
+                    .buffer_info(buffer_info)
+            );
+        }
 
         unsafe {
             self.device_dep.device_push_descriptor.cmd_push_descriptor_set(
@@ -86,7 +136,7 @@ impl CommandBuffer {
                 pipeline.bind_point(),
                 pipeline.layout(),
                 0,
-                &[write_descriptor_set]
+                &write_descriptor_sets
             );
         }
     }
@@ -116,6 +166,34 @@ impl CommandBuffer {
         }
     }
 
+    /// Bind the first mip level of each image as a storage image at its own binding, starting at binding 0.
+    pub fn bind_push_descriptor_storage_images(&self, pipeline: &dyn Pipeline, images: &[&Image]) {
+        let image_infos = images.iter().map(|image| {
+            [vk::DescriptorImageInfo::default()
+                .image_layout(vk::ImageLayout::GENERAL)
+                .image_view(image.image_view)
+                .sampler(image.sampler)]
+        }).collect::<Vec<[vk::DescriptorImageInfo; 1]>>();
+
+        let write_descriptor_sets = image_infos.iter().enumerate().map(|(binding, image_info)| {
+            WriteDescriptorSet::default()
+                .dst_binding(binding as u32)
+                .dst_array_element(0)
+                .descriptor_type(vk::DescriptorType::STORAGE_IMAGE)
+                .image_info(image_info)
+        }).collect::<Vec<WriteDescriptorSet>>();
+
+        unsafe {
+            self.device_dep.device_push_descriptor.cmd_push_descriptor_set(
+                self.command_buffer,
+                pipeline.bind_point(),
+                pipeline.layout(),
+                0,
+                &write_descriptor_sets
+            );
+        }
+    }
+
     pub fn bind_push_descriptor(&self, pipeline: &dyn Pipeline, set: u32, write_descriptor_set: WriteDescriptorSet) {
         unsafe {
             self.device_dep.device_push_descriptor.cmd_push_descriptor_set(
@@ -128,6 +206,22 @@ impl CommandBuffer {
         }
     }
 
+    /// Bind a sampled image at binding 0, for graphics pipelines.
+    pub fn bind_push_descriptor_sampled_image(&self, pipeline: &dyn Pipeline, image: &Image, sampler: vk::Sampler) {
+        let bindings = [vk::DescriptorImageInfo::default()
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .image_view(image.image_view)
+            .sampler(sampler)];
+
+        let write_descriptor_set = WriteDescriptorSet::default()
+            .dst_binding(0)
+            .dst_array_element(0)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(&bindings);
+
+        self.bind_push_descriptor(pipeline, 0, write_descriptor_set);
+    }
+
     pub fn end_render_pass(&self) {
         unsafe {
             self.device_dep.device
@@ -184,6 +278,27 @@ impl CommandBuffer {
         }
     }
 
+    pub fn bind_vertex_buffer(&self, buffer: &Buffer) {
+        unsafe {
+            self.device_dep.device
+                .cmd_bind_vertex_buffers(self.command_buffer, 0, &[buffer.buffer], &[0]);
+        }
+    }
+
+    pub fn bind_index_buffer(&self, buffer: &Buffer, index_type: vk::IndexType) {
+        unsafe {
+            self.device_dep.device
+                .cmd_bind_index_buffer(self.command_buffer, buffer.buffer, 0, index_type);
+        }
+    }
+
+    pub fn draw_indexed(&self, index_count: u32, first_index: u32, vertex_offset: i32) {
+        unsafe {
+            self.device_dep.device
+                .cmd_draw_indexed(self.command_buffer, index_count, 1, first_index, vertex_offset, 0);
+        }
+    }
+
     pub fn dispatch(&self, x: u32, y: u32, z: u32) {
         unsafe {
             self.device_dep.device
@@ -191,6 +306,13 @@ impl CommandBuffer {
         }
     }
 
+    pub fn dispatch_indirect(&self, buffer: &Buffer, offset: u64) {
+        unsafe {
+            self.device_dep.device
+                .cmd_dispatch_indirect(self.command_buffer, buffer.buffer, offset);
+        }
+    }
+
     pub fn image_barrier(
         &self,
         src_stage_mask: vk::PipelineStageFlags,
@@ -228,6 +350,58 @@ impl CommandBuffer {
         }
     }
 
+    /// Insert a single pipeline barrier covering multiple images in the `GENERAL` layout and buffers.
+    pub fn memory_barriers(
+        &self,
+        src_stage_mask: vk::PipelineStageFlags,
+        dst_stage_mask: vk::PipelineStageFlags,
+        src_access_mask: vk::AccessFlags,
+        dst_access_mask: vk::AccessFlags,
+        images: &[&Image],
+        buffers: &[&Buffer]
+    ) {
+        let image_memory_barriers = images.iter().map(|image| {
+            vk::ImageMemoryBarrier::default()
+                .subresource_range(vk::ImageSubresourceRange::default()
+                    .aspect_mask(vk::ImageAspectFlags::COLOR)
+                    .base_array_layer(0)
+                    .base_mip_level(0)
+                    .layer_count(1)
+                    .level_count(vk::REMAINING_MIP_LEVELS))
+                .old_layout(vk::ImageLayout::GENERAL)
+                .new_layout(vk::ImageLayout::GENERAL)
+                .src_access_mask(src_access_mask)
+                .dst_access_mask(dst_access_mask)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .image(*image.handle())
+        }).collect::<Vec<vk::ImageMemoryBarrier>>();
+
+        let buffer_memory_barriers = buffers.iter().map(|buffer| {
+            vk::BufferMemoryBarrier::default()
+                .src_access_mask(src_access_mask)
+                .dst_access_mask(dst_access_mask)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .buffer(*buffer.handle())
+                .offset(0)
+                .size(vk::WHOLE_SIZE)
+        }).collect::<Vec<vk::BufferMemoryBarrier>>();
+
+        unsafe {
+            self.device_dep.device
+                .cmd_pipeline_barrier(
+                    self.command_buffer,
+                    src_stage_mask,
+                    dst_stage_mask,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &buffer_memory_barriers,
+                    &image_memory_barriers
+                );
+        }
+    }
+
     pub fn bind_descriptor_sets(&self, pipeline: &dyn Pipeline, descriptor_sets: &[vk::DescriptorSet]) {
         unsafe {
             self.device_dep.device
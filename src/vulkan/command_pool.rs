@@ -1,5 +1,6 @@
 use std::sync::Arc;
 use ash::vk;
+use crate::Error;
 use crate::vulkan::Device;
 use crate::vulkan::device::DeviceInner;
 
@@ -10,7 +11,7 @@ pub struct CommandPool {
 
 impl CommandPool {
 
-    pub fn new(device: &Device, queue_family_index: u32) -> CommandPool {
+    pub fn new(device: &Device, queue_family_index: u32) -> Result<CommandPool, Error> {
 
         let command_pool_create_info = vk::CommandPoolCreateInfo::default()
             .queue_family_index(queue_family_index)
@@ -19,13 +20,13 @@ impl CommandPool {
         let command_pool = unsafe {
             device.handle()
                 .create_command_pool(&command_pool_create_info, None)
-                .expect("Failed to create command pool")
+                .map_err(Error::vulkan("Failed to create command pool"))?
         };
 
-        Self {
+        Ok(Self {
             device_dep: device.inner.clone(),
             command_pool
-        }
+        })
     }
 
     pub fn handle(&self) -> vk::CommandPool {
@@ -1,11 +1,14 @@
 use std::collections::HashMap;
 use std::ffi::CString;
+use std::path::PathBuf;
 use std::sync::Arc;
 use ash::vk;
 use ash::vk::PushConstantRange;
-use crate::vulkan::{DescriptorSetLayout, Device, Pipeline};
+use crate::vulkan::{DescriptorSetLayout, Device, Diagnostic, Pipeline, ShaderCode};
 use crate::vulkan::device::DeviceInner;
 use crate::vulkan::pipeline::{create_shader_module, load_shader_code, PipelineErr};
+use crate::vulkan::spirv::{reflect_descriptor_bindings, reflect_entry_points, reflect_local_size, reflect_push_constant_size, EXECUTION_MODEL_GL_COMPUTE};
+use crate::Error;
 
 pub struct ComputePipelineInner {
     pub pipeline_layout: vk::PipelineLayout,
@@ -23,7 +26,10 @@ impl Drop for ComputePipelineInner {
 }
 
 pub struct ComputePipeline {
-    inner: Arc<ComputePipelineInner>
+    inner: Arc<ComputePipelineInner>,
+    local_size: [u32; 3],
+    includes: Vec<PathBuf>,
+    warnings: Vec<Diagnostic>,
 }
 
 impl Pipeline for ComputePipeline {
@@ -45,15 +51,56 @@ impl ComputePipeline {
 pub fn new(
     device: &Device,
     shader_source: String,
+    entry_point: &str,
     layouts: &[&DescriptorSetLayout],
     push_constant_ranges: &[PushConstantRange],
-    macros: &HashMap<&str, &dyn ToString>
-) -> Result<Self, PipelineErr> {
+    macros: &HashMap<&str, &dyn ToString>,
+    include_dirs: &[PathBuf]
+) -> Result<Self, Error> {
 
-        let shader_code = load_shader_code(shader_source, macros)?;
-        let shader_module = create_shader_module(device.handle(), shader_code.to_vec());
+        let shader = load_shader_code(shader_source.clone(), shaderc::ShaderKind::Compute, entry_point, macros, include_dirs, None)?;
+        Self::from_shader(device, &shader_source, shader, layouts, push_constant_ranges)
+    }
+
+    /// Create a pipeline from a shader loaded with `load_shader_code`, `shader_source` is used in error messages.
+    pub fn from_shader(
+        device: &Device,
+        shader_source: &str,
+        shader: ShaderCode,
+        layouts: &[&DescriptorSetLayout],
+        push_constant_ranges: &[PushConstantRange]
+    ) -> Result<Self, Error> {
+        let mut pipeline = Self::from_code(device, shader_source, &shader.code, &shader.entry_point, layouts, push_constant_ranges)?;
+        pipeline.includes = shader.includes;
+        pipeline.warnings = shader.warnings;
+        Ok(pipeline)
+    }
+
+    /// Create a pipeline from compiled SPIR-V code starting at `entry_point`, `shader_source` is used in error messages.
+    pub fn from_code(
+        device: &Device,
+        shader_source: &str,
+        shader_code: &[u32],
+        entry_point: &str,
+        layouts: &[&DescriptorSetLayout],
+        push_constant_ranges: &[PushConstantRange]
+    ) -> Result<Self, Error> {
+
+        let entry_points = reflect_entry_points(shader_code, EXECUTION_MODEL_GL_COMPUTE)
+            .map_err(|e| PipelineErr::Validation(format!("{}: {}", shader_source, e)))?;
+        if !entry_points.iter().any(|name| name == entry_point) {
+            return Err(PipelineErr::Validation(format!(
+                "{}: no compute entry point named `{}`, the shader declares {:?}", shader_source, entry_point, entry_points
+            )).into());
+        }
+
+        let local_size = reflect_local_size(shader_code, entry_point)
+            .map_err(|e| PipelineErr::Validation(format!("{}: {}", shader_source, e)))?;
+        Self::validate_local_size(device, shader_source, local_size)?;
+        Self::validate_interface(shader_source, shader_code, entry_point, layouts, push_constant_ranges)?;
+        let shader_module = create_shader_module(device.handle(), shader_code.to_vec())?;
 
-        let binding = CString::new("main").unwrap();
+        let binding = CString::new(entry_point).unwrap();
         let shader_stages = [
             vk::PipelineShaderStageCreateInfo::default()
                 .stage(vk::ShaderStageFlags::COMPUTE)
@@ -67,10 +114,13 @@ pub fn new(
         let create_info = vk::PipelineLayoutCreateInfo::default()
             .set_layouts(&*desc_layouts)
             .push_constant_ranges(&push_constant_ranges);
-        let pipeline_layout = unsafe {
-            device.handle()
-                .create_pipeline_layout(&create_info, None)
-                .expect("Failed to create pipeline layout")
+        let pipeline_layout = unsafe { device.handle().create_pipeline_layout(&create_info, None) };
+        let pipeline_layout = match pipeline_layout {
+            Ok(pipeline_layout) => pipeline_layout,
+            Err(e) => {
+                unsafe { device.handle().destroy_shader_module(shader_module, None); }
+                return Err(Error::vulkan("Failed to create pipeline layout")(e));
+            }
         };
 
         // pipeline
@@ -78,14 +128,21 @@ pub fn new(
             .stage(shader_stages[0])
             .layout(pipeline_layout);
 
-        let compute_pipeline = unsafe {
+        let compute_pipelines = unsafe {
             device.handle()
-                .create_compute_pipelines(vk::PipelineCache::null(), &[compute_pipeline_create_info], None)
-                .expect("Failed to create graphics pipeline")[0]
+                .create_compute_pipelines(device.pipeline_cache(), &[compute_pipeline_create_info], None)
         };
 
         unsafe { device.handle().destroy_shader_module(shader_module, None); }
 
+        let compute_pipeline = match compute_pipelines {
+            Ok(pipelines) => pipelines[0],
+            Err((_, e)) => {
+                unsafe { device.handle().destroy_pipeline_layout(pipeline_layout, None); }
+                return Err(Error::vulkan("Failed to create compute pipeline")(e));
+            }
+        };
+
         let pipeline_inner = ComputePipelineInner {
             pipeline_layout,
             compute_pipeline,
@@ -93,7 +150,146 @@ pub fn new(
         };
 
         Ok(Self {
-            inner: Arc::new(pipeline_inner)
+            inner: Arc::new(pipeline_inner),
+            local_size,
+            includes: Vec::new(),
+            warnings: Vec::new(),
         })
     }
+
+    /// The local workgroup size declared by the shader.
+    pub fn local_size(&self) -> [u32; 3] {
+        self.local_size
+    }
+
+    /// Files included by the shader, empty for pipelines created from code.
+    pub fn includes(&self) -> &[PathBuf] {
+        &self.includes
+    }
+
+    /// Warnings of the shader compiler, empty for pipelines created from code.
+    pub fn warnings(&self) -> &[Diagnostic] {
+        &self.warnings
+    }
+
+    /// Check that the descriptors and push constants `entry_point` uses are provided by the layouts and push constant ranges.
+    fn validate_interface(
+        shader_source: &str,
+        shader_code: &[u32],
+        entry_point: &str,
+        layouts: &[&DescriptorSetLayout],
+        push_constant_ranges: &[PushConstantRange]
+    ) -> Result<(), PipelineErr> {
+        let reflection_error = |e: String| PipelineErr::Validation(format!("{}: {}", shader_source, e));
+        let name = |name: &str| if name.is_empty() { "A descriptor".to_string() } else { format!("`{}`", name) };
+
+        for binding in reflect_descriptor_bindings(shader_code, entry_point).map_err(reflection_error)? {
+            let provided = layouts.get(binding.set as usize).and_then(|layout| layout.binding(binding.binding));
+            let (descriptor_type, count) = match provided {
+                Some(provided) => provided,
+                None => return Err(PipelineErr::Validation(format!(
+                    "{}: {} is bound to set {}, binding {}, which the engine doesn't provide",
+                    shader_source, name(&binding.name), binding.set, binding.binding
+                ))),
+            };
+            if binding.descriptor_type != descriptor_type {
+                return Err(PipelineErr::Validation(format!(
+                    "{}: {} at binding {} is declared as {:?}, the engine binds {:?}",
+                    shader_source, name(&binding.name), binding.binding, binding.descriptor_type, descriptor_type
+                )));
+            }
+            if let Some(declared) = binding.count.filter(|&c| c > count) {
+                return Err(PipelineErr::Validation(format!(
+                    "{}: {} at binding {} declares {} descriptors, the engine provides {}",
+                    shader_source, name(&binding.name), binding.binding, declared, count
+                )));
+            }
+        }
+
+        let provided = push_constant_ranges.iter()
+            .filter(|r| r.stage_flags.contains(vk::ShaderStageFlags::COMPUTE))
+            .map(|r| r.offset + r.size)
+            .max()
+            .unwrap_or(0);
+        if let Some(size) = reflect_push_constant_size(shader_code, entry_point).map_err(reflection_error)?.filter(|&size| size > provided) {
+            return Err(PipelineErr::Validation(format!(
+                "{}: declares push constants of {} bytes, the engine provides {}",
+                shader_source, size, provided
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn validate_local_size(device: &Device, shader_source: &str, local_size: [u32; 3]) -> Result<(), PipelineErr> {
+        let limits = device.limits();
+        let invocations = local_size.iter().map(|&s| s as u64).product::<u64>();
+
+        if local_size.contains(&0) {
+            return Err(PipelineErr::Validation(format!(
+                "{}: local size {:?} is empty",
+                shader_source, local_size
+            )));
+        }
+        for (axis, (size, max)) in ["x", "y", "z"].iter().zip(local_size.iter().zip(limits.max_compute_work_group_size)) {
+            if *size > max {
+                return Err(PipelineErr::Validation(format!(
+                    "{}: local_size_{} of {} exceeds the device limit of {}",
+                    shader_source, axis, size, max
+                )));
+            }
+        }
+        if invocations > limits.max_compute_work_group_invocations as u64 {
+            return Err(PipelineErr::Validation(format!(
+                "{}: local size {:?} has {} invocations, the device supports at most {}",
+                shader_source, local_size, invocations, limits.max_compute_work_group_invocations
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vulkan::pipeline::compile_shader_source;
+
+    fn validate(source: &str, push_constant_size: u32) -> Result<(), PipelineErr> {
+        let code = compile_shader_source(source, shaderc::ShaderKind::Compute, "colors.comp", &HashMap::new()).unwrap();
+        let ranges = [PushConstantRange::default().stage_flags(vk::ShaderStageFlags::COMPUTE).size(push_constant_size)];
+        ComputePipeline::validate_interface("colors.comp", &code, "main", &[], &ranges)
+    }
+
+    #[test]
+    fn validates_push_constant_size() {
+        let source = "#version 450
+            layout( local_size_x = 64 ) in;
+            layout( push_constant ) uniform PushConstants { vec4 color; float scale; } push;
+            shared vec4 color;
+            void main()
+            {
+                color = push.color * push.scale;
+            }";
+        assert!(validate(source, 20).is_ok());
+        assert_eq!(
+            validate(source, 16).err().unwrap().to_string(),
+            "colors.comp: declares push constants of 20 bytes, the engine provides 16"
+        );
+    }
+
+    #[test]
+    fn rejects_missing_descriptors() {
+        let source = "#version 450
+            layout( local_size_x = 64 ) in;
+            layout( binding = 0 ) buffer Colors { vec4 colors[]; };
+            void main()
+            {
+                colors[gl_GlobalInvocationID.x] = vec4( 1.0 );
+            }";
+        assert_eq!(
+            validate(source, 0).err().unwrap().to_string(),
+            "colors.comp: `Colors` is bound to set 0, binding 0, which the engine doesn't provide"
+        );
+    }
 }
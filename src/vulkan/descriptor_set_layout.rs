@@ -1,12 +1,15 @@
 use std::sync::Arc;
 use ash::vk;
 use ash::vk::DescriptorSetLayoutBinding;
+use crate::Error;
 use crate::vulkan::Device;
 use crate::vulkan::device::DeviceInner;
 
 pub struct DescriptorSetLayout {
     device_dep: Arc<DeviceInner>,
     layout: vk::DescriptorSetLayout,
+    /// Type and amount of descriptors of every binding, to validate the shaders using the layout.
+    bindings: Vec<(u32, vk::DescriptorType, u32)>,
 }
 
 impl Drop for DescriptorSetLayout {
@@ -19,7 +22,7 @@ impl Drop for DescriptorSetLayout {
 
 impl DescriptorSetLayout {
 
-    fn create(device: &Device, flags: vk::DescriptorSetLayoutCreateFlags, layout_bindings: &[DescriptorSetLayoutBinding]) -> DescriptorSetLayout {
+    fn create(device: &Device, flags: vk::DescriptorSetLayoutCreateFlags, layout_bindings: &[DescriptorSetLayoutBinding]) -> Result<DescriptorSetLayout, Error> {
 
         let layout_create_info = vk::DescriptorSetLayoutCreateInfo::default()
             .flags(flags)
@@ -28,24 +31,32 @@ impl DescriptorSetLayout {
         let layout = unsafe {
             device.handle()
                 .create_descriptor_set_layout(&layout_create_info, None)
-                .expect("Failed to create descriptor set layout")
+                .map_err(Error::vulkan("Failed to create descriptor set layout"))?
         };
 
-        DescriptorSetLayout {
+        Ok(DescriptorSetLayout {
             device_dep: device.inner.clone(),
             layout,
-        }
+            bindings: layout_bindings.iter().map(|b| (b.binding, b.descriptor_type, b.descriptor_count)).collect(),
+        })
     }
 
-    pub fn new(device: &Device, layout_bindings: &[vk::DescriptorSetLayoutBinding]) -> DescriptorSetLayout {
+    pub fn new(device: &Device, layout_bindings: &[vk::DescriptorSetLayoutBinding]) -> Result<DescriptorSetLayout, Error> {
         DescriptorSetLayout::create(device, vk::DescriptorSetLayoutCreateFlags::empty(), layout_bindings)
     }
 
-    pub fn new_push_descriptor(device: &Device, layout_bindings: &[DescriptorSetLayoutBinding]) -> DescriptorSetLayout {
+    pub fn new_push_descriptor(device: &Device, layout_bindings: &[DescriptorSetLayoutBinding]) -> Result<DescriptorSetLayout, Error> {
         DescriptorSetLayout::create(device, vk::DescriptorSetLayoutCreateFlags::PUSH_DESCRIPTOR_KHR, layout_bindings)
     }
 
     pub(crate) fn handle(&self) -> vk::DescriptorSetLayout {
         self.layout
     }
+
+    /// Type and amount of descriptors at `binding`, `None` when the layout doesn't have it.
+    pub fn binding(&self, binding: u32) -> Option<(vk::DescriptorType, u32)> {
+        self.bindings.iter()
+            .find(|(b, _, _)| *b == binding)
+            .map(|&(_, descriptor_type, count)| (descriptor_type, count))
+    }
 }
\ No newline at end of file
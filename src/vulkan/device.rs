@@ -2,6 +2,8 @@ use std::sync::Arc;
 use ash::khr::swapchain;
 use ash::vk;
 use ash::vk::{PipelineStageFlags, Queue};
+use log::error;
+use crate::Error;
 use crate::vulkan::{CommandBuffer, Instance};
 
 /// A connection to a physical GPU.
@@ -9,40 +11,56 @@ pub struct DeviceInner {
     pub device: ash::Device,
     pub device_push_descriptor: ash::khr::push_descriptor::Device,
     pub queue_family_index: u32,
+    pub limits: vk::PhysicalDeviceLimits,
+    /// Used by every pipeline created on the device, see `ShaderCache` to persist it.
+    pub pipeline_cache: vk::PipelineCache,
+    /// Identifies the pipeline cache data the device is compatible with.
+    pub pipeline_cache_uuid: [u8; vk::UUID_SIZE],
 }
 
 impl Drop for DeviceInner {
     fn drop(&mut self) {
         unsafe {
-            self.device.device_wait_idle().unwrap();
+            if let Err(e) = self.device.device_wait_idle() {
+                error!("Failed to wait for the device before destroying it: {}", e);
+            }
+            self.device.destroy_pipeline_cache(self.pipeline_cache, None);
             self.device.destroy_device(None);
         }
     }
 }
 
+#[derive(Clone)]
 pub struct Device {
     pub inner: Arc<DeviceInner>,
 }
 
 impl Device {
-    pub fn new(instance: &Instance, physical_device: vk::PhysicalDevice, queue_family_index: u32) -> Device {
+    /// Create a logical device.
+    /// The swapchain extension is only enabled when `presentation` is requested.
+    pub fn new(instance: &Instance, physical_device: vk::PhysicalDevice, queue_family_index: u32, presentation: bool) -> Result<Device, Error> {
         let priorities = [1.0];
 
         let queue_info = vk::DeviceQueueCreateInfo::default()
             .queue_family_index(queue_family_index)
             .queue_priorities(&priorities);
 
-        let device_extension_names_raw = [
-            swapchain::NAME.as_ptr(),
+        let mut device_extension_names_raw = vec![
             // Push descriptors
             ash::khr::push_descriptor::NAME.as_ptr(),
             // MoltenVK
             #[cfg(target_os = "macos")]
                 ash::khr::portability_subset::NAME.as_ptr(),
         ];
+        if presentation {
+            device_extension_names_raw.push(swapchain::NAME.as_ptr());
+        }
 
+        // Formats such as rg16f and r16f need the extended storage image formats
+        let supported_features = unsafe { instance.handle().get_physical_device_features(physical_device) };
         let features = vk::PhysicalDeviceFeatures {
             shader_clip_distance: 1,
+            shader_storage_image_extended_formats: supported_features.shader_storage_image_extended_formats,
             ..Default::default()
         };
 
@@ -54,58 +72,84 @@ impl Device {
         let device = unsafe {
             instance.handle()
                 .create_device(physical_device, &device_create_info, None)
-        }.unwrap();
+        }.map_err(|e| Error::Device(e.to_string()))?;
 
         let device_push_descriptor = ash::khr::push_descriptor::Device::new(instance.handle(), &device);
 
+        let properties = unsafe { instance.handle().get_physical_device_properties(physical_device) };
+
+        let pipeline_cache = match unsafe { device.create_pipeline_cache(&vk::PipelineCacheCreateInfo::default(), None) } {
+            Ok(pipeline_cache) => pipeline_cache,
+            Err(e) => {
+                unsafe { device.destroy_device(None); }
+                return Err(Error::vulkan("Failed to create pipeline cache")(e));
+            }
+        };
+
         let device_inner = DeviceInner {
             device,
             device_push_descriptor,
             queue_family_index,
+            limits: properties.limits,
+            pipeline_cache,
+            pipeline_cache_uuid: properties.pipeline_cache_uuid,
         };
 
-        Self {
+        Ok(Self {
             inner: Arc::new(device_inner),
-        }
+        })
     }
 
     pub fn handle(&self) -> &ash::Device {
         &self.inner.device
     }
 
+    pub fn limits(&self) -> &vk::PhysicalDeviceLimits {
+        &self.inner.limits
+    }
+
+    pub fn pipeline_cache(&self) -> vk::PipelineCache {
+        self.inner.pipeline_cache
+    }
+
+    pub fn pipeline_cache_uuid(&self) -> [u8; vk::UUID_SIZE] {
+        self.inner.pipeline_cache_uuid
+    }
+
     pub fn get_queue(&self, queue_index: u32) -> Queue {
         unsafe { self.handle().get_device_queue(self.inner.queue_family_index, queue_index) }
     }
 
-    pub fn wait_idle(&self) {
+    pub fn wait_idle(&self) -> Result<(), Error> {
         unsafe {
-            self.handle().device_wait_idle().unwrap();
+            self.handle().device_wait_idle().map_err(Error::vulkan("Failed to wait for the device"))
         }
     }
 
-    pub fn wait_for_fence(&self, fence: vk::Fence) {
+    pub fn wait_for_fence(&self, fence: vk::Fence) -> Result<(), Error> {
         unsafe {
             let fences = [fence];
             self.handle()
                 .wait_for_fences(&fences, true, u64::MAX)
-                .expect("Failed to destroy fence");
+                .map_err(Error::vulkan("Failed to wait for fence"))
         }
     }
 
-    pub fn reset_fence(&self, fence: vk::Fence) {
+    pub fn reset_fence(&self, fence: vk::Fence) -> Result<(), Error> {
         unsafe {
             let fences = [fence];
             self.handle()
                 .reset_fences(&fences)
-                .unwrap()
+                .map_err(Error::vulkan("Failed to reset fence"))
         }
     }
 
+    /// Submit a command buffer and block until it has finished execution.
     pub fn submit_single_time_command(
         &self,
         queue: Queue,
-        command_buffer: Arc<CommandBuffer>
-    ) {
+        command_buffer: &CommandBuffer
+    ) -> Result<(), Error> {
         unsafe {
             let command_buffers = [command_buffer.handle()];
             let submit_info = vk::SubmitInfo::default()
@@ -114,15 +158,16 @@ impl Device {
             let fence_create_info = vk::FenceCreateInfo::default();
             let fence = self.handle()
                     .create_fence(&fence_create_info, None)
-                    .expect("Failed to create fence");
+                    .map_err(Error::vulkan("Failed to create fence"))?;
 
             let submits = [submit_info];
-            self.handle().queue_submit(queue, &submits, fence).unwrap();
-
-            self.wait_for_fence(fence);
+            let result = self.handle().queue_submit(queue, &submits, fence)
+                .map_err(Error::vulkan("Failed to submit command buffer"))
+                .and_then(|_| self.wait_for_fence(fence));
 
             self.handle()
                 .destroy_fence(fence, None);
+            result
         }
     }
 
@@ -140,7 +185,7 @@ impl Device {
         wait_semaphore: vk::Semaphore,
         signal_semaphore: vk::Semaphore,
         command_buffer: &CommandBuffer
-    ) {
+    ) -> Result<(), Error> {
         let command_buffers = [command_buffer.handle()];
         let wait_semaphores = [wait_semaphore];
         let signal_semaphores = [signal_semaphore];
@@ -153,6 +198,9 @@ impl Device {
             .wait_dst_stage_mask(&wait_dst_stage_masks);
 
         let submits = [submit_info];
-        unsafe { self.handle().queue_submit(*queue, &submits, fence).unwrap(); }
+        unsafe {
+            self.handle().queue_submit(*queue, &submits, fence)
+                .map_err(Error::vulkan("Failed to submit command buffer"))
+        }
     }
 }
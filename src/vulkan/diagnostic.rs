@@ -0,0 +1,270 @@
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A message of the shader compiler, located in the file it's about.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// The shader's path, the canonical path of an included file or a standard header as `<kiyo/...>`.
+    /// Empty when the compiler didn't name a file.
+    pub file: String,
+    /// 1-based line, `None` when the message is about the whole file.
+    pub line: Option<u32>,
+    /// 1-based column of the token the message is about, when it was found on the line.
+    pub column: Option<u32>,
+    pub message: String,
+    /// The source code at `line`.
+    pub source_line: Option<String>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.file.is_empty() {
+            write!(f, "{}:", self.file)?;
+        }
+        if let Some(line) = self.line {
+            write!(f, "{}:", line)?;
+        }
+        if let Some(column) = self.column {
+            write!(f, "{}:", column)?;
+        }
+        write!(f, " {}: {}", self.severity, self.message)?;
+
+        if let Some(source_line) = &self.source_line {
+            write!(f, "\n    {}", source_line)?;
+            if let Some(column) = self.column {
+                // Keep tabs, so the caret lines up with the source line
+                let indent: String = source_line.chars()
+                    .take(column as usize - 1)
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect();
+                write!(f, "\n    {}^", indent)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/**
+ * Parse the messages of shaderc, formatted as `file:line: error: 'token' : message`.
+ * `source` returns the content of a file named in a message, `macros` are the macros defined for the shader,
+ * to point at a macro when the token comes from its expansion.
+ */
+pub(crate) fn parse_diagnostics(log: &str, source: impl Fn(&str) -> Option<String>, macros: &[(String, String)]) -> Vec<Diagnostic> {
+    let mut sources: HashMap<String, Option<String>> = HashMap::new();
+    let mut diagnostics: Vec<Diagnostic> = Vec::new();
+
+    for line in log.lines().map(str::trim_end).filter(|l| !l.is_empty()) {
+        let (location, severity, message) = match split_message(line) {
+            Some(message) => message,
+            None => {
+                // Summaries like `1 error generated.` are left out, other lines continue the previous message
+                match diagnostics.last_mut() {
+                    Some(diagnostic) if !line.ends_with(" generated.") => {
+                        diagnostic.message.push('\n');
+                        diagnostic.message.push_str(line);
+                    },
+                    _ => {},
+                }
+                continue;
+            },
+        };
+
+        let (file, line_number) = match location.rsplit_once(':') {
+            Some((file, number)) => match number.trim().parse::<u32>() {
+                Ok(number) => (file, Some(number)),
+                Err(_) => (location, None),
+            },
+            None => (location, None),
+        };
+
+        let source_line = line_number.and_then(|number| {
+            let content = sources.entry(file.to_string()).or_insert_with(|| source(file));
+            content.as_ref()?.lines().nth(number.checked_sub(1)? as usize).map(|l| l.trim_end().to_string())
+        });
+
+        let token = token(message);
+        let mut message = message.to_string();
+        let column = match (&source_line, token) {
+            (Some(source_line), Some(token)) => match find_column(source_line, token) {
+                Some(column) => Some(column),
+                None => {
+                    // The token isn't in the source, it can come from an injected macro used on the line
+                    let expansion = macros.iter()
+                        .filter(|(_, value)| value.contains(token))
+                        .find_map(|(name, value)| find_column(source_line, name).map(|column| (name, value, column)));
+                    match expansion {
+                        Some((name, value, column)) => {
+                            message.push_str(&format!(" (in the expansion of macro {} = {})", name, value));
+                            Some(column)
+                        },
+                        None => None,
+                    }
+                },
+            },
+            _ => None,
+        };
+
+        diagnostics.push(Diagnostic {
+            severity,
+            file: file.to_string(),
+            line: line_number,
+            column,
+            message,
+            source_line,
+        });
+    }
+    diagnostics
+}
+
+/// Split a message into its location, severity and text.
+fn split_message(line: &str) -> Option<(&str, Severity, &str)> {
+    for (prefix, severity) in [("error: ", Severity::Error), ("warning: ", Severity::Warning)] {
+        if let Some(message) = line.strip_prefix(prefix) {
+            return Some(("", severity, message));
+        }
+    }
+
+    [(": error: ", Severity::Error), (": warning: ", Severity::Warning)].into_iter()
+        .filter_map(|(separator, severity)| line.find(separator).map(|i| (i, separator, severity)))
+        .min_by_key(|(i, _, _)| *i)
+        .map(|(i, separator, severity)| (&line[..i], severity, &line[i + separator.len()..]))
+}
+
+/// The token glslang quotes at the start of its messages, `'token' : message`.
+fn token(message: &str) -> Option<&str> {
+    message.strip_prefix('\'')
+        .and_then(|m| m.split_once("' :"))
+        .map(|(token, _)| token.trim())
+        .filter(|token| !token.is_empty())
+}
+
+/// 1-based column of the first occurrence of `token` as a whole word.
+fn find_column(source_line: &str, token: &str) -> Option<u32> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let (index, _) = source_line.match_indices(token).find(|(i, _)| {
+        let before = source_line[..*i].chars().next_back();
+        let after = source_line[i + token.len()..].chars().next();
+        !before.is_some_and(is_word) && !after.is_some_and(is_word)
+    })?;
+    Some(source_line[..index].chars().count() as u32 + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHADER: &str = "#version 450\n\
+        layout( binding = 0, OUT_FORMAT ) uniform image2D out_image;\n\
+        void main()\n\
+        {\n\
+        \tvec4 c = colr * 2.0;\n\
+        \tfloat unused_result = fbm( c.xy );\n\
+        }\n";
+
+    const COMMON: &str = "float fbm( vec2 p )\n{\n    return noise( p );\n}\n";
+
+    fn source(file: &str) -> Option<String> {
+        match file {
+            "shaders/screen.comp" => Some(SHADER.to_string()),
+            "/home/user/project/shaders/common.glsl" => Some(COMMON.to_string()),
+            _ => None,
+        }
+    }
+
+    fn parse(log: &str) -> Vec<Diagnostic> {
+        parse_diagnostics(log, source, &[("OUT_FORMAT".to_string(), "rgba9".to_string())])
+    }
+
+    #[test]
+    fn parses_error() {
+        let diagnostics = parse("shaders/screen.comp:5: error: 'colr' : undeclared identifier\n1 error generated.\n");
+        assert_eq!(diagnostics.len(), 1);
+        let d = &diagnostics[0];
+        assert_eq!(d.severity, Severity::Error);
+        assert_eq!((d.file.as_str(), d.line, d.column), ("shaders/screen.comp", Some(5), Some(11)));
+        assert_eq!(d.message, "'colr' : undeclared identifier");
+        assert_eq!(d.source_line.as_deref(), Some("\tvec4 c = colr * 2.0;"));
+        assert_eq!(d.to_string(), "shaders/screen.comp:5:11: error: 'colr' : undeclared identifier\n    \tvec4 c = colr * 2.0;\n    \t         ^");
+    }
+
+    #[test]
+    fn parses_warning() {
+        let diagnostics = parse("shaders/screen.comp:1: warning: '#extension' : extension not supported: GL_EXT_foo\n");
+        assert_eq!(diagnostics.len(), 1);
+        let d = &diagnostics[0];
+        assert_eq!(d.severity, Severity::Warning);
+        assert_eq!((d.line, d.column), (Some(1), None));
+        assert_eq!(d.source_line.as_deref(), Some("#version 450"));
+    }
+
+    #[test]
+    fn parses_error_in_include() {
+        let log = "/home/user/project/shaders/common.glsl:3: error: 'noise' : no matching overloaded function found\n\
+            shaders/screen.comp:6: error: 'unused_result' : redefinition\n\
+            2 errors generated.\n";
+        let diagnostics = parse(log);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].file, "/home/user/project/shaders/common.glsl");
+        assert_eq!((diagnostics[0].line, diagnostics[0].column), (Some(3), Some(12)));
+        assert_eq!(diagnostics[0].source_line.as_deref(), Some("    return noise( p );"));
+        assert_eq!((diagnostics[1].file.as_str(), diagnostics[1].column), ("shaders/screen.comp", Some(8)));
+    }
+
+    #[test]
+    fn points_at_expanded_macro() {
+        let log = "shaders/screen.comp:2: error: 'rgba9' : unrecognized layout identifier, or qualifier requires assignment (e.g., binding = 4)\n";
+        let diagnostics = parse(log);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].column, Some(22));
+        assert!(diagnostics[0].message.ends_with("(in the expansion of macro OUT_FORMAT = rgba9)"));
+    }
+
+    #[test]
+    fn keeps_unlocated_messages() {
+        let log = "shaders/missing.comp:4: error: 'x' : undeclared identifier\n\
+            error: linking failed\n\
+            note: see the previous error\n";
+        let diagnostics = parse(log);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!((diagnostics[0].line, diagnostics[0].source_line.as_ref()), (Some(4), None));
+        assert_eq!((diagnostics[1].file.as_str(), diagnostics[1].line), ("", None));
+        assert_eq!(diagnostics[1].message, "linking failed\nnote: see the previous error");
+    }
+
+    #[test]
+    fn splits_message() {
+        assert_eq!(split_message("a.comp:3: warning: x: error: y"), Some(("a.comp:3", Severity::Warning, "x: error: y")));
+        assert_eq!(split_message("error: a.comp: missing"), Some(("", Severity::Error, "a.comp: missing")));
+        assert_eq!(split_message("1 error generated."), None);
+    }
+
+    #[test]
+    fn finds_token() {
+        assert_eq!(token("'colr' : undeclared identifier"), Some("colr"));
+        assert_eq!(token("'' : empty"), None);
+        assert_eq!(token("syntax error"), None);
+    }
+
+    #[test]
+    fn finds_whole_words() {
+        assert_eq!(find_column("float c2 = c + c;", "c"), Some(12));
+        assert_eq!(find_column("float c_d = 1.0;", "c"), None);
+        assert_eq!(find_column("é = c;", "c"), Some(5));
+    }
+}
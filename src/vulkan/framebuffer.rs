@@ -1,6 +1,7 @@
 use std::sync::Arc;
 use ash::vk;
 use ash::vk::Extent2D;
+use crate::Error;
 use crate::vulkan::{Device, RenderPass};
 use crate::vulkan::device::DeviceInner;
 
@@ -23,7 +24,7 @@ pub struct Framebuffer {
 }
 
 impl Framebuffer {
-    pub fn new(device: &Device, extent: vk::Extent2D, render_pass: &RenderPass, attachments: Vec<vk::ImageView>) -> Self {
+    pub fn new(device: &Device, extent: vk::Extent2D, render_pass: &RenderPass, attachments: Vec<vk::ImageView>) -> Result<Self, Error> {
 
         let framebuffer_create_info = vk::FramebufferCreateInfo::default()
             .render_pass(render_pass.handle())
@@ -35,7 +36,7 @@ impl Framebuffer {
         let framebuffer = unsafe {
             device.handle()
                 .create_framebuffer(&framebuffer_create_info, None)
-                .expect("Failed to create framebuffer")
+                .map_err(Error::vulkan("Failed to create framebuffer"))?
         };
 
         let framebuffer_inner = FramebufferInner {
@@ -44,9 +45,9 @@ impl Framebuffer {
             extent
         };
 
-        Framebuffer {
+        Ok(Framebuffer {
             inner: Arc::new(framebuffer_inner)
-        }
+        })
     }
 
     pub fn handle(&self) -> vk::Framebuffer {
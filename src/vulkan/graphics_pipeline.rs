@@ -4,7 +4,8 @@ use std::sync::Arc;
 use ash::vk;
 use crate::vulkan::{DescriptorSetLayout, Device, Pipeline, RenderPass};
 use crate::vulkan::device::DeviceInner;
-use crate::vulkan::pipeline::{create_shader_module, load_shader_code, PipelineErr};
+use crate::vulkan::pipeline::{create_shader_module, load_shader_code};
+use crate::Error;
 
 pub struct GraphicsPipelineInner {
     pub pipeline_layout: vk::PipelineLayout,
@@ -25,6 +26,28 @@ pub struct GraphicsPipeline {
     inner: Arc<GraphicsPipelineInner>
 }
 
+/// Fixed function state which differs between graphics pipelines.
+pub struct GraphicsPipelineState<'a> {
+    pub vertex_bindings: &'a [vk::VertexInputBindingDescription],
+    pub vertex_attributes: &'a [vk::VertexInputAttributeDescription],
+    pub cull_mode: vk::CullModeFlags,
+    /// Blend with premultiplied alpha instead of overwriting the attachment.
+    pub alpha_blending: bool,
+    pub push_constant_ranges: &'a [vk::PushConstantRange],
+}
+
+impl Default for GraphicsPipelineState<'_> {
+    fn default() -> Self {
+        Self {
+            vertex_bindings: &[],
+            vertex_attributes: &[],
+            cull_mode: vk::CullModeFlags::BACK,
+            alpha_blending: false,
+            push_constant_ranges: &[],
+        }
+    }
+}
+
 impl Pipeline for GraphicsPipeline {
     fn handle(&self) -> vk::Pipeline {
         self.inner.graphics_pipeline
@@ -41,14 +64,30 @@ impl Pipeline for GraphicsPipeline {
 
 impl GraphicsPipeline {
 
-    pub fn new(device: &Device, render_pass: &RenderPass, vertex_shader_source: String, fragment_shader_source: String, layouts: &[&DescriptorSetLayout], macros: HashMap<&str, &dyn ToString>) -> Result<Self, PipelineErr> {
+    pub fn new(device: &Device, render_pass: &RenderPass, vertex_shader_source: String, fragment_shader_source: String, layouts: &[&DescriptorSetLayout], macros: HashMap<&str, &dyn ToString>) -> Result<Self, Error> {
 
-        let vertex_shader_code = load_shader_code(vertex_shader_source, &macros)?;
-        let fragment_shader_code = load_shader_code(fragment_shader_source, &macros)?;
+        let vertex_shader = load_shader_code(vertex_shader_source, shaderc::ShaderKind::Vertex, "main", &macros, &[], None)?;
+        let fragment_shader = load_shader_code(fragment_shader_source, shaderc::ShaderKind::Fragment, "main", &macros, &[], None)?;
+
+        Self::with_state(device, render_pass, &vertex_shader.code, &fragment_shader.code, layouts, &GraphicsPipelineState::default())
+    }
+
+    /// Create a pipeline from compiled SPIR-V code.
+    pub fn with_state(device: &Device, render_pass: &RenderPass, vertex_shader_code: &[u32], fragment_shader_code: &[u32], layouts: &[&DescriptorSetLayout], state: &GraphicsPipelineState) -> Result<Self, Error> {
 
         // Shaders
-        let vertex_shader_module = create_shader_module(device.handle(), vertex_shader_code.to_vec());
-        let fragment_shader_module = create_shader_module(device.handle(), fragment_shader_code.to_vec());
+        let vertex_shader_module = create_shader_module(device.handle(), vertex_shader_code.to_vec())?;
+        let fragment_shader_module = match create_shader_module(device.handle(), fragment_shader_code.to_vec()) {
+            Ok(module) => module,
+            Err(e) => {
+                unsafe { device.handle().destroy_shader_module(vertex_shader_module, None); }
+                return Err(e);
+            }
+        };
+        let destroy_shader_modules = || unsafe {
+            device.handle().destroy_shader_module(fragment_shader_module, None);
+            device.handle().destroy_shader_module(vertex_shader_module, None);
+        };
 
         let binding = CString::new("main").unwrap();
         let shader_stages = [
@@ -86,7 +125,9 @@ impl GraphicsPipeline {
             .scissors(&scissors);
 
         // Vertex input
-        let vertex_input_state_create_info = vk::PipelineVertexInputStateCreateInfo::default();
+        let vertex_input_state_create_info = vk::PipelineVertexInputStateCreateInfo::default()
+            .vertex_binding_descriptions(state.vertex_bindings)
+            .vertex_attribute_descriptions(state.vertex_attributes);
 
         // Input assembly
         let input_assembly_state_create_info = vk::PipelineInputAssemblyStateCreateInfo::default()
@@ -96,19 +137,23 @@ impl GraphicsPipeline {
         // Rasterization
         let rasterization_state = vk::PipelineRasterizationStateCreateInfo::default()
             .polygon_mode(vk::PolygonMode::FILL)
-            .cull_mode(vk::CullModeFlags::BACK)
+            .cull_mode(state.cull_mode)
             .front_face(vk::FrontFace::CLOCKWISE)
             .line_width(1.0);
 
         // Color blending
+        let (dst_color_blend_factor, dst_alpha_blend_factor) = match state.alpha_blending {
+            true => (vk::BlendFactor::ONE_MINUS_SRC_ALPHA, vk::BlendFactor::ONE_MINUS_SRC_ALPHA),
+            false => (vk::BlendFactor::ZERO, vk::BlendFactor::ZERO),
+        };
         let color_blend_attachment_state = vk::PipelineColorBlendAttachmentState::default()
-            .blend_enable(false)
+            .blend_enable(state.alpha_blending)
             .color_write_mask(vk::ColorComponentFlags::RGBA)
             .src_color_blend_factor(vk::BlendFactor::ONE)
-            .dst_color_blend_factor(vk::BlendFactor::ZERO)
+            .dst_color_blend_factor(dst_color_blend_factor)
             .color_blend_op(vk::BlendOp::ADD)
             .src_alpha_blend_factor(vk::BlendFactor::ONE)
-            .dst_alpha_blend_factor(vk::BlendFactor::ZERO)
+            .dst_alpha_blend_factor(dst_alpha_blend_factor)
             .alpha_blend_op(vk::BlendOp::ADD);
         let color_blend_attachment_states = [color_blend_attachment_state];
 
@@ -133,11 +178,14 @@ impl GraphicsPipeline {
         let desc_layouts = layouts
             .iter().map(|layout| layout.handle()).collect::<Vec<_>>();
         let create_info = vk::PipelineLayoutCreateInfo::default()
-            .set_layouts(&*desc_layouts);
-        let pipeline_layout = unsafe {
-            device.handle()
-                .create_pipeline_layout(&create_info, None)
-                .expect("Failed to create pipeline layout")
+            .set_layouts(&*desc_layouts)
+            .push_constant_ranges(state.push_constant_ranges);
+        let pipeline_layout = match unsafe { device.handle().create_pipeline_layout(&create_info, None) } {
+            Ok(pipeline_layout) => pipeline_layout,
+            Err(e) => {
+                destroy_shader_modules();
+                return Err(Error::vulkan("Failed to create pipeline layout")(e));
+            }
         };
 
         // pipeline
@@ -154,14 +202,20 @@ impl GraphicsPipeline {
             .dynamic_state(&dynamic_state_create_info)
             .layout(pipeline_layout);
 
-        let graphics_pipeline = unsafe {
+        let graphics_pipelines = unsafe {
             device.handle()
-                .create_graphics_pipelines(vk::PipelineCache::null(), &[graphics_pipeline_create_info], None)
-                .expect("Failed to create graphics pipeline")[0]
+                .create_graphics_pipelines(device.pipeline_cache(), &[graphics_pipeline_create_info], None)
         };
 
-        unsafe { device.handle().destroy_shader_module(fragment_shader_module, None); }
-        unsafe { device.handle().destroy_shader_module(vertex_shader_module, None); }
+        destroy_shader_modules();
+
+        let graphics_pipeline = match graphics_pipelines {
+            Ok(pipelines) => pipelines[0],
+            Err((_, e)) => {
+                unsafe { device.handle().destroy_pipeline_layout(pipeline_layout, None); }
+                return Err(Error::vulkan("Failed to create graphics pipeline")(e));
+            }
+        };
 
         let pipeline_inner = GraphicsPipelineInner {
             pipeline_layout,
@@ -3,6 +3,7 @@ use ash::vk;
 use ash::vk::{ComponentMapping, ImageAspectFlags};
 use gpu_allocator::MemoryLocation;
 use gpu_allocator::vulkan::{Allocation, AllocationScheme};
+use crate::Error;
 use crate::vulkan::{Allocator, Device};
 use crate::vulkan::allocator::AllocatorInner;
 use crate::vulkan::device::DeviceInner;
@@ -11,10 +12,15 @@ pub struct Image {
     pub device_dep: Arc<DeviceInner>,
     pub allocator_dep: Arc<Mutex<AllocatorInner>>,
     pub(crate) image: vk::Image,
+    /// View of the first mip level.
     pub(crate) image_view: vk::ImageView,
+    /// Views of the subsequent mip levels, starting at level 1.
+    pub(crate) mip_views: Vec<vk::ImageView>,
     pub(crate) sampler: vk::Sampler,
     pub width: u32,
     pub height: u32,
+    pub format: vk::Format,
+    pub mip_levels: u32,
     pub allocation: Option<Allocation>,
 }
 
@@ -22,6 +28,9 @@ impl Drop for Image {
     fn drop(&mut self) {
         unsafe {
             self.device_dep.device.destroy_sampler(self.sampler, None);
+            for &mip_view in &self.mip_views {
+                self.device_dep.device.destroy_image_view(mip_view, None);
+            }
             self.device_dep.device.destroy_image_view(self.image_view, None);
             if let Some(allocation) = self.allocation.take() {
                 self.allocator_dep.lock().unwrap().allocator.free(allocation).unwrap();
@@ -32,13 +41,13 @@ impl Drop for Image {
 }
 
 impl Image {
-    pub fn new(device: &Device, allocator: &mut Allocator, width: u32, height: u32, image_usage_flags: vk::ImageUsageFlags) -> Image {
+    pub fn new(device: &Device, allocator: &mut Allocator, width: u32, height: u32, format: vk::Format, mip_levels: u32, image_usage_flags: vk::ImageUsageFlags) -> Result<Image, Error> {
 
         // Image
         let create_info = vk::ImageCreateInfo::default()
             .extent(vk::Extent3D {
-                width: width,
-                height: height,
+                width,
+                height,
                 depth: 1,
             })
             .samples(vk::SampleCountFlags::TYPE_1)
@@ -46,13 +55,13 @@ impl Image {
             .sharing_mode(vk::SharingMode::EXCLUSIVE)
             .initial_layout(vk::ImageLayout::UNDEFINED)
             .array_layers(1)
-            .mip_levels(1)
+            .mip_levels(mip_levels)
             .image_type(vk::ImageType::TYPE_2D)
-            .format(vk::Format::R8G8B8A8_UNORM);
+            .format(format);
 
         let image = unsafe {
             device.handle().create_image(&create_info, None)
-                .expect("Failed to create image")
+                .map_err(Error::vulkan("Failed to create image"))?
         };
 
         // Allocate memory
@@ -64,55 +73,68 @@ impl Image {
                 location: MemoryLocation::GpuOnly,
                 linear: true,
                 allocation_scheme: AllocationScheme::GpuAllocatorManaged,
-            }).unwrap();
+            });
+
+        // From here on the image is released by `drop` when a step fails, destroying null handles is allowed
+        let mut image = Image {
+            image,
+            image_view: vk::ImageView::null(),
+            mip_views: Vec::new(),
+            sampler: vk::Sampler::null(),
+            allocation: None,
+            device_dep: device.inner.clone(),
+            allocator_dep: allocator.inner.clone(),
+            width,
+            height,
+            format,
+            mip_levels,
+        };
+        let allocation = image.allocation.insert(allocation?);
 
         unsafe {
-            device.handle().bind_image_memory(image, allocation.memory(), allocation.offset())
-            .expect("Failed to bind image memory")
+            device.handle().bind_image_memory(image.image, allocation.memory(), allocation.offset())
+                .map_err(Error::vulkan("Failed to bind image memory"))?
         }
 
-        // Image view
-        let image_view_create_info = vk::ImageViewCreateInfo::default()
-            .format(vk::Format::R8G8B8A8_UNORM)
-            .image(image)
-            .view_type(vk::ImageViewType::TYPE_2D)
-            .components(ComponentMapping {
-                r: vk::ComponentSwizzle::R,
-                g: vk::ComponentSwizzle::G,
-                b: vk::ComponentSwizzle::B,
-                a: vk::ComponentSwizzle::A,
-            })
-            .subresource_range(vk::ImageSubresourceRange {
-                aspect_mask: ImageAspectFlags::COLOR,
-                base_mip_level: 0,
-                level_count: 1,
-                base_array_layer: 0,
-                layer_count: 1,
-            });
+        // Image views, storage images can only be bound one mip level at a time
+        for mip_level in 0..mip_levels {
+            let image_view_create_info = vk::ImageViewCreateInfo::default()
+                .format(format)
+                .image(image.image)
+                .view_type(vk::ImageViewType::TYPE_2D)
+                .components(ComponentMapping {
+                    r: vk::ComponentSwizzle::IDENTITY,
+                    g: vk::ComponentSwizzle::IDENTITY,
+                    b: vk::ComponentSwizzle::IDENTITY,
+                    a: vk::ComponentSwizzle::IDENTITY,
+                })
+                .subresource_range(vk::ImageSubresourceRange {
+                    aspect_mask: ImageAspectFlags::COLOR,
+                    base_mip_level: mip_level,
+                    level_count: 1,
+                    base_array_layer: 0,
+                    layer_count: 1,
+                });
 
-        let image_view = unsafe {
-            device.handle().create_image_view(&image_view_create_info, None)
-                .expect("Failed to create image")
-        };
+            let image_view = unsafe {
+                device.handle().create_image_view(&image_view_create_info, None)
+                    .map_err(Error::vulkan("Failed to create image view"))?
+            };
+            match mip_level {
+                0 => image.image_view = image_view,
+                _ => image.mip_views.push(image_view),
+            }
+        }
 
         let sampler_create_info = vk::SamplerCreateInfo::default();
 
         // Sampler
-        let sampler = unsafe {
+        image.sampler = unsafe {
             device.handle().create_sampler(&sampler_create_info, None)
-                .expect("Failed to create sampler")
+                .map_err(Error::vulkan("Failed to create sampler"))?
         };
 
-        Image {
-            image,
-            image_view,
-            sampler,
-            allocation: Some(allocation),
-            device_dep: device.inner.clone(),
-            allocator_dep: allocator.inner.clone(),
-            width,
-            height
-        }
+        Ok(image)
     }
 
     pub fn handle(&self) -> &vk::Image {
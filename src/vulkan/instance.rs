@@ -8,6 +8,7 @@ use std::sync::Arc;
 use ash::khr::surface;
 use log::{debug, error, info, warn};
 use winit::raw_window_handle::RawDisplayHandle;
+use crate::Error;
 use crate::vulkan::surface::Surface;
 
 struct ValidationInfo {
@@ -42,14 +43,17 @@ unsafe extern "system" fn vulkan_debug_utils_callback(
 pub struct InstanceInner {
     instance: ash::Instance,
     pub debug_utils: ash::ext::debug_utils::Instance,
-    pub debug_utils_messenger: DebugUtilsMessengerEXT,
+    /// Only present when the validation layers are available.
+    pub debug_utils_messenger: Option<DebugUtilsMessengerEXT>,
 }
 
 impl Drop for InstanceInner {
     fn drop(&mut self) {
         unsafe {
-            self.debug_utils
-                .destroy_debug_utils_messenger(self.debug_utils_messenger, None);
+            if let Some(debug_utils_messenger) = self.debug_utils_messenger {
+                self.debug_utils
+                    .destroy_debug_utils_messenger(debug_utils_messenger, None);
+            }
             self.instance.destroy_instance(None);
         }
     }
@@ -60,7 +64,10 @@ pub struct Instance {
 }
 
 impl Instance {
-    pub fn new(entry: &Entry, display_handle: RawDisplayHandle) -> Self {
+    /// Create a new instance.
+    /// When no `display_handle` is given the instance is created without surface extensions,
+    /// which allows running on headless machines and software drivers such as lavapipe.
+    pub fn new(entry: &Entry, display_handle: Option<RawDisplayHandle>) -> Result<Self, Error> {
         let app_name = CString::new("kiyo").unwrap();
         let engine_name = CString::new("kiyo Engine").unwrap();
         let app_info = vk::ApplicationInfo::default()
@@ -70,11 +77,14 @@ impl Instance {
             .api_version(vk::make_api_version(0, 1, 0, 0))
             .application_name(app_name.as_c_str());
 
-        let mut extension_names =
-            ash_window::enumerate_required_extensions(display_handle)
-                .unwrap()
-                .to_vec();
-        extension_names.push(debug_utils::NAME.as_ptr());
+        let mut extension_names = match display_handle {
+            Some(display_handle) => {
+                ash_window::enumerate_required_extensions(display_handle)
+                    .map_err(|e| Error::Instance(format!("Failed to find the surface extensions: {}", e)))?
+                    .to_vec()
+            },
+            None => Vec::new(),
+        };
         extension_names.push(ash::khr::get_physical_device_properties2::NAME.as_ptr());
 
         #[cfg(target_os = "macos")]
@@ -90,11 +100,28 @@ impl Instance {
             ],
         };
 
-        let c_ptr_validation_layers = validation
-            .required_validation_layers
-            .iter()
-            .map(|layer_name| layer_name.as_ptr())
-            .collect::<Vec<_>>();
+        // Validation layers are usually not installed on render farms or CI machines
+        let available_layers = unsafe {
+            entry.enumerate_instance_layer_properties()
+                .map_err(|e| Error::Instance(format!("Failed to enumerate instance layers: {}", e)))?
+        };
+        let validation_enabled = validation.required_validation_layers.iter().all(|layer_name| {
+            available_layers.iter().any(|layer| {
+                layer.layer_name_as_c_str().is_ok_and(|name| name == layer_name.as_c_str())
+            })
+        });
+
+        let c_ptr_validation_layers = if validation_enabled {
+            extension_names.push(debug_utils::NAME.as_ptr());
+            validation
+                .required_validation_layers
+                .iter()
+                .map(|layer_name| layer_name.as_ptr())
+                .collect::<Vec<_>>()
+        } else {
+            warn!("Validation layers are not available, continuing without validation");
+            Vec::new()
+        };
 
         let create_flags = if cfg!(target_os = "macos") {
             vk::InstanceCreateFlags::ENUMERATE_PORTABILITY_KHR
@@ -111,7 +138,7 @@ impl Instance {
         let instance: ash::Instance = unsafe {
             entry
                 .create_instance(&create_info, None)
-                .expect("Instance creation error")
+                .map_err(|e| Error::Instance(e.to_string()))?
         };
 
         let debug_utils_create_info = vk::DebugUtilsMessengerCreateInfoEXT {
@@ -131,9 +158,14 @@ impl Instance {
         };
 
         let debug_utils = debug_utils::Instance::new(&entry, &instance);
-        let debug_utils_messenger =
+        // Validation messages are a debugging aid, the instance is still usable without them
+        let debug_utils_messenger = if validation_enabled {
             unsafe { debug_utils.create_debug_utils_messenger(&debug_utils_create_info, None) }
-                .expect("Failed to create debug utils messenger");
+                .inspect_err(|e| warn!("Failed to create debug utils messenger: {}", e))
+                .ok()
+        } else {
+            None
+        };
 
         let instance_inner = InstanceInner {
             instance,
@@ -141,16 +173,18 @@ impl Instance {
             debug_utils_messenger,
         };
 
-        Self {
+        Ok(Self {
             inner: Arc::new(instance_inner),
-        }
+        })
     }
 
-    pub fn create_physical_device(&self, entry: &Entry, surface: &Surface) -> (PhysicalDevice, u32) {
+    /// Find a physical device with a graphics queue.
+    /// If a `surface` is given, the queue also has to support presenting to it.
+    pub fn create_physical_device(&self, entry: &Entry, surface: Option<&Surface>) -> Result<(PhysicalDevice, u32), Error> {
         let physical_devices = unsafe {
             self.handle()
                 .enumerate_physical_devices()
-                .expect("Failed to enumerate physical devices.")
+                .map_err(|e| Error::Device(format!("Failed to enumerate physical devices: {}", e)))?
         };
         let surface_loader = surface::Instance::new(&entry, &self.handle());
         let (physical_device, queue_family_index) = physical_devices
@@ -161,13 +195,16 @@ impl Instance {
                         .iter()
                         .enumerate()
                         .find_map(|(index, info)| {
-                            let supports_graphics_and_surface =
-                                info.queue_flags.contains(vk::QueueFlags::GRAPHICS)
-                                && surface_loader.get_physical_device_surface_support(
+                            let supports_surface = surface.is_none_or(|surface| {
+                                surface_loader.get_physical_device_surface_support(
                                     *physical_device,
                                     index as u32,
                                     *surface.handle()
-                                ).unwrap();
+                                ).unwrap_or(false)
+                            });
+                            let supports_graphics_and_surface =
+                                info.queue_flags.contains(vk::QueueFlags::GRAPHICS)
+                                && supports_surface;
                             if supports_graphics_and_surface {
                                 Some((*physical_device, index))
                             } else {
@@ -176,8 +213,11 @@ impl Instance {
                         })
                 }
             })
-            .expect("Couldn't find a suitable device.");
-        (physical_device, queue_family_index as u32)
+            .ok_or_else(|| match surface {
+                Some(_) => Error::Device("No device has a graphics queue which can present to the window".to_string()),
+                None => Error::Device("No device has a graphics queue".to_string()),
+            })?;
+        Ok((physical_device, queue_family_index as u32))
     }
 
     pub fn handle(&self) -> &ash::Instance {
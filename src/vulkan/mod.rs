@@ -12,19 +12,30 @@ mod pipeline;
 mod image;
 mod descriptor_set_layout;
 mod allocator;
+mod buffer;
+mod spirv;
+mod diagnostic;
+mod shader_cache;
+mod sampler;
 
 pub use self::allocator::Allocator;
+pub use self::buffer::Buffer;
 pub use self::command_buffer::CommandBuffer;
 pub use self::command_pool::CommandPool;
 pub use self::compute_pipeline::ComputePipeline;
 pub use self::device::Device;
 pub use self::descriptor_set_layout::DescriptorSetLayout;
 pub use self::framebuffer::Framebuffer;
-pub use self::graphics_pipeline::GraphicsPipeline;
+pub use self::graphics_pipeline::{GraphicsPipeline, GraphicsPipelineState};
 pub use self::image::Image;
 pub use self::instance::Instance;
 pub use self::surface::Surface;
 pub use self::swapchain::Swapchain;
 pub use self::pipeline::Pipeline;
 pub use self::pipeline::PipelineErr;
+pub use self::pipeline::{CompileError, ShaderCode};
+pub use self::diagnostic::{Diagnostic, Severity};
+pub use self::pipeline::{compile_shader_source, load_shader_code};
+pub use self::shader_cache::ShaderCache;
 pub use self::renderpass::RenderPass;
+pub use self::sampler::Sampler;
@@ -1,8 +1,13 @@
+use std::cell::RefCell;
 use std::collections::HashMap;
-use std::{fmt, fs};
+use std::{fmt, fs, io};
+use std::path::{Path, PathBuf};
 use ash::vk;
 use ash::vk::ShaderModule;
-use log::{info};
+use log::{info, warn};
+use crate::Error;
+use crate::vulkan::diagnostic::{parse_diagnostics, Diagnostic, Severity};
+use crate::vulkan::shader_cache::{content_hash, ShaderCache, StableHasher};
 
 pub trait Pipeline {
     fn handle(&self) -> vk::Pipeline;
@@ -10,20 +15,42 @@ pub trait Pipeline {
     fn layout(&self) -> vk::PipelineLayout;
 }
 
-pub fn create_shader_module(device: &ash::Device, code: Vec<u32>) -> ShaderModule {
+pub fn create_shader_module(device: &ash::Device, code: Vec<u32>) -> Result<ShaderModule, Error> {
     let shader_module_create_info = vk::ShaderModuleCreateInfo::default()
         .code(unsafe { std::slice::from_raw_parts(code.as_ptr(), code.len()) });
 
     unsafe {
         device
             .create_shader_module(&shader_module_create_info, None)
-            .expect("Failed to create shader module")
+            .map_err(Error::vulkan("Failed to create shader module"))
     }
 }
 
 #[derive(Debug)]
 pub enum PipelineErr {
-    ShaderCompilation(String)
+    ShaderCompilation(CompileError),
+    /// The shader compiled, but can't be used as configured.
+    Validation(String),
+    /// The draw config is invalid regardless of its shaders, e.g. a pass uses a buffer which isn't declared.
+    Config(String),
+}
+
+impl PipelineErr {
+    /// The compiler's messages located in the shader's files, empty if they couldn't be parsed or for validation errors.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        match self {
+            PipelineErr::ShaderCompilation(err) => &err.diagnostics,
+            PipelineErr::Validation(_) | PipelineErr::Config(_) => &[],
+        }
+    }
+
+    /// Files included by the shader which failed to compile, to watch for a fix.
+    pub fn includes(&self) -> &[PathBuf] {
+        match self {
+            PipelineErr::ShaderCompilation(err) => &err.includes,
+            PipelineErr::Validation(_) | PipelineErr::Config(_) => &[],
+        }
+    }
 }
 
 impl fmt::Display for PipelineErr {
@@ -32,48 +59,401 @@ impl fmt::Display for PipelineErr {
             PipelineErr::ShaderCompilation(ref err) => {
                 write!(f, "{}", err)
             },
+            PipelineErr::Validation(ref err) | PipelineErr::Config(ref err) => {
+                write!(f, "{}", err)
+            },
+        }
+    }
+}
+
+/// A shader which failed to compile.
+#[derive(Debug)]
+pub struct CompileError {
+    /// The compiler's error messages.
+    pub log: String,
+    /// The messages of `log` parsed, with errors and warnings.
+    pub diagnostics: Vec<Diagnostic>,
+    /// Files included by the shader up to the error, see `ShaderCode::includes`.
+    pub includes: Vec<PathBuf>,
+}
+
+impl CompileError {
+    pub fn new(log: String) -> CompileError {
+        CompileError { log, diagnostics: Vec::new(), includes: Vec::new() }
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.diagnostics.is_empty() {
+            return write!(f, "{}", self.log);
+        }
+        for (i, diagnostic) in self.diagnostics.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", diagnostic)?;
         }
+        Ok(())
     }
 }
 
+/// SPIR-V code compiled from a shader file.
+pub struct ShaderCode {
+    pub code: Vec<u32>,
+    /// Name of the entry point in `code`.
+    pub entry_point: String,
+    /// Every file the shader includes, directly or through other includes.
+    /// Standard headers are embedded in kiyo, so they aren't listed.
+    pub includes: Vec<PathBuf>,
+    /// Content hash of every include when the shader was compiled, to validate cache entries.
+    pub(crate) include_hashes: Vec<u64>,
+    /// Paths searched for includes which didn't exist, before the include directory the file was found in.
+    /// A file created at one of them would be included instead, so it invalidates cache entries as well.
+    pub(crate) include_misses: Vec<PathBuf>,
+    /// Warnings of the compiler, the shader compiled but may not do what's intended.
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// Headers shipped with kiyo, available to shaders as `#include <kiyo/...>`.
+const STANDARD_HEADERS: &[(&str, &str)] = &[
+    ("kiyo/engine.glsl", include_str!("../../include/kiyo/engine.glsl")),
+    ("kiyo/noise.glsl", include_str!("../../include/kiyo/noise.glsl")),
+    ("kiyo/sdf.glsl", include_str!("../../include/kiyo/sdf.glsl")),
+];
+
+fn standard_header(name: &str) -> Option<shaderc::ResolvedInclude> {
+    STANDARD_HEADERS.iter()
+        .find(|(header, _)| *header == name)
+        .map(|(header, content)| shaderc::ResolvedInclude {
+            resolved_name: format!("<{}>", header),
+            content: content.to_string(),
+        })
+}
+
+/// Resolve an `#include`, `requesting` is the resolved name of the including file.
+/// `"file"` is looked up next to the including file and then in the include directories,
+/// `<file>` in the standard headers and then in the include directories.
+/// Included files are added to `includes`, with the hash of their content.
+fn resolve_include(
+    requested: &str,
+    include_type: shaderc::IncludeType,
+    requesting: &str,
+    include_dirs: &[PathBuf],
+    includes: &RefCell<Vec<(PathBuf, u64)>>,
+    misses: &RefCell<Vec<PathBuf>>
+) -> shaderc::IncludeCallbackResult {
+    // Standard headers are named `<kiyo/...>`, relative includes in them refer to other standard headers
+    let standard_dir = requesting.strip_prefix('<').and_then(|r| r.strip_suffix('>')).map(|r| Path::new(r).parent().unwrap_or(Path::new("")));
+    let relative_name = |dir: &Path| dir.join(requested).to_string_lossy().replace('\\', "/");
+    let header = match (include_type, standard_dir) {
+        (shaderc::IncludeType::Standard, _) => standard_header(requested),
+        (shaderc::IncludeType::Relative, Some(dir)) => standard_header(&relative_name(dir)),
+        (shaderc::IncludeType::Relative, None) => None,
+    };
+    if let Some(header) = header {
+        return Ok(header);
+    }
+
+    let relative_dir = match (include_type, standard_dir) {
+        (shaderc::IncludeType::Relative, None) => Path::new(requesting).parent(),
+        _ => None,
+    };
+    let candidates = relative_dir.into_iter()
+        .chain(include_dirs.iter().map(PathBuf::as_path))
+        .map(|dir| dir.join(requested));
+    for candidate in candidates {
+        if let Ok(content) = fs::read_to_string(&candidate) {
+            let path = fs::canonicalize(&candidate).unwrap_or(candidate);
+            let mut includes = includes.borrow_mut();
+            if !includes.iter().any(|(p, _)| *p == path) {
+                includes.push((path.clone(), content_hash(content.as_bytes())));
+            }
+            return Ok(shaderc::ResolvedInclude {
+                resolved_name: path.to_string_lossy().to_string(),
+                content,
+            });
+        }
+        let miss = candidate.parent().and_then(|dir| fs::canonicalize(dir).ok())
+            .zip(candidate.file_name())
+            .map_or(candidate.clone(), |(dir, file)| dir.join(file));
+        let mut misses = misses.borrow_mut();
+        if !misses.contains(&miss) {
+            misses.push(miss);
+        }
+    }
+    Err(format!("Cannot find include file: {}", requested))
+}
+
 /**
- * Load a shader from a file and compile it into SPIR-V.
+ * Load a shader from a file and compile it into SPIR-V, the language is picked by the file's extension:
+ * - `.comp`, `.vert` and `.frag` are GLSL, their stage follows from the extension and they start at `main`.
+ * - `.hlsl` is HLSL, compiled for `stage` starting at `entry_point`.
+ * - `.wgsl` is WGSL, translated for `stage` starting at `entry_point`. WGSL has no preprocessor, so `macros` aren't applied.
+ * - `.spv` is SPIR-V which is loaded as is.
+ *
+ * `include_dirs` are searched for included files which aren't found next to the including file.
+ * Compiled shaders are stored in `cache`, and loaded from it as long as nothing they depend on changed.
  */
-pub fn load_shader_code(source_file: String, macros: &HashMap<&str, &dyn ToString>) -> Result<Vec<u32>, PipelineErr>
+pub fn load_shader_code(
+    source_file: String,
+    stage: shaderc::ShaderKind,
+    entry_point: &str,
+    macros: &HashMap<&str, &dyn ToString>,
+    include_dirs: &[PathBuf],
+    cache: Option<&ShaderCache>
+) -> Result<ShaderCode, Error>
 {
     use shaderc;
 
-    let shader_kind = match source_file.split(".").last() {
-        Some("vert") => shaderc::ShaderKind::Vertex,
-        Some("frag") => shaderc::ShaderKind::Fragment,
-        Some("comp") => shaderc::ShaderKind::Compute,
-        _ => panic!("Unknown shader type")
+    // Reported like a compile error, so the app waits for a missing shader to be created
+    let read_error = |e: io::Error| Error::from(PipelineErr::ShaderCompilation(CompileError::new(format!("{}: {}", source_file, e))));
+    let extension = Path::new(&source_file).extension().and_then(|e| e.to_str()).unwrap_or("");
+    let (shader_kind, entry_point) = match extension {
+        "vert" => (shaderc::ShaderKind::Vertex, "main"),
+        "frag" => (shaderc::ShaderKind::Fragment, "main"),
+        "comp" => (shaderc::ShaderKind::Compute, "main"),
+        "hlsl" | "wgsl" => (stage, entry_point),
+        "spv" => {
+            let bytes = fs::read(&source_file).map_err(read_error)?;
+            return Ok(load_spirv(&bytes, &source_file, entry_point)?);
+        },
+        _ => return Err(PipelineErr::ShaderCompilation(CompileError::new(format!(
+            "{}: unknown shader type, expected .comp, .vert, .frag, .hlsl, .wgsl or .spv", source_file
+        ))).into())
+    };
+
+    let source = fs::read_to_string(&source_file).map_err(read_error)?;
+
+    let cache = cache.map(|cache| (cache, cache_key(&source_file, &source, shader_kind, entry_point, macros, include_dirs)));
+    if let Some(shader) = cache.and_then(|(cache, key)| cache.load(key)) {
+        for warning in &shader.warnings {
+            warn!("{}", warning);
+        }
+        info!("Loaded cached shader: {}", source_file);
+        return Ok(shader);
+    }
+
+    let shader = match extension {
+        "wgsl" => compile_wgsl(&source, shader_kind, &source_file, entry_point)?,
+        "hlsl" => compile_shader(&source, shader_kind, &source_file, entry_point, shaderc::SourceLanguage::HLSL, macros, include_dirs)?,
+        _ => compile_shader(&source, shader_kind, &source_file, entry_point, shaderc::SourceLanguage::GLSL, macros, include_dirs)?,
     };
 
-    let source = fs::read_to_string(source_file.clone()).expect(format!("Failed to read file: {}", source_file).as_str());
+    // A shader which can't be cached still works, it's compiled again next time
+    if let Some((cache, key)) = cache {
+        if let Err(e) = cache.store(key, &shader) {
+            warn!("Failed to cache shader {}: {}", source_file, e);
+        }
+    }
+    Ok(shader)
+}
+
+/// Bumped when shaders are compiled differently, which invalidates every cached shader.
+const CACHE_VERSION: u32 = 1;
+
+/// Hash of everything the SPIR-V of a shader depends on, besides the content of its includes.
+/// Paths are canonicalized, as the same relative path of another project resolves its includes to other files.
+/// The include directories are hashed in their search order, which decides the file an include resolves to.
+fn cache_key(
+    source_file: &str,
+    source: &str,
+    shader_kind: shaderc::ShaderKind,
+    entry_point: &str,
+    macros: &HashMap<&str, &dyn ToString>,
+    include_dirs: &[PathBuf]
+) -> u64 {
+    let mut macros = macros.iter().map(|( k, v )| ( *k, v.to_string() )).collect::<Vec<_>>();
+    macros.sort();
+    let canonical = |path: &Path| fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()).to_string_lossy().to_string();
+
+    let mut hasher = StableHasher::default();
+    hasher.write_u32(CACHE_VERSION);
+    hasher.write_str(env!("CARGO_PKG_VERSION"));
+    hasher.write_u32(STANDARD_HEADERS.len() as u32);
+    for (name, content) in STANDARD_HEADERS {
+        hasher.write_str(name);
+        hasher.write_str(content);
+    }
+    hasher.write_str(&canonical(Path::new(source_file)));
+    hasher.write_str(source);
+    hasher.write_str(&format!("{:?}", shader_kind));
+    hasher.write_str(entry_point);
+    hasher.write_u32(macros.len() as u32);
+    for (name, value) in &macros {
+        hasher.write_str(name);
+        hasher.write_str(value);
+    }
+    hasher.write_u32(include_dirs.len() as u32);
+    for dir in include_dirs {
+        hasher.write_str(&canonical(dir));
+    }
+    hasher.finish()
+}
+
+fn load_spirv(bytes: &[u8], name: &str, entry_point: &str) -> Result<ShaderCode, PipelineErr> {
+    if !bytes.len().is_multiple_of(4) {
+        return Err(PipelineErr::Validation(format!("{}: SPIR-V size of {} bytes isn't a multiple of 4", name, bytes.len())));
+    }
+    let code = bytes.chunks_exact(4)
+        .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+        .collect();
+    info!("Loaded SPIR-V shader: {}", name);
+    Ok(ShaderCode { code, entry_point: entry_point.to_string(), includes: Vec::new(), include_hashes: Vec::new(), include_misses: Vec::new(), warnings: Vec::new() })
+}
+
+/// Translate WGSL into SPIR-V with naga.
+fn compile_wgsl(source: &str, stage: shaderc::ShaderKind, name: &str, entry_point: &str) -> Result<ShaderCode, PipelineErr> {
+    let shader_stage = match stage {
+        shaderc::ShaderKind::Vertex => naga::ShaderStage::Vertex,
+        shaderc::ShaderKind::Fragment => naga::ShaderStage::Fragment,
+        _ => naga::ShaderStage::Compute,
+    };
+
+    let error = |log: String, message: String, location: Option<naga::SourceLocation>| {
+        let diagnostic = Diagnostic {
+            severity: Severity::Error,
+            file: name.to_string(),
+            line: location.map(|l| l.line_number),
+            column: location.map(|l| l.line_position),
+            message,
+            source_line: location.and_then(|l| source.lines().nth(l.line_number as usize - 1)).map(|l| l.trim_end().to_string()),
+        };
+        PipelineErr::ShaderCompilation(CompileError { log, diagnostics: Vec::from([ diagnostic ]), includes: Vec::new() })
+    };
+
+    let module = naga::front::wgsl::parse_str(source)
+        .map_err(|e| error(e.emit_to_string_with_path(source, name), e.message().to_string(), e.location(source)))?;
+
+    let info = naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::all())
+        .validate(&module)
+        .map_err(|e| {
+            // The validation error only says what failed, its sources tell why
+            let mut message = e.as_inner().to_string();
+            let mut inner: Option<&dyn std::error::Error> = std::error::Error::source(e.as_inner());
+            while let Some(err) = inner {
+                message.push_str(&format!(": {}", err));
+                inner = err.source();
+            }
+            error(e.emit_to_string_with_path(source, name), message, e.location(source))
+        })?;
+
+    let pipeline_options = naga::back::spv::PipelineOptions {
+        shader_stage,
+        entry_point: entry_point.to_string(),
+    };
+    let code = naga::back::spv::write_vec(&module, &info, &naga::back::spv::Options::default(), Some(&pipeline_options))
+        .map_err(|e| PipelineErr::ShaderCompilation(CompileError::new(format!("{}: {}", name, e))))?;
 
-    let compiler = shaderc::Compiler::new().unwrap();
-    let mut options = shaderc::CompileOptions::new().unwrap();
-    options.add_macro_definition("EP", Some("main"));
-    for ( k, v ) in macros {
-        options.add_macro_definition(k, Some(v.to_string().as_str()));
+    info!("Successfully compiled shader: {}", name);
+    Ok(ShaderCode { code, entry_point: entry_point.to_string(), includes: Vec::new(), include_hashes: Vec::new(), include_misses: Vec::new(), warnings: Vec::new() })
+}
+
+/**
+ * Compile shader source code into SPIR-V, `name` is used in error messages.
+ * Only standard headers can be included, and files relative to `name`.
+ */
+pub fn compile_shader_source(source: &str, shader_kind: shaderc::ShaderKind, name: &str, macros: &HashMap<&str, &dyn ToString>) -> Result<Vec<u32>, PipelineErr>
+{
+    compile_shader(source, shader_kind, name, "main", shaderc::SourceLanguage::GLSL, macros, &[]).map(|shader| shader.code)
+}
+
+fn compile_shader(
+    source: &str,
+    shader_kind: shaderc::ShaderKind,
+    name: &str,
+    entry_point: &str,
+    language: shaderc::SourceLanguage,
+    macros: &HashMap<&str, &dyn ToString>,
+    include_dirs: &[PathBuf]
+) -> Result<ShaderCode, PipelineErr>
+{
+    let includes = RefCell::new(Vec::new());
+    let include_misses = RefCell::new(Vec::new());
+    let mut macro_values = Vec::from([ ( "EP".to_string(), "main".to_string() ) ]);
+    macro_values.extend(macros.iter().map(|( k, v )| ( k.to_string(), v.to_string() )));
+    let (compiler, mut options) = shaderc::Compiler::new().zip(shaderc::CompileOptions::new())
+        .ok_or_else(|| PipelineErr::ShaderCompilation(CompileError::new("Failed to initialize the shader compiler".to_string())))?;
+    options.set_source_language(language);
+    options.set_include_callback(|requested, include_type, requesting, _| {
+        resolve_include(requested, include_type, requesting, include_dirs, &includes, &include_misses)
+    });
+    for ( k, v ) in &macro_values {
+        options.add_macro_definition(k, Some(v));
     }
 
     let binary_result = compiler.compile_into_spirv(
-        source.as_str(),
+        source,
         shader_kind,
-        source_file.as_str(),
-        "main",
+        name,
+        entry_point,
         Some(&options)
     );
 
+    // The options borrow the included files
+    drop(options);
+    let (includes, include_hashes): (Vec<PathBuf>, Vec<u64>) = includes.into_inner().into_iter().unzip();
+    let include_misses = include_misses.into_inner();
+
+    // Messages name the shader, included files by their canonical path and standard headers as `<kiyo/...>`
+    let file_source = |file: &str| {
+        if file == name {
+            return Some(source.to_string());
+        }
+        match file.strip_prefix('<').and_then(|f| f.strip_suffix('>')).and_then(standard_header) {
+            Some(header) => Some(header.content),
+            None => fs::read_to_string(file).ok(),
+        }
+    };
+
     match binary_result {
         Ok(result) => {
-            info!("Successfully compiled shader: {}", source_file);
-            Ok(result.as_binary().to_vec())
+            let warnings = match result.get_num_warnings() {
+                0 => Vec::new(),
+                _ => parse_diagnostics(&result.get_warning_messages(), file_source, &macro_values),
+            };
+            for warning in &warnings {
+                warn!("{}", warning);
+            }
+            info!("Successfully compiled shader: {}", name);
+            Ok(ShaderCode { code: result.as_binary().to_vec(), entry_point: entry_point.to_string(), includes, include_hashes, include_misses, warnings })
+        },
+        Err(shaderc::Error::CompilationError(_, log)) => {
+            let diagnostics = parse_diagnostics(&log, file_source, &macro_values);
+            Err(PipelineErr::ShaderCompilation(CompileError { log, diagnostics, includes }))
         },
         Err(error) => {
-            Err(PipelineErr::ShaderCompilation(error.to_string()))
+            Err(PipelineErr::ShaderCompilation(CompileError { log: error.to_string(), diagnostics: Vec::new(), includes }))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    #[test]
+    fn records_include_misses() {
+        let dir = TestDir::new("pipeline-include-misses");
+        for subdir in ["shaders", "first", "second"] {
+            fs::create_dir_all(dir.path.join(subdir)).unwrap();
         }
+        fs::write(dir.path.join("second/common.glsl"), "float fbm( vec2 p );\n").unwrap();
+        let shader = dir.path.join("shaders/screen.comp").to_string_lossy().to_string();
+        let include_dirs = [dir.path.join("first"), dir.path.join("second")];
+        let (includes, misses) = (RefCell::new(Vec::new()), RefCell::new(Vec::new()));
+
+        let resolved = resolve_include("common.glsl", shaderc::IncludeType::Relative, &shader, &include_dirs, &includes, &misses).unwrap();
+        let canonical = fs::canonicalize(&dir.path).unwrap();
+        assert_eq!(PathBuf::from(resolved.resolved_name), canonical.join("second/common.glsl"));
+        assert_eq!(includes.borrow()[0], (canonical.join("second/common.glsl"), content_hash(b"float fbm( vec2 p );\n")));
+        assert_eq!(*misses.borrow(), [canonical.join("shaders/common.glsl"), canonical.join("first/common.glsl")]);
+
+        let include_dirs = [dir.path.join("second"), dir.path.join("first")];
+        assert_ne!(
+            cache_key(&shader, "", shaderc::ShaderKind::Compute, "main", &HashMap::new(), &include_dirs),
+            cache_key(&shader, "", shaderc::ShaderKind::Compute, "main", &HashMap::new(), &[dir.path.join("first"), dir.path.join("second")])
+        );
     }
 }
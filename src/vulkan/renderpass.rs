@@ -1,5 +1,6 @@
 use std::sync::Arc;
 use ash::{vk};
+use crate::Error;
 use crate::vulkan::{Device};
 use crate::vulkan::device::DeviceInner;
 
@@ -21,7 +22,7 @@ pub struct RenderPass {
 }
 
 impl RenderPass {
-    pub fn new(device: &Device, surface_format: vk::Format) -> RenderPass {
+    pub fn new(device: &Device, surface_format: vk::Format) -> Result<RenderPass, Error> {
         let color_attachment = vk::AttachmentDescription::default()
             .format(surface_format)
             .samples(vk::SampleCountFlags::TYPE_1)
@@ -51,7 +52,7 @@ impl RenderPass {
         let renderpass = unsafe {
             device.handle()
                 .create_render_pass(&renderpass_create_info, None)
-                .expect("Failed to create render pass")
+                .map_err(Error::vulkan("Failed to create render pass"))?
         };
 
         let renderpass_inner = RenderPassInner {
@@ -59,9 +60,62 @@ impl RenderPass {
             device_dep: device.inner.clone()
         };
 
-        RenderPass {
+        Ok(RenderPass {
             inner: Arc::new(renderpass_inner),
-        }
+        })
+    }
+
+    /// Render pass drawing on top of a swapchain image which was just written by a transfer,
+    /// leaving the image ready to be presented.
+    pub fn new_overlay(device: &Device, surface_format: vk::Format) -> Result<RenderPass, Error> {
+        let color_attachment = vk::AttachmentDescription::default()
+            .format(surface_format)
+            .samples(vk::SampleCountFlags::TYPE_1)
+            .load_op(vk::AttachmentLoadOp::LOAD)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .stencil_load_op(vk::AttachmentLoadOp::DONT_CARE)
+            .stencil_store_op(vk::AttachmentStoreOp::DONT_CARE)
+            .initial_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+            .final_layout(vk::ImageLayout::PRESENT_SRC_KHR);
+
+        let render_pass_attachments = [color_attachment];
+
+        let color_attachment_ref = vk::AttachmentReference::default()
+            .attachment( 0 )
+            .layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL);
+
+        let subpass_description = vk::SubpassDescription::default()
+            .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+            .color_attachments(std::slice::from_ref(&color_attachment_ref));
+
+        let subpass_descriptions = [subpass_description];
+
+        // Wait for the transfer writing the image
+        let subpass_dependencies = [vk::SubpassDependency::default()
+            .src_subpass(vk::SUBPASS_EXTERNAL)
+            .dst_subpass(0)
+            .src_stage_mask(vk::PipelineStageFlags::TRANSFER)
+            .dst_stage_mask(vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT)
+            .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+            .dst_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE)];
+
+        let renderpass_create_info = vk::RenderPassCreateInfo::default()
+            .attachments(&render_pass_attachments)
+            .subpasses(&subpass_descriptions)
+            .dependencies(&subpass_dependencies);
+
+        let renderpass = unsafe {
+            device.handle()
+                .create_render_pass(&renderpass_create_info, None)
+                .map_err(Error::vulkan("Failed to create render pass"))?
+        };
+
+        Ok(RenderPass {
+            inner: Arc::new(RenderPassInner {
+                renderpass,
+                device_dep: device.inner.clone()
+            }),
+        })
     }
 
     pub fn handle(&self) -> vk::RenderPass {
@@ -0,0 +1,44 @@
+use std::sync::Arc;
+use ash::vk;
+use crate::Error;
+use crate::vulkan::Device;
+use crate::vulkan::device::DeviceInner;
+
+pub struct Sampler {
+    device_dep: Arc<DeviceInner>,
+    sampler: vk::Sampler,
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        unsafe {
+            self.device_dep.device.destroy_sampler(self.sampler, None);
+        }
+    }
+}
+
+impl Sampler {
+    /// Create a sampler which clamps to the edge of the image.
+    pub fn new(device: &Device, filter: vk::Filter) -> Result<Sampler, Error> {
+        let sampler_create_info = vk::SamplerCreateInfo::default()
+            .mag_filter(filter)
+            .min_filter(filter)
+            .address_mode_u(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_v(vk::SamplerAddressMode::CLAMP_TO_EDGE)
+            .address_mode_w(vk::SamplerAddressMode::CLAMP_TO_EDGE);
+
+        let sampler = unsafe {
+            device.handle().create_sampler(&sampler_create_info, None)
+                .map_err(Error::vulkan("Failed to create sampler"))?
+        };
+
+        Ok(Sampler {
+            device_dep: device.inner.clone(),
+            sampler,
+        })
+    }
+
+    pub fn handle(&self) -> vk::Sampler {
+        self.sampler
+    }
+}
@@ -0,0 +1,362 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use ash::vk;
+use log::info;
+use crate::Error;
+use crate::vulkan::{Device, Diagnostic, Severity, ShaderCode};
+
+/// Start of every cache entry, the last byte is the version of the entry layout.
+const ENTRY_MAGIC: &[u8; 8] = b"KIYOSPV\x02";
+
+/// Size of the header Vulkan puts in front of pipeline cache data, with the device's pipeline cache UUID at its end.
+const PIPELINE_CACHE_HEADER_SIZE: usize = 32;
+
+/// Compiled shaders and the Vulkan pipeline cache, stored in a directory so unchanged shaders aren't compiled again.
+///
+/// Shaders are stored by a hash of everything their SPIR-V depends on: the source, macros, entry point,
+/// compiler settings and kiyo version. The files a shader includes are only known after compiling it,
+/// so their contents are checked when the entry is loaded, as well as that no file was created which an include would resolve to instead.
+#[derive(Clone)]
+pub struct ShaderCache {
+    dir: PathBuf,
+}
+
+impl ShaderCache {
+    pub fn new(dir: impl Into<PathBuf>) -> ShaderCache {
+        ShaderCache { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn entry_path(&self, key: u64) -> PathBuf {
+        self.dir.join(format!("{:016x}.spv", key))
+    }
+
+    fn pipeline_cache_path(&self, device: &Device) -> PathBuf {
+        let uuid = device.pipeline_cache_uuid().iter().map(|b| format!("{:02x}", b)).collect::<String>();
+        self.dir.join(format!("pipelines-{}.bin", uuid))
+    }
+
+    /// The shader stored under `key`, `None` when there is none or an included file changed since it was stored.
+    pub(crate) fn load(&self, key: u64) -> Option<ShaderCode> {
+        let data = fs::read(self.entry_path(key)).ok()?;
+        let shader = decode_entry(&data)?;
+        let (includes, hashes): (Vec<PathBuf>, Vec<u64>) = shader.includes.into_iter().unzip();
+        let unchanged = includes.iter().zip(&hashes)
+            .all(|(path, hash)| fs::read(path).is_ok_and(|content| content_hash(&content) == *hash))
+            && !shader.include_misses.iter().any(|path| path.exists());
+        unchanged.then_some(ShaderCode {
+            code: shader.code,
+            entry_point: shader.entry_point,
+            includes,
+            include_hashes: hashes,
+            include_misses: shader.include_misses,
+            warnings: shader.warnings,
+        })
+    }
+
+    /// Store a compiled shader under `key`.
+    pub(crate) fn store(&self, key: u64, shader: &ShaderCode) -> Result<(), Error> {
+        let mut data = Vec::from(*ENTRY_MAGIC);
+        write_str(&mut data, &shader.entry_point);
+        write_u32(&mut data, shader.includes.len() as u32);
+        for (path, hash) in shader.includes.iter().zip(&shader.include_hashes) {
+            write_str(&mut data, &path.to_string_lossy());
+            data.extend(hash.to_le_bytes());
+        }
+        write_u32(&mut data, shader.include_misses.len() as u32);
+        for path in &shader.include_misses {
+            write_str(&mut data, &path.to_string_lossy());
+        }
+        write_u32(&mut data, shader.warnings.len() as u32);
+        for warning in &shader.warnings {
+            write_str(&mut data, &warning.file);
+            write_u32(&mut data, warning.line.unwrap_or(0));
+            write_u32(&mut data, warning.column.unwrap_or(0));
+            write_str(&mut data, &warning.message);
+            write_str(&mut data, warning.source_line.as_deref().unwrap_or(""));
+        }
+        data.extend(shader.code.iter().flat_map(|word| word.to_le_bytes()));
+
+        self.write(&self.entry_path(key), &data)
+    }
+
+    /// Merge the stored pipeline cache into the device's, data of another device or driver version is ignored.
+    pub fn load_pipelines(&self, device: &Device) -> Result<(), Error> {
+        let path = self.pipeline_cache_path(device);
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(Error::Io(path, e)),
+        };
+        if data.len() < PIPELINE_CACHE_HEADER_SIZE || data[16..PIPELINE_CACHE_HEADER_SIZE] != device.pipeline_cache_uuid() {
+            info!("Ignoring pipeline cache of another device: {}", path.display());
+            return Ok(());
+        }
+
+        let create_info = vk::PipelineCacheCreateInfo::default().initial_data(&data);
+        unsafe {
+            let stored = device.handle().create_pipeline_cache(&create_info, None)
+                .map_err(Error::vulkan("Failed to create pipeline cache"))?;
+            let merged = device.handle().merge_pipeline_caches(device.pipeline_cache(), &[stored]);
+            device.handle().destroy_pipeline_cache(stored, None);
+            merged.map_err(Error::vulkan("Failed to merge pipeline caches"))
+        }
+    }
+
+    /// Write the device's pipeline cache, to be loaded by `load_pipelines` on the next run.
+    pub fn save_pipelines(&self, device: &Device) -> Result<(), Error> {
+        let data = unsafe { device.handle().get_pipeline_cache_data(device.pipeline_cache()) }
+            .map_err(Error::vulkan("Failed to read pipeline cache"))?;
+        self.write(&self.pipeline_cache_path(device), &data)
+    }
+
+    /// Write a file through a temporary file, so a running app never reads a partial file.
+    fn write(&self, path: &Path, data: &[u8]) -> Result<(), Error> {
+        fs::create_dir_all(&self.dir).map_err(|e| Error::Io(self.dir.clone(), e))?;
+        let temp_path = path.with_extension(format!("tmp{}", std::process::id()));
+        fs::write(&temp_path, data).map_err(|e| Error::Io(temp_path.clone(), e))?;
+        fs::rename(&temp_path, path).map_err(|e| Error::Io(path.to_path_buf(), e))
+    }
+}
+
+/// 64-bit FNV-1a, for keys and hashes which are stored on disk.
+/// `DefaultHasher` and the `Hash` implementations may change between Rust releases, so values are written as explicit bytes.
+pub(crate) struct StableHasher {
+    hash: u64,
+}
+
+impl Default for StableHasher {
+    fn default() -> StableHasher {
+        StableHasher { hash: 0xcbf29ce484222325 }
+    }
+}
+
+impl StableHasher {
+    pub fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.hash ^= byte as u64;
+            self.hash = self.hash.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.write(&value.to_le_bytes());
+    }
+
+    /// Write a string prefixed with its length, so consecutive strings can't run into each other.
+    pub fn write_str(&mut self, value: &str) {
+        self.write(&(value.len() as u64).to_le_bytes());
+        self.write(value.as_bytes());
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+/// Hash of a file's content, to see whether it changed.
+pub(crate) fn content_hash(content: &[u8]) -> u64 {
+    let mut hasher = StableHasher::default();
+    hasher.write(content);
+    hasher.finish()
+}
+
+/// A decoded cache entry, with the content hash of every include.
+struct Entry {
+    code: Vec<u32>,
+    entry_point: String,
+    includes: Vec<(PathBuf, u64)>,
+    include_misses: Vec<PathBuf>,
+    warnings: Vec<Diagnostic>,
+}
+
+fn decode_entry(data: &[u8]) -> Option<Entry> {
+    let mut reader = Reader { data: data.strip_prefix(ENTRY_MAGIC)? };
+    let entry_point = reader.str()?;
+    let includes = (0..reader.u32()?)
+        .map(|_| Some(( PathBuf::from(reader.str()?), reader.u64()? )))
+        .collect::<Option<Vec<_>>>()?;
+    let include_misses = (0..reader.u32()?)
+        .map(|_| reader.str().map(PathBuf::from))
+        .collect::<Option<Vec<_>>>()?;
+    let warnings = (0..reader.u32()?)
+        .map(|_| Some(Diagnostic {
+            severity: Severity::Warning,
+            file: reader.str()?,
+            line: Some(reader.u32()?).filter(|&l| l > 0),
+            column: Some(reader.u32()?).filter(|&c| c > 0),
+            message: reader.str()?,
+            source_line: Some(reader.str()?).filter(|l| !l.is_empty()),
+        }))
+        .collect::<Option<Vec<_>>>()?;
+    if reader.data.is_empty() || !reader.data.len().is_multiple_of(4) {
+        return None;
+    }
+    let code = reader.data.chunks_exact(4)
+        .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+        .collect();
+    Some(Entry { code, entry_point, includes, include_misses, warnings })
+}
+
+fn write_u32(data: &mut Vec<u8>, value: u32) {
+    data.extend(value.to_le_bytes());
+}
+
+fn write_str(data: &mut Vec<u8>, value: &str) {
+    write_u32(data, value.len() as u32);
+    data.extend(value.as_bytes());
+}
+
+/// Reads the values written by `write_u32` and `write_str`, `None` when the data ends early.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.data.len() < len {
+            return None;
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Some(bytes)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.bytes(4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.bytes(8).map(|b| u64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+    }
+
+    fn str(&mut self) -> Option<String> {
+        let len = self.u32()? as usize;
+        self.bytes(len).and_then(|b| String::from_utf8(b.to_vec()).ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir::TestDir;
+
+    fn shader(includes: &[&Path]) -> ShaderCode {
+        ShaderCode {
+            code: vec![0x07230203, 0x00010000, 0, 1, 0],
+            entry_point: "main".to_string(),
+            includes: includes.iter().map(|p| p.to_path_buf()).collect(),
+            include_hashes: includes.iter().map(|p| content_hash(&fs::read(p).unwrap())).collect(),
+            include_misses: Vec::new(),
+            warnings: vec![Diagnostic {
+                severity: Severity::Warning,
+                file: "shaders/screen.comp".to_string(),
+                line: Some(5),
+                column: None,
+                message: "'unused' : variable is unused".to_string(),
+                source_line: Some("float unused = 1.0;".to_string()),
+            }],
+        }
+    }
+
+    #[test]
+    fn content_hash_is_stable() {
+        assert_eq!(content_hash(b""), 0xcbf29ce484222325);
+        assert_eq!(content_hash(b"kiyo"), 0xef4e7fd72124e9dd);
+    }
+
+    #[test]
+    fn strings_are_length_prefixed() {
+        let hash = |strings: &[&str]| {
+            let mut hasher = StableHasher::default();
+            strings.iter().for_each(|s| hasher.write_str(s));
+            hasher.finish()
+        };
+        assert_ne!(hash(&["ab", "c"]), hash(&["a", "bc"]));
+    }
+
+    #[test]
+    fn round_trips_entry() {
+        let dir = TestDir::new("shader-cache-round-trip");
+        let include = dir.path.join("common.glsl");
+        fs::write(&include, "float fbm( vec2 p );\n").unwrap();
+        let cache = ShaderCache::new(dir.path.join("cache"));
+
+        let mut stored = shader(&[&include]);
+        stored.include_misses = vec![dir.path.join("shaders/common.glsl")];
+        cache.store(42, &stored).unwrap();
+        let loaded = cache.load(42).unwrap();
+        assert_eq!(loaded.code, stored.code);
+        assert_eq!(loaded.entry_point, "main");
+        assert_eq!(loaded.includes, vec![include]);
+        assert_eq!(loaded.include_hashes, stored.include_hashes);
+        assert_eq!(loaded.include_misses, stored.include_misses);
+
+        assert_eq!(loaded.warnings.len(), 1);
+        let warning = &loaded.warnings[0];
+        assert_eq!(warning.severity, Severity::Warning);
+        assert_eq!((warning.file.as_str(), warning.line, warning.column), ("shaders/screen.comp", Some(5), None));
+        assert_eq!(warning.message, "'unused' : variable is unused");
+        assert_eq!(warning.source_line.as_deref(), Some("float unused = 1.0;"));
+
+        assert!(cache.load(43).is_none());
+    }
+
+    #[test]
+    fn changed_include_invalidates_entry() {
+        let dir = TestDir::new("shader-cache-include-changed");
+        let include = dir.path.join("common.glsl");
+        fs::write(&include, "float fbm( vec2 p );\n").unwrap();
+        let cache = ShaderCache::new(dir.path.join("cache"));
+        cache.store(1, &shader(&[&include])).unwrap();
+
+        fs::write(&include, "float fbm( vec3 p );\n").unwrap();
+        assert!(cache.load(1).is_none());
+
+        fs::write(&include, "float fbm( vec2 p );\n").unwrap();
+        assert!(cache.load(1).is_some());
+
+        fs::remove_file(&include).unwrap();
+        assert!(cache.load(1).is_none());
+    }
+
+    #[test]
+    fn shadowing_include_invalidates_entry() {
+        let dir = TestDir::new("shader-cache-include-shadowed");
+        fs::create_dir_all(dir.path.join("include")).unwrap();
+        let include = dir.path.join("include/common.glsl");
+        fs::write(&include, "float fbm( vec2 p );\n").unwrap();
+        let cache = ShaderCache::new(dir.path.join("cache"));
+        let mut stored = shader(&[&include]);
+        stored.include_misses = vec![dir.path.join("shaders/common.glsl")];
+        cache.store(1, &stored).unwrap();
+        assert!(cache.load(1).is_some());
+
+        // A header next to the shader is found before the include directory
+        fs::create_dir_all(dir.path.join("shaders")).unwrap();
+        fs::write(dir.path.join("shaders/common.glsl"), "float fbm( vec3 p );\n").unwrap();
+        assert!(cache.load(1).is_none());
+    }
+
+    #[test]
+    fn rejects_corrupt_entries() {
+        let dir = TestDir::new("shader-cache-corrupt");
+        let cache = ShaderCache::new(&dir.path);
+        cache.store(1, &shader(&[])).unwrap();
+        let data = fs::read(cache.entry_path(1)).unwrap();
+        assert!(decode_entry(&data).is_some());
+
+        assert!(decode_entry(&data[..data.len() - 1]).is_none());
+        assert!(decode_entry(&data[..ENTRY_MAGIC.len() + 2]).is_none());
+        let mut other_version = data.clone();
+        other_version[ENTRY_MAGIC.len() - 1] = 0;
+        assert!(decode_entry(&other_version).is_none());
+
+        fs::write(cache.entry_path(1), &data[..data.len() - 20]).unwrap();
+        assert!(cache.load(1).is_none());
+    }
+}
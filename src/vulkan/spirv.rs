@@ -0,0 +1,687 @@
+use std::collections::{HashMap, HashSet};
+use ash::vk;
+
+const MAGIC_NUMBER: u32 = 0x07230203;
+const HEADER_SIZE: usize = 5;
+/// From this version on, an entry point's interface lists every global variable it uses.
+const VERSION_1_4: u32 = 0x00010400;
+
+const OP_NAME: u32 = 5;
+const OP_ENTRY_POINT: u32 = 15;
+const OP_EXECUTION_MODE: u32 = 16;
+const OP_CONSTANT: u32 = 43;
+const OP_CONSTANT_COMPOSITE: u32 = 44;
+const OP_SPEC_CONSTANT: u32 = 50;
+const OP_SPEC_CONSTANT_COMPOSITE: u32 = 51;
+const OP_TYPE_BOOL: u32 = 20;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_IMAGE: u32 = 25;
+const OP_TYPE_SAMPLER: u32 = 26;
+const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u32 = 29;
+const OP_TYPE_STRUCT: u32 = 30;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_FUNCTION: u32 = 54;
+const OP_FUNCTION_END: u32 = 56;
+const OP_FUNCTION_CALL: u32 = 57;
+const OP_VARIABLE: u32 = 59;
+const OP_IMAGE_TEXEL_POINTER: u32 = 60;
+const OP_LOAD: u32 = 61;
+const OP_STORE: u32 = 62;
+const OP_COPY_MEMORY: u32 = 63;
+const OP_COPY_MEMORY_SIZED: u32 = 64;
+const OP_ACCESS_CHAIN: u32 = 65;
+const OP_IN_BOUNDS_ACCESS_CHAIN: u32 = 66;
+const OP_PTR_ACCESS_CHAIN: u32 = 67;
+const OP_ARRAY_LENGTH: u32 = 68;
+const OP_IN_BOUNDS_PTR_ACCESS_CHAIN: u32 = 70;
+const OP_COPY_OBJECT: u32 = 83;
+const OP_ATOMIC_LOAD: u32 = 227;
+const OP_ATOMIC_STORE: u32 = 228;
+const OP_ATOMIC_EXCHANGE: u32 = 229;
+const OP_ATOMIC_XOR: u32 = 242;
+const OP_ATOMIC_FLAG_TEST_AND_SET: u32 = 318;
+const OP_ATOMIC_FLAG_CLEAR: u32 = 319;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+const OP_EXECUTION_MODE_ID: u32 = 331;
+
+const EXECUTION_MODE_LOCAL_SIZE: u32 = 17;
+const EXECUTION_MODE_LOCAL_SIZE_ID: u32 = 38;
+
+pub const EXECUTION_MODEL_GL_COMPUTE: u32 = 5;
+
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_ARRAY_STRIDE: u32 = 6;
+const DECORATION_MATRIX_STRIDE: u32 = 7;
+const DECORATION_BUILT_IN: u32 = 11;
+const DECORATION_BINDING: u32 = 33;
+const DECORATION_DESCRIPTOR_SET: u32 = 34;
+const DECORATION_OFFSET: u32 = 35;
+const BUILT_IN_WORKGROUP_SIZE: u32 = 25;
+
+const STORAGE_CLASS_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_CLASS_UNIFORM: u32 = 2;
+const STORAGE_CLASS_PUSH_CONSTANT: u32 = 9;
+const STORAGE_CLASS_STORAGE_BUFFER: u32 = 12;
+
+const DIM_BUFFER: u32 = 5;
+const DIM_SUBPASS_DATA: u32 = 6;
+/// `Sampled` operand of an image type used as a storage image.
+const IMAGE_SAMPLED_STORAGE: u32 = 2;
+
+struct Instruction<'a> {
+    opcode: u32,
+    operands: &'a [u32],
+}
+
+/// Split a SPIR-V module into its instructions.
+fn instructions(code: &[u32]) -> Result<Vec<Instruction<'_>>, String> {
+    if code.len() < HEADER_SIZE || code[0] != MAGIC_NUMBER {
+        return Err("Invalid SPIR-V module header".to_string());
+    }
+
+    let mut instructions = Vec::new();
+    let mut offset = HEADER_SIZE;
+    while offset < code.len() {
+        let word_count = (code[offset] >> 16) as usize;
+        let opcode = code[offset] & 0xffff;
+        if word_count == 0 || offset + word_count > code.len() {
+            return Err(format!("Malformed SPIR-V instruction at word {}", offset));
+        }
+
+        instructions.push(Instruction {
+            opcode,
+            operands: &code[offset + 1..offset + word_count],
+        });
+        offset += word_count;
+    }
+
+    Ok(instructions)
+}
+
+/// The id operands of an instruction in a function which may refer to a global variable, the pointers it accesses and the arguments of calls.
+/// Literal operands are left out, as they could equal the id of a variable which isn't used.
+fn pointer_operands<'a>(instruction: &Instruction<'a>) -> &'a [u32] {
+    let operands = instruction.operands;
+    let range = match instruction.opcode {
+        OP_LOAD | OP_IMAGE_TEXEL_POINTER | OP_ARRAY_LENGTH | OP_ATOMIC_LOAD | OP_ATOMIC_EXCHANGE..=OP_ATOMIC_XOR | OP_ATOMIC_FLAG_TEST_AND_SET => 2..3,
+        OP_STORE | OP_COPY_MEMORY | OP_COPY_MEMORY_SIZED => 0..2,
+        OP_ATOMIC_STORE | OP_ATOMIC_FLAG_CLEAR => 0..1,
+        OP_ACCESS_CHAIN | OP_IN_BOUNDS_ACCESS_CHAIN | OP_PTR_ACCESS_CHAIN | OP_IN_BOUNDS_PTR_ACCESS_CHAIN | OP_COPY_OBJECT | OP_FUNCTION_CALL => 2..operands.len(),
+        _ => return &[],
+    };
+    operands.get(range).unwrap_or_default()
+}
+
+/// Decode a nul terminated literal string, packed little endian.
+fn literal_string(words: &[u32]) -> String {
+    let bytes = words.iter()
+        .flat_map(|word| word.to_le_bytes())
+        .take_while(|&b| b != 0)
+        .collect::<Vec<u8>>();
+    String::from_utf8_lossy(&bytes).to_string()
+}
+
+/// Names of the entry points with the given execution model.
+pub fn reflect_entry_points(code: &[u32], execution_model: u32) -> Result<Vec<String>, String> {
+    let names = instructions(code)?.iter()
+        .filter(|i| i.opcode == OP_ENTRY_POINT && i.operands.len() >= 3 && i.operands[0] == execution_model)
+        .map(|i| literal_string(&i.operands[2..]))
+        .collect();
+    Ok(names)
+}
+
+/// The `OpEntryPoint` of the entry point `name` with the given execution model.
+fn find_entry_point<'i, 'a>(instructions: &'i [Instruction<'a>], execution_model: u32, name: &str) -> Result<&'i Instruction<'a>, String> {
+    instructions.iter()
+        .find(|i| i.opcode == OP_ENTRY_POINT && i.operands.len() >= 3 && i.operands[0] == execution_model && literal_string(&i.operands[2..]) == name)
+        .ok_or_else(|| format!("No entry point named `{}`", name))
+}
+
+/// Read the local workgroup size of the compute entry point `entry_point`.
+/// A constant decorated with the `WorkgroupSize` built-in takes precedence over the `LocalSize` execution mode,
+/// like it does for the driver. Specialization constants resolve to their default value.
+pub fn reflect_local_size(code: &[u32], entry_point: &str) -> Result<[u32; 3], String> {
+    let instructions = instructions(code)?;
+    let function = find_entry_point(&instructions, EXECUTION_MODEL_GL_COMPUTE, entry_point)?.operands[1];
+
+    let constants = instructions.iter()
+        .filter(|i| (i.opcode == OP_CONSTANT || i.opcode == OP_SPEC_CONSTANT) && i.operands.len() >= 3)
+        .map(|i| (i.operands[1], i.operands[2]))
+        .collect::<HashMap<u32, u32>>();
+    let constant = |id: u32| constants.get(&id).copied()
+        .ok_or_else(|| format!("Local size refers to unknown constant %{}", id));
+
+    let workgroup_size_id = instructions.iter()
+        .find(|i| i.opcode == OP_DECORATE && i.operands.get(1..3) == Some(&[DECORATION_BUILT_IN, BUILT_IN_WORKGROUP_SIZE]))
+        .map(|i| i.operands[0]);
+    if let Some(id) = workgroup_size_id {
+        let composite = instructions.iter()
+            .find(|i| (i.opcode == OP_CONSTANT_COMPOSITE || i.opcode == OP_SPEC_CONSTANT_COMPOSITE) && i.operands.get(1) == Some(&id))
+            .filter(|i| i.operands.len() == 5)
+            .ok_or_else(|| "WorkgroupSize built-in isn't a constant vector".to_string())?;
+        return Ok([constant(composite.operands[2])?, constant(composite.operands[3])?, constant(composite.operands[4])?]);
+    }
+
+    // Other entry points of the module may declare another size
+    for i in &instructions {
+        match (i.opcode, i.operands) {
+            (OP_EXECUTION_MODE, &[target, EXECUTION_MODE_LOCAL_SIZE, x, y, z]) if target == function => {
+                return Ok([x, y, z]);
+            },
+            (OP_EXECUTION_MODE_ID, &[target, EXECUTION_MODE_LOCAL_SIZE_ID, x, y, z]) if target == function => {
+                return Ok([constant(x)?, constant(y)?, constant(z)?]);
+            },
+            _ => {}
+        }
+    }
+
+    Err("Shader doesn't declare a local workgroup size".to_string())
+}
+
+/// A descriptor declared by a shader.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DescriptorBinding {
+    /// Name of the variable, or of its block when the variable has none. Empty when the shader was stripped.
+    pub name: String,
+    pub set: u32,
+    pub binding: u32,
+    pub descriptor_type: vk::DescriptorType,
+    /// Amount of descriptors, `None` for a runtime sized array.
+    pub count: Option<u32>,
+}
+
+/// The types, constants, names and decorations of a module, to look up what its variables are.
+/// Specialization constants resolve to their default value.
+struct Module<'a> {
+    version: u32,
+    instructions: Vec<Instruction<'a>>,
+    constants: HashMap<u32, u32>,
+}
+
+impl<'a> Module<'a> {
+    fn new(code: &'a [u32]) -> Result<Module<'a>, String> {
+        let instructions = instructions(code)?;
+        let constants = instructions.iter()
+            .filter(|i| (i.opcode == OP_CONSTANT || i.opcode == OP_SPEC_CONSTANT) && i.operands.len() >= 3)
+            .map(|i| (i.operands[1], i.operands[2]))
+            .collect();
+        Ok(Module { version: code[1], instructions, constants })
+    }
+
+    /// The instruction defining `id`, types and constants have their result id first, variables second.
+    fn definition(&self, id: u32) -> Option<&Instruction<'a>> {
+        self.instructions.iter().find(|i| match i.opcode {
+            OP_VARIABLE | OP_CONSTANT | OP_SPEC_CONSTANT => i.operands.get(1) == Some(&id),
+            OP_TYPE_BOOL..=OP_TYPE_POINTER => i.operands.first() == Some(&id),
+            _ => false,
+        })
+    }
+
+    fn decoration(&self, id: u32, decoration: u32) -> Option<&'a [u32]> {
+        self.instructions.iter()
+            .find(|i| i.opcode == OP_DECORATE && i.operands.len() >= 2 && i.operands[0] == id && i.operands[1] == decoration)
+            .map(|i| &i.operands[2..])
+    }
+
+    fn member_decoration(&self, id: u32, member: u32, decoration: u32) -> Option<&'a [u32]> {
+        self.instructions.iter()
+            .find(|i| i.opcode == OP_MEMBER_DECORATE && i.operands.len() >= 3 && i.operands[..3] == [id, member, decoration])
+            .map(|i| &i.operands[3..])
+    }
+
+    fn name(&self, id: u32) -> Option<String> {
+        self.instructions.iter()
+            .find(|i| i.opcode == OP_NAME && i.operands.first() == Some(&id))
+            .map(|i| literal_string(&i.operands[1..]))
+            .filter(|name| !name.is_empty())
+    }
+
+    /// Ids of the global variables the compute entry point `name` may use.
+    /// Before SPIR-V 1.4 its interface only lists inputs and outputs, so the pointers accessed by the functions it calls are collected instead.
+    fn entry_point_variables(&self, name: &str) -> Result<HashSet<u32>, String> {
+        let entry_point = find_entry_point(&self.instructions, EXECUTION_MODEL_GL_COMPUTE, name)?;
+        if self.version >= VERSION_1_4 {
+            let name_words = entry_point.operands[2..].iter()
+                .position(|word| word.to_le_bytes().contains(&0))
+                .map_or(entry_point.operands.len() - 2, |i| i + 1);
+            return Ok(entry_point.operands[2 + name_words..].iter().copied().collect());
+        }
+
+        let mut functions = HashMap::new();
+        let mut start = None;
+        for (index, i) in self.instructions.iter().enumerate() {
+            match (i.opcode, start) {
+                (OP_FUNCTION, _) => start = i.operands.get(1).map(|&id| (id, index)),
+                (OP_FUNCTION_END, Some((id, first))) => {
+                    functions.insert(id, &self.instructions[first..index]);
+                    start = None;
+                },
+                _ => {},
+            }
+        }
+
+        let mut used = HashSet::new();
+        let mut visited = HashSet::new();
+        let mut pending = vec![entry_point.operands[1]];
+        while let Some(function) = pending.pop() {
+            if !visited.insert(function) {
+                continue;
+            }
+            for i in functions.get(&function).copied().unwrap_or_default() {
+                if i.opcode == OP_FUNCTION_CALL && i.operands.len() >= 3 {
+                    pending.push(i.operands[2]);
+                }
+                used.extend(pointer_operands(i));
+            }
+        }
+        Ok(used)
+    }
+
+    fn type_definition(&self, id: u32) -> Result<&Instruction<'a>, String> {
+        self.definition(id).ok_or_else(|| format!("Unknown type %{}", id))
+    }
+
+    /// Size in bytes of a type in a block, following its explicit layout decorations.
+    fn size(&self, type_id: u32) -> Result<u32, String> {
+        let definition = self.type_definition(type_id)?;
+        match (definition.opcode, definition.operands) {
+            (OP_TYPE_BOOL, _) => Ok(4),
+            (OP_TYPE_INT | OP_TYPE_FLOAT, &[_, width, ..]) => Ok(width / 8),
+            (OP_TYPE_VECTOR, &[_, component, count]) => Ok(self.size(component)? * count),
+            (OP_TYPE_MATRIX, &[_, column, count]) => Ok(self.size(column)? * count),
+            (OP_TYPE_ARRAY, &[_, element, length]) => {
+                let length = self.constants.get(&length).copied()
+                    .ok_or_else(|| format!("Array length refers to unknown constant %{}", length))?;
+                let stride = match self.decoration(type_id, DECORATION_ARRAY_STRIDE) {
+                    Some(&[stride]) => stride,
+                    _ => self.size(element)?,
+                };
+                Ok(stride * length)
+            },
+            (OP_TYPE_STRUCT, members) => {
+                let mut size = 0;
+                for (member, &member_type) in members[1..].iter().enumerate() {
+                    let offset = match self.member_decoration(type_id, member as u32, DECORATION_OFFSET) {
+                        Some(&[offset]) => offset,
+                        _ => size,
+                    };
+                    // Matrix columns are padded to their stride
+                    let member_size = match (self.type_definition(member_type)?, self.member_decoration(type_id, member as u32, DECORATION_MATRIX_STRIDE)) {
+                        (Instruction { opcode: OP_TYPE_MATRIX, operands: &[_, _, count] }, Some(&[stride])) => stride * count,
+                        _ => self.size(member_type)?,
+                    };
+                    size = size.max(offset + member_size);
+                }
+                Ok(size)
+            },
+            _ => Err(format!("Type %{} has no size", type_id)),
+        }
+    }
+}
+
+/// The descriptors used by the compute entry point `entry_point`, with the descriptor type their variable's type corresponds to.
+pub fn reflect_descriptor_bindings(code: &[u32], entry_point: &str) -> Result<Vec<DescriptorBinding>, String> {
+    let module = Module::new(code)?;
+    let variables = module.entry_point_variables(entry_point)?;
+
+    let mut bindings = Vec::new();
+    for variable in module.instructions.iter().filter(|i| i.opcode == OP_VARIABLE && i.operands.len() >= 3 && variables.contains(&i.operands[1])) {
+        let (pointer_type, id, storage_class) = (variable.operands[0], variable.operands[1], variable.operands[2]);
+        if ![STORAGE_CLASS_UNIFORM_CONSTANT, STORAGE_CLASS_UNIFORM, STORAGE_CLASS_STORAGE_BUFFER].contains(&storage_class) {
+            continue;
+        }
+        let (set, binding) = match (module.decoration(id, DECORATION_DESCRIPTOR_SET), module.decoration(id, DECORATION_BINDING)) {
+            (Some(&[set]), Some(&[binding])) => (set, binding),
+            (None, Some(&[binding])) => (0, binding),
+            _ => continue,
+        };
+
+        let mut type_id = match module.type_definition(pointer_type)?.operands {
+            &[_, _, pointee] => pointee,
+            _ => return Err(format!("Variable %{} isn't a pointer", id)),
+        };
+        let mut count = Some(1);
+        let definition = module.type_definition(type_id)?;
+        match (definition.opcode, definition.operands) {
+            (OP_TYPE_ARRAY, &[_, element, length]) => {
+                count = Some(module.constants.get(&length).copied()
+                    .ok_or_else(|| format!("Array length refers to unknown constant %{}", length))?);
+                type_id = element;
+            },
+            (OP_TYPE_RUNTIME_ARRAY, &[_, element]) => {
+                count = None;
+                type_id = element;
+            },
+            _ => {},
+        }
+
+        let definition = module.type_definition(type_id)?;
+        let descriptor_type = match (definition.opcode, definition.operands) {
+            (OP_TYPE_IMAGE, &[_, _, DIM_BUFFER, _, _, _, IMAGE_SAMPLED_STORAGE, ..]) => vk::DescriptorType::STORAGE_TEXEL_BUFFER,
+            (OP_TYPE_IMAGE, &[_, _, DIM_BUFFER, ..]) => vk::DescriptorType::UNIFORM_TEXEL_BUFFER,
+            (OP_TYPE_IMAGE, &[_, _, DIM_SUBPASS_DATA, ..]) => vk::DescriptorType::INPUT_ATTACHMENT,
+            (OP_TYPE_IMAGE, &[_, _, _, _, _, _, IMAGE_SAMPLED_STORAGE, ..]) => vk::DescriptorType::STORAGE_IMAGE,
+            (OP_TYPE_IMAGE, _) => vk::DescriptorType::SAMPLED_IMAGE,
+            (OP_TYPE_SAMPLER, _) => vk::DescriptorType::SAMPLER,
+            (OP_TYPE_SAMPLED_IMAGE, _) => vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            (OP_TYPE_STRUCT, _) if storage_class == STORAGE_CLASS_STORAGE_BUFFER || module.decoration(type_id, DECORATION_BUFFER_BLOCK).is_some() => {
+                vk::DescriptorType::STORAGE_BUFFER
+            },
+            (OP_TYPE_STRUCT, _) => vk::DescriptorType::UNIFORM_BUFFER,
+            // Acceleration structures and other descriptors kiyo doesn't bind
+            _ => continue,
+        };
+
+        bindings.push(DescriptorBinding {
+            name: module.name(id).or_else(|| module.name(type_id)).unwrap_or_default(),
+            set,
+            binding,
+            descriptor_type,
+            count,
+        });
+    }
+    Ok(bindings)
+}
+
+/// Size in bytes of the push constant block used by the compute entry point `entry_point`, up to the end of its last member.
+/// `None` when it uses none.
+pub fn reflect_push_constant_size(code: &[u32], entry_point: &str) -> Result<Option<u32>, String> {
+    let module = Module::new(code)?;
+    let variables = module.entry_point_variables(entry_point)?;
+
+    // An entry point uses at most one push constant block
+    let variable = module.instructions.iter()
+        .find(|i| i.opcode == OP_VARIABLE && i.operands.get(2) == Some(&STORAGE_CLASS_PUSH_CONSTANT) && variables.contains(&i.operands[1]));
+    match variable {
+        Some(variable) => match module.type_definition(variable.operands[0])?.operands {
+            &[_, _, pointee] => module.size(pointee).map(Some),
+            _ => Err(format!("Variable %{} isn't a pointer", variable.operands[1])),
+        },
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vulkan::pipeline::compile_shader_source;
+
+    const OP_TYPE_VOID: u32 = 19;
+    const OP_TYPE_FUNCTION: u32 = 33;
+    const OP_COMPOSITE_EXTRACT: u32 = 81;
+
+    /// Encode an instruction, with its word count in the high half of the first word.
+    fn op(opcode: u32, operands: &[u32]) -> Vec<u32> {
+        let mut words = vec![((operands.len() as u32 + 1) << 16) | opcode];
+        words.extend_from_slice(operands);
+        words
+    }
+
+    /// Encode a nul terminated literal string.
+    fn string(s: &str) -> Vec<u32> {
+        let mut bytes = s.as_bytes().to_vec();
+        bytes.resize(s.len() / 4 * 4 + 4, 0);
+        bytes.chunks(4).map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]])).collect()
+    }
+
+    fn module(instructions: &[Vec<u32>]) -> Vec<u32> {
+        let mut code = vec![MAGIC_NUMBER, 0x00010000, 0, 100, 0];
+        code.extend(instructions.iter().flatten());
+        code
+    }
+
+    /// A compute entry point `main` with function id 1, and a `uint` type with id 2.
+    fn compute_module(instructions: &[Vec<u32>]) -> Vec<u32> {
+        let mut all = vec![op(OP_ENTRY_POINT, &[[EXECUTION_MODEL_GL_COMPUTE, 1].as_slice(), &string("main")].concat())];
+        all.extend_from_slice(instructions);
+        all.push(op(OP_TYPE_INT, &[2, 32, 0]));
+        module(&all)
+    }
+
+    #[test]
+    fn rejects_invalid_header() {
+        assert_eq!(instructions(&[MAGIC_NUMBER, 0x00010000]).err().unwrap(), "Invalid SPIR-V module header");
+        assert_eq!(instructions(&[0x03022307, 0x00010000, 0, 1, 0]).err().unwrap(), "Invalid SPIR-V module header");
+        assert!(reflect_local_size(&[], "main").is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_instruction() {
+        let mut code = module(&[op(OP_TYPE_INT, &[2, 32, 0])]);
+        code.pop();
+        assert_eq!(instructions(&code).err().unwrap(), "Malformed SPIR-V instruction at word 5");
+
+        let code = module(&[op(OP_TYPE_INT, &[2, 32, 0]), vec![OP_TYPE_BOOL]]);
+        assert_eq!(instructions(&code).err().unwrap(), "Malformed SPIR-V instruction at word 9");
+    }
+
+    #[test]
+    fn splits_instructions() {
+        let code = module(&[op(OP_TYPE_VOID, &[3]), op(OP_TYPE_FUNCTION, &[4, 3])]);
+        let instructions = instructions(&code).unwrap();
+        assert_eq!(instructions.len(), 2);
+        assert_eq!((instructions[1].opcode, instructions[1].operands), (OP_TYPE_FUNCTION, [4, 3].as_slice()));
+    }
+
+    #[test]
+    fn reads_entry_points() {
+        let code = module(&[
+            op(OP_ENTRY_POINT, &[[EXECUTION_MODEL_GL_COMPUTE, 1].as_slice(), &string("blur")].concat()),
+            op(OP_ENTRY_POINT, &[[0, 2].as_slice(), &string("vertex")].concat()),
+            op(OP_ENTRY_POINT, &[[EXECUTION_MODEL_GL_COMPUTE, 3].as_slice(), &string("main")].concat()),
+        ]);
+        assert_eq!(reflect_entry_points(&code, EXECUTION_MODEL_GL_COMPUTE).unwrap(), ["blur", "main"]);
+    }
+
+    #[test]
+    fn reads_local_size() {
+        let code = compute_module(&[op(OP_EXECUTION_MODE, &[1, EXECUTION_MODE_LOCAL_SIZE, 16, 8, 1])]);
+        assert_eq!(reflect_local_size(&code, "main").unwrap(), [16, 8, 1]);
+    }
+
+    #[test]
+    fn reads_local_size_id() {
+        let code = compute_module(&[
+            op(OP_EXECUTION_MODE_ID, &[1, EXECUTION_MODE_LOCAL_SIZE_ID, 10, 11, 12]),
+            op(OP_CONSTANT, &[2, 10, 32]),
+            op(OP_SPEC_CONSTANT, &[2, 11, 4]),
+            op(OP_CONSTANT, &[2, 12, 1]),
+        ]);
+        assert_eq!(reflect_local_size(&code, "main").unwrap(), [32, 4, 1]);
+
+        let code = compute_module(&[op(OP_EXECUTION_MODE_ID, &[1, EXECUTION_MODE_LOCAL_SIZE_ID, 10, 11, 12])]);
+        assert_eq!(reflect_local_size(&code, "main").err().unwrap(), "Local size refers to unknown constant %10");
+    }
+
+    #[test]
+    fn workgroup_size_takes_precedence() {
+        let code = compute_module(&[
+            op(OP_EXECUTION_MODE, &[1, EXECUTION_MODE_LOCAL_SIZE, 16, 16, 1]),
+            op(OP_DECORATE, &[20, DECORATION_BUILT_IN, BUILT_IN_WORKGROUP_SIZE]),
+            op(OP_CONSTANT, &[2, 10, 64]),
+            op(OP_SPEC_CONSTANT, &[2, 11, 2]),
+            op(OP_CONSTANT, &[2, 12, 1]),
+            op(OP_SPEC_CONSTANT_COMPOSITE, &[21, 20, 10, 11, 12]),
+        ]);
+        assert_eq!(reflect_local_size(&code, "main").unwrap(), [64, 2, 1]);
+
+        let code = compute_module(&[
+            op(OP_DECORATE, &[20, DECORATION_BUILT_IN, BUILT_IN_WORKGROUP_SIZE]),
+            op(OP_CONSTANT, &[2, 20, 64]),
+        ]);
+        assert_eq!(reflect_local_size(&code, "main").err().unwrap(), "WorkgroupSize built-in isn't a constant vector");
+    }
+
+    #[test]
+    fn reads_local_size_of_entry_point() {
+        let code = module(&[
+            op(OP_ENTRY_POINT, &[[EXECUTION_MODEL_GL_COMPUTE, 1].as_slice(), &string("blur_horizontal")].concat()),
+            op(OP_ENTRY_POINT, &[[EXECUTION_MODEL_GL_COMPUTE, 3].as_slice(), &string("blur_vertical")].concat()),
+            op(OP_EXECUTION_MODE, &[1, EXECUTION_MODE_LOCAL_SIZE, 64, 1, 1]),
+            op(OP_EXECUTION_MODE, &[3, EXECUTION_MODE_LOCAL_SIZE, 1, 64, 1]),
+        ]);
+        assert_eq!(reflect_local_size(&code, "blur_horizontal").unwrap(), [64, 1, 1]);
+        assert_eq!(reflect_local_size(&code, "blur_vertical").unwrap(), [1, 64, 1]);
+        assert_eq!(reflect_local_size(&code, "main").err().unwrap(), "No entry point named `main`");
+    }
+
+    #[test]
+    fn requires_local_size() {
+        let code = compute_module(&[]);
+        assert_eq!(reflect_local_size(&code, "main").err().unwrap(), "Shader doesn't declare a local workgroup size");
+    }
+
+    fn compile(source: &str) -> Vec<u32> {
+        compile_shader_source(source, shaderc::ShaderKind::Compute, "test.comp", &HashMap::new()).unwrap()
+    }
+
+    fn binding(name: &str, set: u32, binding: u32, descriptor_type: vk::DescriptorType, count: Option<u32>) -> DescriptorBinding {
+        DescriptorBinding { name: name.to_string(), set, binding, descriptor_type, count }
+    }
+
+    fn bindings(code: &[u32]) -> Vec<DescriptorBinding> {
+        let mut bindings = reflect_descriptor_bindings(code, "main").unwrap();
+        bindings.sort_by_key(|b| (b.set, b.binding));
+        bindings
+    }
+
+    #[test]
+    fn reflects_descriptor_bindings() {
+        let code = compile("#version 450
+            layout( local_size_x = 16, local_size_y = 16 ) in;
+            layout( binding = 0, rgba8 ) uniform image2D out_image;
+            layout( binding = 2 ) uniform Uniforms { float time; } uniforms;
+            layout( binding = 3 ) buffer Particles { vec4 positions[]; } particles;
+            layout( set = 1, binding = 1 ) uniform sampler2D textures[4];
+            void main()
+            {
+                vec4 c = texture( textures[1], vec2( 0.5 ) ) * uniforms.time + particles.positions[0];
+                imageStore( out_image, ivec2( gl_GlobalInvocationID.xy ), c );
+            }");
+        assert_eq!(bindings(&code), [
+            binding("out_image", 0, 0, vk::DescriptorType::STORAGE_IMAGE, Some(1)),
+            binding("uniforms", 0, 2, vk::DescriptorType::UNIFORM_BUFFER, Some(1)),
+            binding("particles", 0, 3, vk::DescriptorType::STORAGE_BUFFER, Some(1)),
+            binding("textures", 1, 1, vk::DescriptorType::COMBINED_IMAGE_SAMPLER, Some(4)),
+        ]);
+        assert_eq!(reflect_push_constant_size(&code, "main").unwrap(), None);
+    }
+
+    #[test]
+    fn reflects_push_constant_size() {
+        let code = compile("#version 450
+            layout( local_size_x = 64 ) in;
+            layout( push_constant ) uniform PushConstants { mat4 transform; vec3 offset; uint frame; } push;
+            layout( binding = 0 ) buffer Points { vec4 points[]; };
+            void main()
+            {
+                points[gl_GlobalInvocationID.x] = push.transform * vec4( push.offset, float( push.frame ) );
+            }");
+        assert_eq!(reflect_push_constant_size(&code, "main").unwrap(), Some(80));
+    }
+
+    #[test]
+    fn reflects_spec_constant_array_lengths() {
+        let code = compile("#version 450
+            layout( local_size_x = 64 ) in;
+            layout( constant_id = 0 ) const uint COUNT = 3;
+            layout( binding = 0 ) uniform sampler2D textures[COUNT];
+            layout( binding = 1, rgba8 ) uniform writeonly image2D out_image;
+            layout( push_constant ) uniform PushConstants { vec4 weights[COUNT]; float scale; } push;
+            void main()
+            {
+                vec4 c = vec4( 0.0 );
+                for( uint i = 0; i < COUNT; i++ ) c += texture( textures[i], vec2( 0.5 ) ) * push.weights[i];
+                imageStore( out_image, ivec2( gl_GlobalInvocationID.xy ), c * push.scale );
+            }");
+        assert_eq!(bindings(&code)[0], binding("textures", 0, 0, vk::DescriptorType::COMBINED_IMAGE_SAMPLER, Some(3)));
+        assert_eq!(reflect_push_constant_size(&code, "main").unwrap(), Some(52));
+    }
+
+    #[test]
+    fn skips_variables_the_entry_point_doesnt_use() {
+        // Shaders are compiled for SPIR-V 1.0, so the functions `main` calls are walked
+        let code = compile("#version 450
+            layout( local_size_x = 64 ) in;
+            layout( binding = 0 ) buffer Used { uint used[]; };
+            layout( binding = 1 ) buffer Unused { uint unused[]; };
+            layout( push_constant ) uniform PushConstants { vec4 color; } push;
+            void write( uint i )
+            {
+                used[i] = i;
+            }
+            void main()
+            {
+                write( gl_GlobalInvocationID.x );
+            }");
+        assert_eq!(bindings(&code), [binding("Used", 0, 0, vk::DescriptorType::STORAGE_BUFFER, Some(1))]);
+        assert_eq!(reflect_push_constant_size(&code, "main").unwrap(), None);
+    }
+
+    /// Compute entry points `a` with function id 1 and `b` with function id 3, `a` uses binding 0 and `b` binding 1 through function 4.
+    fn two_entry_points(version: u32) -> Vec<u32> {
+        let mut code = module(&[
+            op(OP_ENTRY_POINT, &[[EXECUTION_MODEL_GL_COMPUTE, 1].as_slice(), &string("a"), &[10]].concat()),
+            op(OP_ENTRY_POINT, &[[EXECUTION_MODEL_GL_COMPUTE, 3].as_slice(), &string("b"), &[11]].concat()),
+            op(OP_DECORATE, &[10, DECORATION_BINDING, 0]),
+            op(OP_DECORATE, &[11, DECORATION_BINDING, 1]),
+            op(OP_TYPE_VOID, &[2]),
+            op(OP_TYPE_SAMPLER, &[5]),
+            op(OP_TYPE_POINTER, &[6, STORAGE_CLASS_UNIFORM_CONSTANT, 5]),
+            op(OP_TYPE_FUNCTION, &[7, 2]),
+            op(OP_VARIABLE, &[6, 10, STORAGE_CLASS_UNIFORM_CONSTANT]),
+            op(OP_VARIABLE, &[6, 11, STORAGE_CLASS_UNIFORM_CONSTANT]),
+            op(OP_FUNCTION, &[2, 1, 0, 7]),
+            op(OP_LOAD, &[5, 20, 10]),
+            op(OP_FUNCTION_END, &[]),
+            op(OP_FUNCTION, &[2, 3, 0, 7]),
+            op(OP_FUNCTION_CALL, &[2, 21, 4]),
+            op(OP_FUNCTION_END, &[]),
+            op(OP_FUNCTION, &[2, 4, 0, 7]),
+            op(OP_LOAD, &[5, 22, 11]),
+            op(OP_FUNCTION_END, &[]),
+        ]);
+        code[1] = version;
+        code
+    }
+
+    #[test]
+    fn reflects_descriptors_of_entry_point() {
+        for version in [0x00010000, VERSION_1_4] {
+            let code = two_entry_points(version);
+            let sampler = |b| binding("", 0, b, vk::DescriptorType::SAMPLER, Some(1));
+            assert_eq!(reflect_descriptor_bindings(&code, "a").unwrap(), [sampler(0)]);
+            assert_eq!(reflect_descriptor_bindings(&code, "b").unwrap(), [sampler(1)]);
+            assert_eq!(reflect_descriptor_bindings(&code, "main").err().unwrap(), "No entry point named `main`");
+        }
+    }
+
+    #[test]
+    fn skips_variable_matching_literal() {
+        // Variable 12 isn't used, but equals the literal index of an `OpCompositeExtract`
+        let code = module(&[
+            op(OP_ENTRY_POINT, &[[EXECUTION_MODEL_GL_COMPUTE, 1].as_slice(), &string("main")].concat()),
+            op(OP_DECORATE, &[10, DECORATION_BINDING, 0]),
+            op(OP_DECORATE, &[12, DECORATION_BINDING, 7]),
+            op(OP_TYPE_VOID, &[2]),
+            op(OP_TYPE_FLOAT, &[3, 32]),
+            op(OP_TYPE_VECTOR, &[4, 3, 4]),
+            op(OP_TYPE_STRUCT, &[5, 4]),
+            op(OP_TYPE_POINTER, &[6, STORAGE_CLASS_UNIFORM, 5]),
+            op(OP_TYPE_FUNCTION, &[7, 2]),
+            op(OP_VARIABLE, &[6, 10, STORAGE_CLASS_UNIFORM]),
+            op(OP_VARIABLE, &[6, 12, STORAGE_CLASS_UNIFORM]),
+            op(OP_FUNCTION, &[2, 1, 0, 7]),
+            op(OP_LOAD, &[5, 20, 10]),
+            op(OP_COMPOSITE_EXTRACT, &[4, 21, 20, 12]),
+            op(OP_FUNCTION_END, &[]),
+        ]);
+        assert_eq!(reflect_descriptor_bindings(&code, "main").unwrap(), [binding("", 0, 0, vk::DescriptorType::UNIFORM_BUFFER, Some(1))]);
+    }
+}
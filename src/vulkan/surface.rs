@@ -2,6 +2,7 @@ use ash::khr::surface;
 use ash::vk;
 use ash::vk::{PresentModeKHR, SurfaceCapabilitiesKHR, SurfaceKHR};
 use crate::app::Window;
+use crate::Error;
 use crate::vulkan::Instance;
 
 /// A presentation surface for rendering graphics to a window.
@@ -11,7 +12,7 @@ pub struct Surface {
 }
 
 impl Surface {
-    pub fn new(entry: &ash::Entry, instance: &Instance, window: &Window) -> Surface {
+    pub fn new(entry: &ash::Entry, instance: &Instance, window: &Window) -> Result<Surface, Error> {
         let surface_loader = surface::Instance::new(&entry, instance.handle());
 
         let surface = unsafe {
@@ -21,29 +22,32 @@ impl Surface {
                 window.display_handle(),
                 window.window_handle(),
                 None,
-            ).expect("Failed to get surface.")
+            ).map_err(Error::vulkan("Failed to create surface"))?
         };
 
-        Surface {
+        Ok(Surface {
             surface,
             surface_loader,
-        }
+        })
     }
 
     pub fn handle(&self) -> &SurfaceKHR {
         &self.surface
     }
 
-    pub fn get_formats(&self, physical_device: &vk::PhysicalDevice) -> Vec<vk::SurfaceFormatKHR> {
-        unsafe { self.surface_loader.get_physical_device_surface_formats(*physical_device, self.surface).unwrap() }
+    pub fn get_formats(&self, physical_device: &vk::PhysicalDevice) -> Result<Vec<vk::SurfaceFormatKHR>, Error> {
+        unsafe { self.surface_loader.get_physical_device_surface_formats(*physical_device, self.surface) }
+            .map_err(Error::vulkan("Failed to get surface formats"))
     }
 
-    pub fn get_present_modes(&self, physical_device: &vk::PhysicalDevice) -> Vec<PresentModeKHR> {
-        unsafe { self.surface_loader.get_physical_device_surface_present_modes(*physical_device, self.surface).unwrap() }
+    pub fn get_present_modes(&self, physical_device: &vk::PhysicalDevice) -> Result<Vec<PresentModeKHR>, Error> {
+        unsafe { self.surface_loader.get_physical_device_surface_present_modes(*physical_device, self.surface) }
+            .map_err(Error::vulkan("Failed to get surface present modes"))
     }
 
-    pub fn get_surface_capabilities(&self, physical_device: &vk::PhysicalDevice) -> SurfaceCapabilitiesKHR {
-        unsafe { self.surface_loader.get_physical_device_surface_capabilities(*physical_device, self.surface).unwrap() }
+    pub fn get_surface_capabilities(&self, physical_device: &vk::PhysicalDevice) -> Result<SurfaceCapabilitiesKHR, Error> {
+        unsafe { self.surface_loader.get_physical_device_surface_capabilities(*physical_device, self.surface) }
+            .map_err(Error::vulkan("Failed to get surface capabilities"))
     }
 
 }
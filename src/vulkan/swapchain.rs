@@ -4,6 +4,7 @@ use ash::vk;
 use ash::vk::{CompositeAlphaFlagsKHR, ImageUsageFlags, PresentModeKHR, SharingMode, SurfaceFormatKHR, SwapchainKHR};
 use log::info;
 use crate::app::Window;
+use crate::Error;
 use crate::vulkan::{Device, Instance, Surface};
 use crate::vulkan::device::DeviceInner;
 
@@ -16,7 +17,8 @@ pub struct SwapchainInner {
     images: Vec<vk::Image>,
     image_views: Vec<vk::ImageView>,
     extent: vk::Extent2D,
-    format: SurfaceFormatKHR
+    format: SurfaceFormatKHR,
+    present_mode: PresentModeKHR,
 }
 
 impl Drop for SwapchainInner {
@@ -35,27 +37,31 @@ pub struct Swapchain {
 }
 
 impl Swapchain {
+    /// Create a swapchain matching the current size of the surface.
+    /// Pass the swapchain which is being replaced as `old_swapchain`, so presentation can continue while it's recreated.
     pub fn new(
         instance: &Instance,
         physical_device: &vk::PhysicalDevice,
         device: &Device,
         window: &Window,
         surface: &Surface,
-        preferred_present_mode: PresentModeKHR
-    ) -> Swapchain {
+        preferred_present_mode: PresentModeKHR,
+        old_swapchain: Option<&Swapchain>
+    ) -> Result<Swapchain, Error> {
         let swapchain_loader = swapchain::Device::new(instance.handle(), device.handle());
 
-        let available_formats = surface.get_formats(physical_device);
+        let available_formats = surface.get_formats(physical_device)?;
         let surface_format = available_formats.iter()
             .find(|f| f == &&vk::SurfaceFormatKHR {
                 format: vk::Format::R8G8B8A8_UNORM,
                 color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
             })
-            .unwrap_or(available_formats.first().expect("No surface format found"));
+            .or(available_formats.first())
+            .ok_or_else(|| Error::Device("The surface supports no formats".to_string()))?;
 
         info!("Using surface format: {:?}", surface_format);
 
-        let surface_capabilities = surface.get_surface_capabilities(physical_device);
+        let surface_capabilities = surface.get_surface_capabilities(physical_device)?;
 
         let mut desired_image_count = surface_capabilities.min_image_count + 1;
         // Max image count can be 0
@@ -69,7 +75,7 @@ impl Swapchain {
             surface_capabilities.current_transform
         };
 
-        let present_modes = surface.get_present_modes(physical_device);
+        let present_modes = surface.get_present_modes(physical_device)?;
         let present_mode = present_modes
             .iter()
             .cloned()
@@ -93,13 +99,27 @@ impl Swapchain {
             .min_image_count(desired_image_count)
             .surface(*surface.handle())
             .clipped(true)
-            .image_array_layers(1);
+            .image_array_layers(1)
+            .old_swapchain(old_swapchain.map_or(vk::SwapchainKHR::null(), |s| s.handle()));
 
-        let swapchain = unsafe { swapchain_loader.create_swapchain(&create_info, None).unwrap() };
+        let swapchain = unsafe { swapchain_loader.create_swapchain(&create_info, None) }
+            .map_err(Error::vulkan("Failed to create swapchain"))?;
 
-        let images = unsafe { swapchain_loader.get_swapchain_images(swapchain).unwrap() };
+        // From here on the swapchain is destroyed by `drop` when a step fails
+        let mut swapchain_inner = SwapchainInner {
+            device_dep: device.inner.clone(),
+            swapchain_loader,
+            swapchain,
+            images: Vec::new(),
+            image_views: Vec::new(),
+            extent,
+            format: *surface_format,
+            present_mode,
+        };
+
+        let images = unsafe { swapchain_inner.swapchain_loader.get_swapchain_images(swapchain) }
+            .map_err(Error::vulkan("Failed to get swapchain images"))?;
 
-        let mut image_views = Vec::new();
         for &image in images.iter() {
             let image_view_create_info = vk::ImageViewCreateInfo::default()
                 .flags(vk::ImageViewCreateFlags::empty())
@@ -120,23 +140,15 @@ impl Swapchain {
                 })
                 .image(image);
 
-            let imageview = unsafe { device.handle().create_image_view(&image_view_create_info, None).unwrap() };
-            image_views.push(imageview);
+            let imageview = unsafe { device.handle().create_image_view(&image_view_create_info, None) }
+                .map_err(Error::vulkan("Failed to create swapchain image view"))?;
+            swapchain_inner.image_views.push(imageview);
         }
+        swapchain_inner.images = images;
 
-        let swapchain_inner = SwapchainInner {
-            device_dep: device.inner.clone(),
-            swapchain_loader,
-            swapchain,
-            images,
-            image_views,
-            extent,
-            format: *surface_format
-        };
-
-        Self {
+        Ok(Self {
             inner: Arc::new(swapchain_inner)
-        }
+        })
     }
 
     pub fn get_images(&self) -> &Vec<vk::Image> {
@@ -159,6 +171,10 @@ impl Swapchain {
         self.inner.format
     }
 
+    pub fn get_present_mode(&self) -> PresentModeKHR {
+        self.inner.present_mode
+    }
+
     pub fn handle(&self) -> SwapchainKHR {
         self.inner.swapchain
     }
@@ -166,8 +182,10 @@ impl Swapchain {
     /// Queue an image for presentation.
     ///
     /// - `semaphore` - A semapore to wait on before issuing the present info.
+    ///
+    /// Returns whether the swapchain is suboptimal, `ERROR_OUT_OF_DATE_KHR` means it has to be recreated.
     /// https://registry.khronos.org/vulkan/specs/1.3-extensions/man/html/vkQueuePresentKHR.html
-    pub fn queue_present(&self, queue: vk::Queue, wait_semaphore: vk::Semaphore, image_index: u32) {
+    pub fn queue_present(&self, queue: vk::Queue, wait_semaphore: vk::Semaphore, image_index: u32) -> Result<bool, vk::Result> {
         let mut result = [vk::Result::SUCCESS];
         unsafe {
             let swapchains = [self.handle()];
@@ -179,25 +197,24 @@ impl Swapchain {
                 .image_indices(&indices)
                 .results(&mut result);
             self.inner.swapchain_loader.queue_present(queue, &present_info)
-                .expect("Failed to present queue");
         }
     }
 
     /// Acquire the next image in the swapchain.
     /// * `semaphore` - A semaphore to signal when the image is available.
     ///
+    /// Returns the image index and whether the swapchain is suboptimal.
+    /// On `ERROR_OUT_OF_DATE_KHR` no image is acquired and the swapchain has to be recreated.
     /// https://registry.khronos.org/vulkan/specs/1.3-extensions/man/html/vkAcquireNextImageKHR.html
-    pub fn acquire_next_image(&self, semaphore: vk::Semaphore) -> u32 {
+    pub fn acquire_next_image(&self, semaphore: vk::Semaphore) -> Result<(u32, bool), vk::Result> {
         unsafe {
-            let (image_index, _) = self.inner.swapchain_loader
+            self.inner.swapchain_loader
                 .acquire_next_image(
                     self.handle(),
                     u64::MAX,
                     semaphore,
                     vk::Fence::null()
                 )
-                .expect("Failed to acquire next image");
-            image_index
         }
     }
 }